@@ -0,0 +1,1002 @@
+use std::ops::Range;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::color::{Color, Theme};
+use crate::io::{FrameFormat, FrameSink, ImageFormat, VideoSettings, load_pgm_matte};
+use crate::math::{Vec3, parse_vec3};
+use crate::mesh_cache;
+use crate::post::{Background, PixelArt, Sensor, Telescope, circle_matte, parse_palette};
+use crate::raster::{CullMode, DebugView, Framebuffer, StarField};
+use crate::scene::{Moon, ShepherdMoon};
+use crate::settings::{LightSource, Lighting, RenderSettings, Tessellation, ToonStyle, body_key};
+use crate::shaders::{Aurora, RingGap, RingStyle, Ringlet, Storm, ZonalProfile};
+use crate::timeline::Timeline;
+
+// Command line: an optional subcommand, then the switches for the default renders
+#[derive(Parser)]
+#[command(name = "shaders", about = "Software-rasterized procedural planets")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Switches for the default Solar System renders, animations and exports
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub switches: Vec<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render a single planet to an image file
+    Render(RenderArgs),
+    /// Render a planet spinning over a numbered sequence of frames, picking up where an interrupted run stopped
+    Animate(AnimateArgs),
+    /// Render a showcase spin of a planet, turning it while the light and shader time stay put
+    Turntable(TurntableArgs),
+    /// Show a planet in a window, re-rendered every frame as time advances
+    View(ViewArgs),
+    /// Render planets on request: POST JSON to http://127.0.0.1:PORT/render, get a PNG back
+    Serve(ServeArgs),
+    /// Render a scene description file, with every body where its orbit puts it
+    RenderScene(RenderSceneArgs),
+    /// Work with scene description files
+    Scene {
+        #[command(subcommand)]
+        action: SceneCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SceneCommand {
+    /// Check a scene file and report every problem in it
+    Validate { path: String },
+    /// Rewrite a scene file for the current shader schema (comments aren't kept)
+    Upgrade {
+        path: String,
+        /// Write the upgraded file here instead of over the original
+        #[arg(long)]
+        out: Option<String>,
+    },
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// Planet shader, e.g. volcanic, rocky_planet or hot_jupiter
+    #[arg(long)]
+    pub planet: String,
+    /// Output size as WIDTHxHEIGHT [default: 800x800]
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<Framebuffer>,
+    /// Shader time in seconds
+    #[arg(long, default_value_t = 0.0)]
+    pub time: f32,
+    /// Rotation about the spin axis, in radians
+    #[arg(long, default_value_t = 0.0)]
+    pub rotation: f32,
+    /// Output image; the format follows the extension (.png, .ppm)
+    #[arg(long)]
+    pub out: String,
+    /// Render and write the image this many rows at a time [default: whole frames up to 16 MP]
+    #[arg(long)]
+    pub band_rows: Option<usize>,
+    /// Render only the rectangle X,Y,WIDTHxHEIGHT and write it over that part of the existing --out image
+    #[arg(long, value_parser = parse_region)]
+    pub region: Option<Region>,
+    /// Render on one thread and on several, and fail unless the images are identical
+    #[arg(long)]
+    pub check_threads: bool,
+    /// Any of the default switches (lighting, theme, background...), after the ones above
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub switches: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct RenderSceneArgs {
+    /// Scene description file (TOML)
+    pub path: String,
+    /// Output size as WIDTHxHEIGHT [default: 800x800]
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<Framebuffer>,
+    /// Shader time in seconds, which also moves the bodies along their orbits
+    #[arg(long, default_value_t = 0.0)]
+    pub time: f32,
+    /// Rotation of every body about its spin axis, in radians
+    #[arg(long, default_value_t = 0.0)]
+    pub rotation: f32,
+    /// Output image; the format follows the extension (.png, .ppm)
+    #[arg(long)]
+    pub out: String,
+    /// Any of the default switches (lighting, theme, background...), after the ones above
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub switches: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct AnimateArgs {
+    /// Planet shader, e.g. volcanic, rocky_planet or hot_jupiter
+    #[arg(long)]
+    pub planet: String,
+    /// Frame size as WIDTHxHEIGHT [default: 800x800]
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<Framebuffer>,
+    /// Frames in the sequence; the planet makes one full turn over them
+    #[arg(long, default_value_t = 240)]
+    pub frames: usize,
+    /// Frames per second of shader time
+    #[arg(long, default_value_t = 24)]
+    pub fps: u32,
+    /// Directory for the numbered frames (frame_0000.png, ...)
+    #[arg(long)]
+    pub out: String,
+    /// Any of the default switches (lighting, theme, background...), after the ones above
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub switches: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct TurntableArgs {
+    /// Planet shader, e.g. volcanic, rocky_planet or hot_jupiter
+    #[arg(long)]
+    pub planet: String,
+    /// Frame size as WIDTHxHEIGHT [default: 800x800]
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<Framebuffer>,
+    /// Frames in the spin
+    #[arg(long, default_value_t = 120)]
+    pub frames: usize,
+    /// Turns the planet makes over the frames; whole turns loop
+    #[arg(long, default_value_t = 1.0)]
+    pub revolutions: f32,
+    /// Directory or file for the frames, as with --frames-out
+    #[arg(long)]
+    pub out: String,
+    /// Any of the default switches (lighting, theme, background...), after the ones above
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub switches: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ViewArgs {
+    /// Planet shader, e.g. volcanic, rocky_planet or hot_jupiter
+    #[arg(long)]
+    pub planet: String,
+    /// Window size as WIDTHxHEIGHT [default: 800x800]
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<Framebuffer>,
+    /// Shader seconds per second of wall-clock time
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f32,
+    /// Rotation about the spin axis, in radians per shader second
+    #[arg(long, default_value_t = 0.25)]
+    pub spin: f32,
+    /// Any of the default switches (lighting, theme, background...), after the ones above
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub switches: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Port to listen on, on the local machine only
+    #[arg(long, default_value_t = 7878)]
+    pub port: u16,
+    /// Let pages from this origin (e.g. http://localhost:8000) call the server from a browser
+    #[arg(long, value_parser = parse_origin)]
+    pub allow_origin: Option<String>,
+    /// Default switches for every request (lighting, theme, background...); a request's own come after them
+    #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub switches: Vec<String>,
+}
+
+impl RenderArgs {
+    pub fn options(&self) -> Vec<String> {
+        sized_switches(self.size, &self.switches)
+    }
+}
+
+impl RenderSceneArgs {
+    pub fn options(&self) -> Vec<String> {
+        sized_switches(self.size, &self.switches)
+    }
+}
+
+impl AnimateArgs {
+    // The frame count, rate and directory go through the shared switches too, so the frame
+    // sink is set up as for --animate
+    pub fn options(&self) -> Vec<String> {
+        let sequence = [
+            "--frames".to_string(),
+            self.frames.to_string(),
+            "--fps".to_string(),
+            self.fps.to_string(),
+            "--frames-out".to_string(),
+            self.out.clone(),
+        ];
+        let switches: Vec<String> = sequence.into_iter().chain(self.switches.iter().cloned()).collect();
+        sized_switches(self.size, &switches)
+    }
+}
+
+impl TurntableArgs {
+    // Like `AnimateArgs::options`, so the frame sink is set up as for --animate
+    pub fn options(&self) -> Vec<String> {
+        let sequence = ["--frames".to_string(), self.frames.to_string(), "--frames-out".to_string(), self.out.clone()];
+        let switches: Vec<String> = sequence.into_iter().chain(self.switches.iter().cloned()).collect();
+        sized_switches(self.size, &switches)
+    }
+}
+
+impl ViewArgs {
+    pub fn options(&self) -> Vec<String> {
+        sized_switches(self.size, &self.switches)
+    }
+}
+
+// The switches for `Options::parse`. --size goes through the shared switch, so everything sized
+// to the frame (like mattes) is built at the requested size.
+fn sized_switches(size: Option<Framebuffer>, switches: &[String]) -> Vec<String> {
+    let size = size.map(|size| ["--size".to_string(), format!("{}x{}", size.width, size.height)]);
+    size.into_iter().flatten().chain(switches.iter().cloned()).collect()
+}
+
+// Widest or tallest frame a size can ask for; `shaders render` draws anything past 16 MP a band of
+// rows at a time
+pub const MAX_SIZE: usize = 65536;
+
+pub fn parse_size(value: &str) -> Result<Framebuffer, String> {
+    let parsed = value.split_once('x').and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+    match parsed {
+        Some((width, height)) if width > MAX_SIZE || height > MAX_SIZE => {
+            Err(format!("{:?} is too large; frames go up to {}x{}", value, MAX_SIZE, MAX_SIZE))
+        }
+        Some((width, height)) if width > 0 && height > 0 => Ok(Framebuffer::new(width, height)),
+        _ => Err(format!("expected WIDTHxHEIGHT, got {:?}", value)),
+    }
+}
+
+// A rectangle of the frame, from `X,Y,WIDTHxHEIGHT`
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub columns: Range<usize>,
+    pub rows: Range<usize>,
+}
+
+pub fn parse_region(value: &str) -> Result<Region, String> {
+    let parsed = value.split_once(',').and_then(|(x, rest)| {
+        let (y, size) = rest.split_once(',')?;
+        Some((x.parse::<usize>().ok()?, y.parse::<usize>().ok()?, parse_size(size).ok()?))
+    });
+    let Some((x, y, size)) = parsed else {
+        return Err(format!("expected X,Y,WIDTHxHEIGHT, got {:?}", value));
+    };
+    match (x.checked_add(size.width), y.checked_add(size.height)) {
+        (Some(right), Some(bottom)) if right <= MAX_SIZE && bottom <= MAX_SIZE => {
+            Ok(Region { columns: x..right, rows: y..bottom })
+        }
+        _ => Err(format!("{:?} reaches past the largest frame, {}x{}", value, MAX_SIZE, MAX_SIZE)),
+    }
+}
+
+// A single web origin, scheme://host[:port], so it can go into a header as is
+pub fn parse_origin(value: &str) -> Result<String, String> {
+    let host = value.strip_prefix("http://").or_else(|| value.strip_prefix("https://"));
+    match host {
+        Some(host) if !host.is_empty() && host.chars().all(|c| c.is_ascii_alphanumeric() || "-.:[]".contains(c)) => {
+            Ok(value.to_string())
+        }
+        _ => Err(format!("expected an origin like http://localhost:8000, got {:?}", value)),
+    }
+}
+
+// Command-line switches
+pub struct Options {
+    pub heightmaps: bool,
+    pub texture: Option<String>,
+    pub texture_width: usize,
+    pub cube_map: bool,
+    pub texture_lit: bool,
+    pub transit: bool,
+    pub metadata: bool,
+    pub image_format: ImageFormat,
+    pub random_system: Option<u64>,
+    pub caption: bool,
+    pub animate: Option<String>,
+    pub animate_moon: bool,
+    pub moon_preset: String,
+    pub moon_distance: Option<(f32, Option<f32>)>,
+    pub moon_inclination: Option<f32>,
+    pub moon_period: Option<f32>,
+    // Where --spin-axis START:END swings the spin axis to by the last frame
+    pub spin_axis_end: Option<Vec3>,
+    // Keyframed camera, light and shader parameters for animations
+    pub timeline: Option<Timeline>,
+    pub icons: Option<String>,
+    pub frames: usize,
+    pub save_frames: bool,
+    pub frame_format: FrameFormat,
+    pub frames_out: Option<String>,
+    pub threads: usize,
+    // Meshes kept for reuse across frames and renders; 0 builds every mesh each time
+    pub mesh_cache: usize,
+    pub background: Background,
+    pub matte: Option<Vec<f32>>,
+    pub sizes: Vec<usize>,
+    pub render: RenderSettings,
+    pub profile: bool,
+    pub dry_run: bool,
+    // Net -v minus -q switches; see `logging::level_for`
+    pub verbosity: i32,
+    pub pixel_art: Option<PixelArt>,
+    pub telescope: Option<Telescope>,
+    pub sensor: Option<Sensor>,
+    pub sprite_size: usize,
+    pub video: VideoSettings,
+}
+
+impl Options {
+    // Frame output path for an animation: a directory, or a single file for Y4M streams, videos
+    // and sprite sheets (whose atlas goes next to the image as .json)
+    pub fn frame_sink(&self, default_name: &str) -> Result<FrameSink, String> {
+        let path = match &self.frames_out {
+            Some(path) => path.clone(),
+            None if self.frame_format == FrameFormat::Y4m => format!("screenshots/{}.y4m", default_name),
+            None if self.frame_format == FrameFormat::Sprites => format!("screenshots/{}.png", default_name),
+            None if self.frame_format == FrameFormat::Mp4 => format!("screenshots/{}.mp4", default_name),
+            None if self.frame_format == FrameFormat::WebM => format!("screenshots/{}.webm", default_name),
+            None => format!("screenshots/{}", default_name),
+        };
+        FrameSink::create(
+            self.frame_format,
+            &path,
+            self.render.framebuffer,
+            self.frames,
+            self.sprite_size,
+            self.background,
+            self.matte.clone(),
+            self.pixel_art.clone(),
+            self.telescope,
+            self.sensor,
+            &self.video,
+        )
+        .map_err(|error| format!("Could not open the frame output {}: {}", path, error))
+    }
+
+    // Every switch that is unknown or has a bad value is reported, one per line
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            heightmaps: false,
+            texture: None,
+            texture_width: 2048,
+            cube_map: false,
+            texture_lit: false,
+            transit: false,
+            metadata: false,
+            image_format: ImageFormat::Ppm,
+            random_system: None,
+            caption: false,
+            animate: None,
+            animate_moon: false,
+            moon_preset: "moon".to_string(),
+            moon_distance: None,
+            moon_inclination: None,
+            moon_period: None,
+            spin_axis_end: None,
+            timeline: None,
+            icons: None,
+            frames: 60,
+            save_frames: false,
+            frame_format: FrameFormat::Png,
+            frames_out: None,
+            threads: 0,
+            mesh_cache: mesh_cache::DEFAULT_CAPACITY,
+            background: Background::Solid(Color::new(0, 0, 0)),
+            matte: None,
+            sizes: Vec::new(),
+            render: RenderSettings::default(),
+            profile: false,
+            dry_run: false,
+            verbosity: 0,
+            pixel_art: None,
+            telescope: None,
+            sensor: None,
+            sprite_size: 128,
+            video: VideoSettings::default(),
+        };
+
+        // Any pixel-art switch turns the mode on with defaults for the others
+        fn pixel_art(options: &mut Options) -> &mut PixelArt {
+            options.pixel_art.get_or_insert_with(|| PixelArt {
+                size: 128,
+                palette: parse_palette("pico8").unwrap_or_default(),
+                dither: true,
+            })
+        }
+
+        fn aurora(options: &mut Options) -> &mut Aurora {
+            options.render.aurora.get_or_insert_with(Aurora::default)
+        }
+
+        fn sensor(options: &mut Options) -> &mut Sensor {
+            options.sensor.get_or_insert_with(Sensor::default)
+        }
+
+        fn telescope(options: &mut Options) -> &mut Telescope {
+            options.telescope.get_or_insert_with(|| Telescope::preset("jwst").unwrap())
+        }
+
+        let mut errors = Vec::new();
+        // Per-body lighting is resolved after the loop so it builds on the final scene lighting
+        let mut lighting_overrides = Vec::new();
+        // Mattes are built after the loop too, at the final frame size
+        let mut matte = None;
+
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--heightmaps" => options.heightmaps = true,
+                // A shader name, or "all"; the texture is twice as wide as it is tall
+                "--texture" => match args.next() {
+                    Some(name) => options.texture = Some(name),
+                    None => errors.push("--texture expects a shader name or all".to_string()),
+                },
+                // Six cube faces instead of one equirectangular map; each face is a quarter of
+                // --texture-width, which keeps the texel density at the equator the same
+                "--cube-map" => options.cube_map = true,
+                // Shade baked textures with the scene light instead of writing plain albedo
+                "--texture-lit" => options.texture_lit = true,
+                "--texture-width" => match args.next().and_then(|value| value.parse::<usize>().ok()) {
+                    Some(width) if width >= 2 => options.texture_width = width,
+                    _ => errors.push("--texture-width expects a width in pixels".to_string()),
+                },
+                "--transit" => options.transit = true,
+                "--metadata" => options.metadata = true,
+                // The seed is optional, so a switch right after it is left for the next round
+                "--random-system" => {
+                    let seed = args.next_if(|value| !value.starts_with("--"));
+                    match seed.as_deref().map_or(Some(1), |value| value.parse().ok()) {
+                        Some(seed) => options.random_system = Some(seed),
+                        None => errors.push("--random-system expects a numeric seed".to_string()),
+                    }
+                }
+                random if random.starts_with("--random-system=") => match random["--random-system=".len()..].parse() {
+                    Ok(seed) => options.random_system = Some(seed),
+                    Err(_) => errors.push("--random-system expects a numeric seed".to_string()),
+                },
+                "--caption" => options.caption = true,
+                "--animate" => match args.next() {
+                    Some(name) => options.animate = Some(name),
+                    None => errors.push("--animate expects a shader name".to_string()),
+                },
+                // Adds an orbiting moon to the animation
+                "--moon" => options.animate_moon = true,
+                // Which moon to add: moon, io or europa
+                "--moon-preset" => match args.next() {
+                    Some(name) if Moon::by_name(&name, 0.0).is_some() => {
+                        options.animate_moon = true;
+                        options.moon_preset = name;
+                    }
+                    _ => errors.push("--moon-preset expects moon, io or europa".to_string()),
+                },
+                // Orbit radius in planet radii; "START:END" moves the moon over the animation
+                "--moon-distance" => {
+                    let value = args.next().unwrap_or_default();
+                    let (start, end) = match value.split_once(':') {
+                        Some((start, end)) => (start.parse::<f32>().ok(), end.parse::<f32>().ok().map(Some)),
+                        None => (value.parse::<f32>().ok(), Some(None)),
+                    };
+                    match (start, end) {
+                        (Some(start), Some(end)) if start > 0.0 && end.is_none_or(|end| end > 0.0) => {
+                            options.animate_moon = true;
+                            options.moon_distance = Some((start, end));
+                        }
+                        _ => errors.push("--moon-distance expects a distance or START:END".to_string()),
+                    }
+                }
+                // Tilt of the moon's orbit against the planet's equator, in degrees
+                "--moon-inclination" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(degrees) if (-180.0..=180.0).contains(&degrees) => {
+                        options.animate_moon = true;
+                        options.moon_inclination = Some(degrees.to_radians());
+                    }
+                    _ => errors.push("--moon-inclination expects an angle in degrees, from -180 to 180".to_string()),
+                },
+                // Seconds per orbit: the moon follows its Keplerian orbit through the animation's
+                // time instead of keeping pace with the spin
+                "--moon-period" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(seconds) if seconds > 0.0 => {
+                        options.animate_moon = true;
+                        options.moon_period = Some(seconds);
+                    }
+                    _ => errors.push("--moon-period expects a positive number of seconds".to_string()),
+                },
+                // Output directory for the icon set, e.g. screenshots/icons; sizes come from --sizes when given
+                "--icons" => match args.next() {
+                    Some(dir) => options.icons = Some(dir),
+                    None => errors.push("--icons expects an output directory".to_string()),
+                },
+                "--frames" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(frames) if frames > 0 => options.frames = frames,
+                    _ => errors.push("--frames expects a number of frames".to_string()),
+                },
+                "--save-frames" => options.save_frames = true,
+                // Still renders as ppm (ASCII P3), p6 (binary PPM) or png
+                "--image-format" => match args.next().as_deref().and_then(ImageFormat::parse) {
+                    Some(format) => options.image_format = format,
+                    None => errors.push("--image-format expects ppm, p6 or png".to_string()),
+                },
+                "--frame-format" => match args.next().as_deref() {
+                    Some("png") => options.frame_format = FrameFormat::Png,
+                    Some("rgba") => options.frame_format = FrameFormat::Rgba,
+                    Some("y4m") => options.frame_format = FrameFormat::Y4m,
+                    Some("sprites") => options.frame_format = FrameFormat::Sprites,
+                    // Encoded by ffmpeg, which has to be on the PATH
+                    #[cfg(feature = "video")]
+                    Some("mp4") => options.frame_format = FrameFormat::Mp4,
+                    #[cfg(feature = "video")]
+                    Some("webm") => options.frame_format = FrameFormat::WebM,
+                    #[cfg(not(feature = "video"))]
+                    Some(format @ ("mp4" | "webm")) => {
+                        errors.push(format!("--frame-format {} needs a build with the `video` feature", format))
+                    }
+                    other => errors.push(format!("Unknown frame format: {:?}", other)),
+                },
+                "--frames-out" => match args.next() {
+                    Some(path) => options.frames_out = Some(path),
+                    None => errors.push("--frames-out expects a directory or file".to_string()),
+                },
+                // JSON file of keyframes that animations sample at every frame
+                "--timeline" => match args.next() {
+                    Some(path) => match Timeline::load(&path) {
+                        Ok(timeline) => options.timeline = Some(timeline),
+                        Err(error) => errors.push(format!("Could not load timeline {}: {}", path, error)),
+                    },
+                    None => errors.push("--timeline expects a JSON file".to_string()),
+                },
+                // Frame rate of Y4M and video output
+                "--fps" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(fps) if fps > 0 => options.video.fps = fps,
+                    _ => errors.push("--fps expects a frame rate".to_string()),
+                },
+                // Video bitrate in ffmpeg's notation, like 8M or 2500k
+                "--bitrate" => match args.next() {
+                    Some(bitrate) if !bitrate.is_empty() => options.video.bitrate = Some(bitrate),
+                    _ => errors.push("--bitrate expects a bitrate like 8M".to_string()),
+                },
+                // Cell size of each sprite-sheet frame in pixels
+                "--sprite-size" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(size) if size > 0 => options.sprite_size = size,
+                    _ => errors.push("--sprite-size expects a cell size in pixels".to_string()),
+                },
+                "--background" => match args.next().as_deref().and_then(Background::parse) {
+                    Some(background) => options.background = background,
+                    None => errors.push("Invalid background, expected transparent, #rrggbb or gradient:#rrggbb:#rrggbb".to_string()),
+                },
+                // "circle", "circle:<radius px>" or "circle:<radius px>:<feather px>", or a PGM mask file
+                "--matte" => match args.next() {
+                    Some(value) => matte = Some(value),
+                    None => errors.push("--matte needs circle[:radius[:feather]] or a PGM mask path".to_string()),
+                },
+                // Frame size in pixels, e.g. 1920x1080
+                "--size" => match args.next().as_deref().map(parse_size) {
+                    Some(Ok(size)) => options.render.framebuffer = size,
+                    Some(Err(error)) => errors.push(format!("Invalid --size: {}", error)),
+                    None => errors.push("--size expects WIDTHxHEIGHT".to_string()),
+                },
+                "--half-res" => options.render.half_res_shading = true,
+                "--checkerboard" => options.render.checkerboard = true,
+                "--taa" => options.render.temporal_aa = true,
+                "--smooth-limb" => options.render.smooth_limb = true,
+                // Mesh tessellation: draft halves every segment count, high doubles it
+                "--quality" => match args.next().as_deref().and_then(Tessellation::parse) {
+                    Some(quality) => options.render.tessellation = quality,
+                    None => errors.push("--quality expects draft, normal or high".to_string()),
+                },
+                "--cull" => match args.next().as_deref().and_then(CullMode::parse) {
+                    Some(cull) => options.render.cull = cull,
+                    None => errors.push("--cull expects none, back or front".to_string()),
+                },
+                // Where the scene is seen from and what at, in world units; the bodies sit around
+                // the origin with radius 1
+                "--camera" => match args.next().as_deref().and_then(parse_vec3) {
+                    Some(position) => options.render.camera.position = position,
+                    None => errors.push("--camera expects x,y,z".to_string()),
+                },
+                "--look-at" => match args.next().as_deref().and_then(parse_vec3) {
+                    Some(target) => options.render.camera.target = target,
+                    None => errors.push("--look-at expects x,y,z".to_string()),
+                },
+                "--camera-up" => match args.next().as_deref().and_then(parse_vec3) {
+                    Some(up) => options.render.camera.up = up,
+                    None => errors.push("--camera-up expects x,y,z".to_string()),
+                },
+                // Field of view in degrees across the shorter side; switches to a perspective projection
+                "--fov" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(degrees) if degrees > 0.0 && degrees < 180.0 => {
+                        options.render.camera.fov = Some(degrees.to_radians())
+                    }
+                    _ => errors.push("--fov expects degrees between 0 and 180".to_string()),
+                },
+                "--near" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(near) if near > 0.0 => options.render.camera.near = near,
+                    _ => errors.push("--near expects a positive distance".to_string()),
+                },
+                "--far" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(far) if far > 0.0 => options.render.camera.far = far,
+                    _ => errors.push("--far expects a positive distance".to_string()),
+                },
+                "--light-dir" => match args.next().as_deref().and_then(parse_vec3) {
+                    Some(direction) => options.render.light.source = LightSource::Direction(direction),
+                    None => errors.push("--light-dir expects x,y,z".to_string()),
+                },
+                // A point light in world units; the bodies sit around the origin with radius 1
+                "--light-pos" => match args.next().as_deref().and_then(parse_vec3) {
+                    Some(position) => options.render.light.source = LightSource::Position(position),
+                    None => errors.push("--light-pos expects x,y,z".to_string()),
+                },
+                "--light-color" => match args.next().as_deref().and_then(Color::from_hex) {
+                    Some(color) => options.render.light.color = color,
+                    None => errors.push("--light-color expects #rrggbb".to_string()),
+                },
+                "--light-intensity" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(intensity) if intensity >= 0.0 => options.render.light.intensity = intensity,
+                    _ => errors.push("--light-intensity expects a non-negative number".to_string()),
+                },
+                "--planet-shine" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(strength) if strength >= 0.0 => options.render.planet_shine = strength,
+                    _ => errors.push("--planet-shine expects a non-negative strength".to_string()),
+                },
+                "--ring-gaps" => match args.next().as_deref().and_then(RingGap::parse_list) {
+                    Some(gaps) => options.render.ring_gaps = gaps,
+                    None => errors.push("--ring-gaps expects saturn, none or name:radius:width[:softness],...".to_string()),
+                },
+                "--ringlets" => match args.next().as_deref().and_then(Ringlet::parse_list) {
+                    Some(ringlets) => options.render.ringlets = ringlets,
+                    None => errors.push("--ringlets expects saturn, none or radius:width[:eccentricity[:clumping[:seed]]],...".to_string()),
+                },
+                "--magnetosphere" => options.render.magnetosphere = true,
+                // Earth-like auroras; the --aurora-* switches move and tune them
+                "--aurora" => {
+                    aurora(&mut options);
+                }
+                // Magnetic pole as OFFSET:LONGITUDE, in degrees from the rotation pole
+                "--aurora-pole" => {
+                    let value = args.next().unwrap_or_default();
+                    let parsed = value.split_once(':').and_then(|(offset, longitude)| {
+                        Some((offset.parse::<f32>().ok()?, longitude.parse::<f32>().ok()?))
+                    });
+                    match parsed {
+                        Some((offset, longitude)) if (0.0..=90.0).contains(&offset) => {
+                            let aurora = aurora(&mut options);
+                            aurora.pole_offset = offset;
+                            aurora.pole_longitude = longitude;
+                        }
+                        _ => errors.push("--aurora-pole expects OFFSET:LONGITUDE in degrees, with an offset up to 90".to_string()),
+                    }
+                }
+                "--aurora-oval" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(radius) if radius > 0.0 && radius < 90.0 => aurora(&mut options).oval_radius = radius,
+                    _ => errors.push("--aurora-oval expects the oval's radius in degrees".to_string()),
+                },
+                // LEVEL or LEVEL:VARIABILITY, how much it flares over an animation
+                "--aurora-activity" => {
+                    let value = args.next().unwrap_or_default();
+                    let (level, variability) = match value.split_once(':') {
+                        Some((level, variability)) => (level.parse::<f32>().ok(), variability.parse::<f32>().ok()),
+                        None => (value.parse::<f32>().ok(), Some(aurora(&mut options).variability)),
+                    };
+                    match (level, variability) {
+                        (Some(level), Some(variability)) if level >= 0.0 && variability >= 0.0 => {
+                            let aurora = aurora(&mut options);
+                            aurora.activity = level;
+                            aurora.variability = variability;
+                        }
+                        _ => errors.push("--aurora-activity expects LEVEL[:VARIABILITY]".to_string()),
+                    }
+                }
+                // Background stars from a seed
+                "--stars" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(seed) => {
+                        let refraction = options.render.stars.map_or(1.0, |stars| stars.refraction);
+                        options.render.stars = Some(StarField { seed, refraction });
+                    }
+                    None => errors.push("--stars expects a seed".to_string()),
+                },
+                "--limb-refraction" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(refraction) if refraction >= 0.0 => {
+                        options.render.stars.get_or_insert(StarField { seed: 1, refraction }).refraction = refraction
+                    }
+                    _ => errors.push("--limb-refraction expects a non-negative strength".to_string()),
+                },
+                "--ring-style" => match args.next().as_deref().and_then(RingStyle::parse) {
+                    Some(style) => options.render.ring_style = style,
+                    None => errors.push("--ring-style expects rings or debris".to_string()),
+                },
+                "--shepherd-moons" => match args.next().as_deref().and_then(ShepherdMoon::parse_list) {
+                    Some(moons) => options.render.shepherd_moons = moons,
+                    None => errors.push("--shepherd-moons expects saturn, none or orbit_radius:radius[:angle],...".to_string()),
+                },
+                "--ring-thickness" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(thickness) if thickness >= 0.0 => options.render.ring_thickness = thickness,
+                    _ => errors.push("--ring-thickness expects a non-negative thickness".to_string()),
+                },
+                "--internal-heat" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(heat) if heat >= 0.0 => options.render.internal_heat = heat,
+                    _ => errors.push("--internal-heat expects a non-negative amount".to_string()),
+                },
+                "--equilibrium-temperature" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(kelvin) if kelvin > 0.0 => options.render.equilibrium_temperature = kelvin,
+                    _ => errors.push("--equilibrium-temperature expects a temperature in kelvin".to_string()),
+                },
+                // realistic, pastel, synthwave or monochrome, for every body in the run
+                "--theme" => match args.next().as_deref().and_then(Theme::parse) {
+                    Some(theme) => options.render.theme = theme,
+                    None => errors.push("--theme expects realistic, pastel, synthwave or monochrome".to_string()),
+                },
+                "--variation" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(amount) if (0.0..=1.0).contains(&amount) => options.render.variation = amount,
+                    _ => errors.push("--variation expects an amount from 0 to 1".to_string()),
+                },
+                "--cloud-altitude" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(altitude) if (0.0..1.0).contains(&altitude) => options.render.cloud_altitude = altitude,
+                    _ => errors.push("--cloud-altitude expects a height in planet radii, below 1".to_string()),
+                },
+                "--tilt" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(degrees) => options.render.axial_tilt = degrees.to_radians(),
+                    None => errors.push("--tilt expects an angle in degrees".to_string()),
+                },
+                // Direction of the spin axis before the tilt; "START:END" swings it over the animation
+                "--spin-axis" => {
+                    let value = args.next().unwrap_or_default();
+                    let (start, end) = match value.split_once(':') {
+                        Some((start, end)) => (parse_vec3(start), parse_vec3(end).map(Some)),
+                        None => (parse_vec3(&value), Some(None)),
+                    };
+                    let usable = |axis: &Vec3| axis.length() > 1e-6;
+                    match (start, end) {
+                        (Some(start), Some(end)) if usable(&start) && end.as_ref().is_none_or(usable) => {
+                            options.render.spin_axis = Some(start);
+                            options.spin_axis_end = end;
+                        }
+                        _ => errors.push("--spin-axis expects x,y,z or START:END".to_string()),
+                    }
+                }
+                "--bands" => match args.next().as_deref().and_then(ZonalProfile::parse) {
+                    Some(profile) => options.render.bands = Some(profile),
+                    None => errors.push("--bands expects jupiter, saturn, uranus, neptune or lat:width:#rrggbb,...".to_string()),
+                },
+                // "lat:lon:size:#rrggbb[:drift[:rotation]]", comma separated
+                "--storms" => {
+                    let value = args.next().unwrap_or_default();
+                    let storms: Option<Vec<Storm>> = value.split(',').filter(|storm| !storm.is_empty()).map(Storm::parse).collect();
+                    match storms {
+                        Some(storms) => options.render.storms = Some(storms),
+                        None => errors.push("--storms expects lat:lon:size:#rrggbb[:drift[:rotation]],...".to_string()),
+                    }
+                }
+                "--ambient" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(ambient) => options.render.lighting.ambient = ambient.clamp(0.0, 1.0),
+                    None => errors.push("--ambient expects a number between 0 and 1".to_string()),
+                },
+                // "density" or "density:start_z"
+                "--fog" => {
+                    let value = args.next().unwrap_or_default();
+                    let (density, start) = match value.split_once(':') {
+                        Some((density, start)) => (density.parse::<f32>().ok(), start.parse::<f32>().ok().map(Some)),
+                        None => (value.parse::<f32>().ok(), Some(None)),
+                    };
+                    match (density, start) {
+                        (Some(density), Some(start)) => {
+                            options.render.lighting.fog_density = density.max(0.0);
+                            if let Some(start) = start {
+                                options.render.lighting.fog_start = start;
+                            }
+                        }
+                        _ => errors.push("--fog expects a density, optionally followed by :start_z".to_string()),
+                    }
+                }
+                "--wrap" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(wrap) => options.render.lighting.wrap = wrap.clamp(0.0, 1.0),
+                    None => errors.push("--wrap expects a number between 0 and 1".to_string()),
+                },
+                // Per-body override, e.g. "moon:ambient=0.02" or "ring:ambient=0.4,wrap=0.5";
+                // keys not given fall back to the scene lighting
+                "--lighting" => match args.next() {
+                    Some(value) => lighting_overrides.push(value),
+                    None => errors.push("--lighting expects body:key=value,...".to_string()),
+                },
+                "--debug" => match args.next().as_deref() {
+                    Some("overdraw") => options.render.overdraw = true,
+                    value => match value.and_then(DebugView::parse) {
+                        Some(view) => options.render.debug = Some(view),
+                        None => errors.push(
+                            "Unknown debug view, expected wireframe, normals, depth, uv, intensity or overdraw"
+                        .to_string()),
+                    },
+                },
+                "--profile" => options.profile = true,
+                // Print the estimated render time and memory instead of rendering
+                "--dry-run" => options.dry_run = true,
+                // Low-resolution width, e.g. 128; see also --palette and --dither
+                "--pixel-art" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(size) => pixel_art(&mut options).size = size,
+                    None => errors.push("--pixel-art expects the low-resolution width in pixels".to_string()),
+                },
+                "--palette" => match args.next().as_deref().and_then(parse_palette) {
+                    Some(palette) => pixel_art(&mut options).palette = palette,
+                    None => errors.push("Invalid palette, expected pico8, db32 or #rrggbb,#rrggbb,...".to_string()),
+                },
+                "--dither" => match args.next().as_deref() {
+                    Some("bayer") => pixel_art(&mut options).dither = true,
+                    Some("none") => pixel_art(&mut options).dither = false,
+                    other => errors.push(format!("Unknown dither mode: {:?}", other)),
+                },
+                // Telescope look for bright sources: hst or jwst, tuned with the --psf-* switches
+                "--telescope" => match args.next().as_deref().and_then(Telescope::preset) {
+                    Some(telescope) => options.telescope = Some(telescope),
+                    None => errors.push("--telescope expects hst or jwst".to_string()),
+                },
+                "--psf-spikes" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(length) if length >= 0.0 => telescope(&mut options).spike_length = length,
+                    _ => errors.push("--psf-spikes expects the spike length in pixels".to_string()),
+                },
+                "--psf-halo" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(halo) if halo >= 0.0 => telescope(&mut options).halo = halo,
+                    _ => errors.push("--psf-halo expects a non-negative strength".to_string()),
+                },
+                "--psf-threshold" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(threshold) if (0.0..1.0).contains(&threshold) => telescope(&mut options).threshold = threshold,
+                    _ => errors.push("--psf-threshold expects a brightness from 0 up to 1".to_string()),
+                },
+                // Camera realism with default settings; the --sensor-* switches tune it
+                "--sensor" => {
+                    sensor(&mut options);
+                }
+                "--exposure" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(exposure) if exposure > 0.0 => sensor(&mut options).exposure = exposure,
+                    _ => errors.push("--exposure expects a positive relative exposure".to_string()),
+                },
+                "--sensor-read-noise" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(noise) if noise >= 0.0 => sensor(&mut options).read_noise = noise,
+                    _ => errors.push("--sensor-read-noise expects electrons per pixel".to_string()),
+                },
+                "--sensor-hot-pixels" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(fraction) if (0.0..=1.0).contains(&fraction) => sensor(&mut options).hot_pixels = fraction,
+                    _ => errors.push("--sensor-hot-pixels expects a fraction of pixels".to_string()),
+                },
+                "--sensor-vignetting" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(amount) if (0.0..=1.0).contains(&amount) => sensor(&mut options).vignetting = amount,
+                    _ => errors.push("--sensor-vignetting expects an amount from 0 to 1".to_string()),
+                },
+                "--sensor-bleed" => match args.next().as_deref() {
+                    Some("on") => sensor(&mut options).bleed = true,
+                    Some("off") => sensor(&mut options).bleed = false,
+                    other => errors.push(format!("--sensor-bleed expects on or off, got {:?}", other)),
+                },
+                "--sensor-seed" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(seed) => sensor(&mut options).seed = seed,
+                    None => errors.push("--sensor-seed expects a number".to_string()),
+                },
+                "--toon" => options.render.toon = Some(options.render.toon.unwrap_or_default()),
+                "--toon-bands" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(bands) => options.render.toon.get_or_insert_with(ToonStyle::default).bands = bands,
+                    None => errors.push("--toon-bands expects a number".to_string()),
+                },
+                "--sizes" => {
+                    let list = args.next().unwrap_or_default();
+                    let sizes: Option<Vec<usize>> = list.split(',').map(|size| size.trim().parse().ok().filter(|&size| size > 0)).collect();
+                    match sizes {
+                        Some(sizes) => options.sizes = sizes,
+                        None => errors.push("--sizes expects a comma-separated list of pixel sizes".to_string()),
+                    }
+                }
+                "--threads" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(threads) => options.threads = threads,
+                    None => errors.push("--threads expects a number of threads (0 for one per core)".to_string()),
+                },
+                "--mesh-cache" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(meshes) => options.mesh_cache = meshes,
+                    None => errors.push("--mesh-cache expects a number of meshes (0 turns the cache off)".to_string()),
+                },
+                // -v for debug logs (per-pass triangle counts, timings), -vv for trace logs
+                // (every near-plane clip); -q for warnings and errors only
+                "--verbose" => options.verbosity += 1,
+                "-q" | "--quiet" => options.verbosity -= 1,
+                flag if flag.len() > 1 && flag.starts_with('-') && flag[1..].chars().all(|c| c == 'v') => {
+                    options.verbosity += flag.len() as i32 - 1;
+                }
+                other => errors.push(format!("Unknown argument: {}", other)),
+            }
+        }
+
+        let camera = options.render.camera;
+        let back = camera.position - camera.target;
+        if back.length() < 1e-6 || camera.up.cross(&back).length() < 1e-6 {
+            errors.push("The camera needs to be apart from what it looks at, with --camera-up not along its view".to_string());
+        }
+        if camera.far <= camera.near {
+            errors.push("--far has to be beyond --near".to_string());
+        }
+        if let Some(Err(error)) = options.timeline.as_ref().map(|timeline| timeline.check_camera(&camera)) {
+            errors.push(format!("--timeline: {}", error));
+        }
+
+        let Framebuffer { width, height } = options.render.framebuffer;
+        // Extra sizes are downsampled from the render, so none can be wider than it. Icons are
+        // sized against their crop instead.
+        if options.icons.is_none() {
+            for &size in options.sizes.iter().filter(|&&size| size > width) {
+                errors.push(format!("--sizes {} is wider than the {}px render; raise --size to at least that", size, width));
+            }
+        }
+        match matte {
+            Some(value) if value.starts_with("circle") => {
+                let mut parts = value.split(':').skip(1).map(|v| v.parse::<f32>().ok());
+                // Just outside a unit sphere by default
+                let radius = parts.next().flatten().unwrap_or(1.05 * options.render.framebuffer.scale());
+                let feather = parts.next().flatten().unwrap_or(4.0);
+                options.matte = Some(circle_matte(width, height, radius, feather));
+            }
+            Some(path) => match load_pgm_matte(&path, width, height) {
+                Ok(mask) => options.matte = Some(mask),
+                Err(err) => errors.push(format!("Could not load matte {}: {}", path, err)),
+            },
+            None => {}
+        }
+
+        for value in lighting_overrides {
+            let parsed = value.split_once(':').and_then(|(body, settings)| {
+                Some((body_key(body).to_string(), Lighting::parse(settings, options.render.lighting)?))
+            });
+            match parsed {
+                Some(entry) => options.render.lighting_overrides.push(entry),
+                None => errors.push(format!(
+                    "Invalid --lighting {:?}, expected body:ambient=N,wrap=N,fog=N,specular=N,roughness=N",
+                    value
+                )),
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(options),
+            false => Err(errors.join("\n")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_need_two_positive_numbers_up_to_the_limit() {
+        let size = parse_size("800x600").unwrap();
+        assert_eq!((size.width, size.height), (800, 600));
+        let largest = parse_size(&format!("{}x1", MAX_SIZE)).unwrap();
+        assert_eq!(largest.width, MAX_SIZE);
+        for bad in ["", "x", "800", "800x", "x600", "0x600", "800x0", "-1x600", "800x600x2", "8e2x600"] {
+            assert!(parse_size(bad).is_err(), "{:?} parsed", bad);
+        }
+        assert!(parse_size(&format!("{}x1", MAX_SIZE + 1)).unwrap_err().contains("too large"));
+        assert!(parse_size(&format!("1x{}", usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn origins_are_one_scheme_and_host() {
+        assert_eq!(parse_origin("http://localhost:8000").unwrap(), "http://localhost:8000");
+        assert!(parse_origin("https://[::1]:3000").is_ok());
+        for bad in ["*", "null", "localhost:8000", "http://", "http://a/b", "http://a\r\nSet-Cookie: x", "ftp://a"] {
+            assert!(parse_origin(bad).is_err(), "{:?} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn regions_stay_inside_the_largest_frame() {
+        let region = parse_region("10,20,30x40").unwrap();
+        assert_eq!((region.columns, region.rows), (10..40, 20..60));
+        let edge = parse_region(&format!("{},0,1x1", MAX_SIZE - 1)).unwrap();
+        assert_eq!(edge.columns.end, MAX_SIZE);
+        assert!(parse_region(&format!("{},0,1x1", MAX_SIZE)).is_err());
+        assert!(parse_region(&format!("0,{},1x1", usize::MAX)).is_err());
+        for bad in ["", "1,2", "1,2,3", "a,2,3x3", "1,2,0x3", "1,-2,3x3"] {
+            assert!(parse_region(bad).is_err(), "{:?} parsed", bad);
+        }
+    }
+}
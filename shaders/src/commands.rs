@@ -0,0 +1,123 @@
+use crate::color::Color;
+use crate::geometry::{Camera, Mesh, Transform};
+use crate::raster::{BlendMode, Fragment, Material, RasterTarget, RgbaShader, draw};
+use crate::settings::Light;
+
+// One recorded draw: a mesh placed in the world, lit by `light`, shaded and blended per its
+// material. `shader_id` is the shader's slot for deferred (half-res) shading; draws with the same
+// shader can share one.
+#[derive(Clone, Copy)]
+pub struct DrawCommand<'a> {
+    pub mesh: &'a Mesh,
+    pub transform: Transform,
+    pub light: Light,
+    pub shader: &'a RgbaShader<'a>,
+    pub shader_id: usize,
+    pub material: Material,
+}
+
+impl DrawCommand<'_> {
+    // How near the mesh's origin is to the camera, in view-space z: larger is nearer
+    pub fn depth(&self, camera: &Camera) -> f32 {
+        camera.apply(&self.transform.offset).z
+    }
+}
+
+// What a frame is made of, recorded by the renderers in scene order and played back by a
+// `Backend`. Recording first leaves the backend free to reorder, batch or skip draws: opaque
+// surfaces can go in any order since the depth buffer settles them, while blended ones must
+// stay in the order they were recorded.
+#[derive(Default)]
+pub struct CommandList<'a> {
+    pub commands: Vec<DrawCommand<'a>>,
+}
+
+impl<'a> CommandList<'a> {
+    pub fn new() -> Self {
+        CommandList::default()
+    }
+
+    pub fn draw(
+        &mut self,
+        mesh: &'a Mesh,
+        transform: &Transform,
+        light: &Light,
+        shader: &'a RgbaShader<'a>,
+        shader_id: usize,
+        material: &Material,
+    ) {
+        self.commands.push(DrawCommand {
+            mesh,
+            transform: *transform,
+            light: *light,
+            shader,
+            shader_id,
+            material: *material,
+        });
+    }
+
+    // The draws that blend over what is behind them, and those that don't
+    pub fn blended(&self) -> impl Iterator<Item = &DrawCommand<'a>> {
+        self.commands.iter().filter(|command| matches!(command.material.blend, BlendMode::Alpha))
+    }
+
+    pub fn opaque(&self) -> impl Iterator<Item = &DrawCommand<'a>> {
+        self.commands.iter().filter(|command| matches!(command.material.blend, BlendMode::Opaque))
+    }
+
+    // The opaque draws nearest first, so the depth test turns away what they hide before it is
+    // shaded. Changing shaders costs the rasterizer nothing, so depth comes first and draws of one
+    // shader only stay together where they tie.
+    pub fn opaque_front_to_back(&self, camera: &Camera) -> Vec<&DrawCommand<'a>> {
+        let mut commands: Vec<_> = self.opaque().collect();
+        commands.sort_by(|a, b| b.depth(camera).total_cmp(&a.depth(camera)).then(a.shader_id.cmp(&b.shader_id)));
+        commands
+    }
+
+    // The blended draws farthest first, as compositing needs; ties (like a ring and its edges)
+    // keep the order they were recorded in
+    pub fn blended_back_to_front(&self, camera: &Camera) -> Vec<&DrawCommand<'a>> {
+        let mut commands: Vec<_> = self.blended().collect();
+        commands.sort_by(|a, b| a.depth(camera).total_cmp(&b.depth(camera)));
+        commands
+    }
+
+    // The shader in each of the opaque draws' slots, for resolving deferred shading. Slots no
+    // opaque draw uses are never looked up.
+    pub fn opaque_shaders(&self) -> Vec<&'a RgbaShader<'a>> {
+        let mut shaders: Vec<&RgbaShader> = Vec::new();
+        for command in self.opaque() {
+            if shaders.len() <= command.shader_id {
+                shaders.resize(command.shader_id + 1, &unused_slot);
+            }
+            shaders[command.shader_id] = command.shader;
+        }
+        shaders
+    }
+}
+
+fn unused_slot(_: &Fragment) -> (Color, f32) {
+    (Color::new(0, 0, 0), 0.0)
+}
+
+// Whatever plays back a command list: the CPU rasterizer for now, a GPU one later
+pub trait Backend {
+    fn execute(&mut self, commands: &CommandList, time: f32);
+}
+
+// Opaque draws first, then their deferred shading is resolved, so the blended draws that follow
+// composite over finished colors
+impl Backend for RasterTarget {
+    fn execute(&mut self, commands: &CommandList, time: f32) {
+        let camera = self.camera;
+        for command in commands.opaque_front_to_back(&camera) {
+            let DrawCommand { mesh, transform, light, shader, shader_id, material } = command;
+            draw(self, mesh, transform, light, *shader, *shader_id, material, time);
+        }
+        self.resolve_half_res(&commands.opaque_shaders());
+        for command in commands.blended_back_to_front(&camera) {
+            let DrawCommand { mesh, transform, light, shader, shader_id, material } = command;
+            draw(self, mesh, transform, light, *shader, *shader_id, material, time);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::mem::size_of;
+
+use crate::geometry::{Mesh, Transform, generate_ring, generate_ring_edge, generate_sphere};
+use crate::math::Vec3;
+use crate::raster::{CullMode, Fragment, Framebuffer};
+use crate::settings::RenderSettings;
+
+// Shading cost per covered pixel in nanoseconds, noise included, timed from single-threaded
+// animations in a release build on the reference machine. Shaders not listed cost like the rocky
+// planet.
+const SHADER_COST_NS: [(&str, f64); 12] = [
+    ("sun", 145.0),
+    ("rocky_planet", 305.0),
+    ("gas_giant", 110.0),
+    ("ice_giant", 90.0),
+    ("desert_planet", 185.0),
+    ("volcanic_planet", 190.0),
+    ("moon", 170.0),
+    ("europa", 1600.0),
+    ("hot_jupiter", 135.0),
+    ("rogue_planet", 230.0),
+    ("carbon_planet", 1170.0),
+    ("rings", 150.0),
+];
+
+// Edge tests, depth and blending per covered pixel
+const RASTER_COST_NS: f64 = 130.0;
+// Setup and clipping per triangle, whether or not it covers anything
+const TRIANGLE_COST_NS: f64 = 400.0;
+// Clearing, post-processing and encoding a whole animation frame, mostly hidden behind the next
+// frame's rendering
+pub const FRAME_OVERHEAD_MS: f64 = 25.0;
+// The same for a still, which is written out before the next render starts
+pub const STILL_OVERHEAD_MS: f64 = 80.0;
+
+// One mesh of a frame: how many triangles it sends down, and how many pixels they cover
+pub struct MeshWork {
+    pub label: String,
+    pub shader: &'static str,
+    pub triangles: usize,
+    pub pixels: f64,
+    // Pixels covered by the triangles facing away, for single-sided meshes; double-sided ones are
+    // drawn from both sides whatever the cull mode, and count them in `pixels`
+    pub back_pixels: Option<f64>,
+}
+
+impl MeshWork {
+    // A UV sphere as `generate_sphere` builds it
+    pub fn sphere(
+        label: &str,
+        shader: &'static str,
+        radius: f32,
+        segments: usize,
+        transform: &Transform,
+        size: Framebuffer,
+    ) -> MeshWork {
+        let triangles: Vec<[Vec3; 3]> = generate_sphere(radius, segments).triangles().collect();
+        MeshWork::from_triangles(label, shader, &triangles, transform, false, size)
+    }
+
+    // Flat ring strips as `generate_ring` builds them, plus the bands around their edges when
+    // the rings have a thickness; rings are drawn from both sides
+    pub fn rings(
+        label: &str,
+        inner_radius: f32,
+        outer_radius: f32,
+        thickness: f32,
+        segments: usize,
+        transform: &Transform,
+        size: Framebuffer,
+    ) -> MeshWork {
+        let mut meshes = vec![generate_ring(inner_radius, outer_radius, segments)];
+        if thickness > 0.0 {
+            meshes.push(generate_ring_edge(inner_radius, thickness, segments));
+            meshes.push(generate_ring_edge(outer_radius, thickness, segments));
+        }
+        let triangles: Vec<[Vec3; 3]> = meshes.iter().flat_map(Mesh::triangles).collect();
+        MeshWork::from_triangles(label, "rings", &triangles, transform, true, size)
+    }
+
+    fn from_triangles(
+        label: &str,
+        shader: &'static str,
+        triangles: &[[Vec3; 3]],
+        transform: &Transform,
+        double_sided: bool,
+        size: Framebuffer,
+    ) -> MeshWork {
+        // Under an orthographic projection a triangle covers half its normal's z in area
+        let scale = size.scale() as f64;
+        let (mut front, mut back) = (0.0, 0.0);
+        for [v1, v2, v3] in triangles {
+            let (v1, v2, v3) = (transform.apply(v1), transform.apply(v2), transform.apply(v3));
+            let z = (v2 - v1).cross(&(v3 - v1)).z as f64;
+            *if z >= 0.0 { &mut front } else { &mut back } += 0.5 * z.abs() * scale * scale;
+        }
+        let frame = size.pixels() as f64;
+        let (pixels, back_pixels) = if double_sided { (front + back, None) } else { (front, Some(back.min(frame))) };
+        MeshWork { label: label.to_string(), shader, triangles: triangles.len(), pixels: pixels.min(frame), back_pixels }
+    }
+
+    // Pixels rasterized with the given cull mode
+    pub fn covered(&self, cull: CullMode) -> f64 {
+        match (cull, self.back_pixels) {
+            (_, None) | (CullMode::Back, _) => self.pixels,
+            (CullMode::Front, Some(back)) => back,
+            (CullMode::None, Some(back)) => self.pixels + back,
+        }
+    }
+
+    fn cost_ns(&self, settings: &RenderSettings) -> (f64, f64) {
+        let shading = SHADER_COST_NS.iter().find(|(name, _)| *name == self.shader).map_or(305.0, |(_, cost)| *cost);
+        let covered = self.covered(settings.cull);
+        // Half-resolution shading runs the shader for one pixel in four
+        let shaded = if settings.half_res_shading { covered / 4.0 } else { covered };
+        (shaded * shading, covered * RASTER_COST_NS + self.triangles as f64 * TRIANGLE_COST_NS)
+    }
+}
+
+// Renders that draw the same meshes every frame, like one still or the frames of an animation
+pub struct Job {
+    pub label: String,
+    pub frames: usize,
+    pub meshes: Vec<MeshWork>,
+    // Fraction of the pixels shaded each frame; checkerboarded animations shade half
+    pub shading_fraction: f64,
+    pub overhead_ms: f64,
+}
+
+pub struct Estimate {
+    pub jobs: Vec<Job>,
+    // Frames rendered at once
+    pub threads: usize,
+    // Memory held by anything other than the frames in flight, like a sprite sheet's atlas
+    pub extra_bytes: usize,
+    // Frames waiting to be encoded, on top of the ones being rendered
+    pub queued_frames: usize,
+}
+
+impl Estimate {
+    // (shading, rasterization, per-frame overhead) in seconds, spread over the render threads
+    pub fn seconds(&self, settings: &RenderSettings) -> (f64, f64, f64) {
+        let (mut shading, mut raster, mut overhead) = (0.0, 0.0, 0.0);
+        for job in &self.jobs {
+            for mesh in &job.meshes {
+                let (mesh_shading, mesh_raster) = mesh.cost_ns(settings);
+                shading += mesh_shading * job.shading_fraction * job.frames as f64;
+                raster += mesh_raster * job.frames as f64;
+            }
+            overhead += job.overhead_ms * 1e6 * job.frames as f64;
+        }
+        let threads = self.threads.max(1) as f64;
+        (shading / threads / 1e9, raster / threads / 1e9, overhead / threads / 1e9)
+    }
+
+    // Peak memory: a raster target per render thread, finished frames waiting for the encoders,
+    // and whatever the output holds on to. Renders done in bands only ever hold one band.
+    pub fn memory_bytes(&self, settings: &RenderSettings) -> usize {
+        let Framebuffer { width, height } = settings.framebuffer;
+        let rows = settings.rows.as_ref().map_or(height, |rows| rows.len());
+        let pixels = settings.columns.as_ref().map_or(width, |columns| columns.len()) * rows;
+        let mut per_target = pixels * (size_of::<u32>() + size_of::<f32>());
+        if settings.half_res_shading && settings.debug.is_none() {
+            per_target += pixels * size_of::<Option<(Fragment, usize)>>();
+        }
+        if settings.overdraw {
+            per_target += pixels * size_of::<u32>();
+        }
+        if settings.toon.is_some() {
+            per_target += pixels * size_of::<Vec3>();
+        }
+        // Post-processing works on a copy of the finished frame
+        let frame = pixels * size_of::<u32>() * 2;
+        self.threads.max(1) * (per_target + frame) + self.queued_frames * frame + self.extra_bytes
+    }
+
+    pub fn print(&self, settings: &RenderSettings) {
+        let frames: usize = self.jobs.iter().map(|job| job.frames).sum();
+        println!("Dry run: {} frame{}, {} render thread{}", frames, plural(frames), self.threads, plural(self.threads));
+        for job in &self.jobs {
+            println!("  {} ({} frame{})", job.label, job.frames, plural(job.frames));
+            for mesh in &job.meshes {
+                println!(
+                    "    {:<16} {:<16} {:>7} triangles {:>9.0} px",
+                    mesh.label,
+                    mesh.shader,
+                    mesh.triangles,
+                    mesh.covered(settings.cull)
+                );
+            }
+        }
+        let (shading, raster, overhead) = self.seconds(settings);
+        println!(
+            "Estimated time: {} (shading {}, rasterization {}, post-processing and encoding {})",
+            duration(shading + raster + overhead),
+            duration(shading),
+            duration(raster),
+            duration(overhead)
+        );
+        println!("Estimated peak memory: {:.1} MB", self.memory_bytes(settings) as f64 / (1024.0 * 1024.0));
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+fn duration(seconds: f64) -> String {
+    if seconds < 1.0 {
+        format!("{:.0} ms", seconds * 1000.0)
+    } else if seconds < 120.0 {
+        format!("{:.1} s", seconds)
+    } else if seconds < 7200.0 {
+        format!("{:.1} min", seconds / 60.0)
+    } else {
+        format!("{:.1} h", seconds / 3600.0)
+    }
+}
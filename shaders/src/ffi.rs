@@ -0,0 +1,120 @@
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use crate::io::unpremultiplied;
+use crate::server::render_request;
+
+// C ABI for embedding the renderer, built into a shared library with
+// `cargo rustc --lib --release --features ffi --crate-type cdylib`:
+//
+//     typedef struct { uint8_t *data; size_t len; uint32_t width; uint32_t height; } ShadersBuffer;
+//     int render_scene_json(const uint8_t *json, size_t len, ShadersBuffer *out_buf);
+//     void free_render_buffer(ShadersBuffer *buf);
+//
+// The JSON is the request `shaders serve` takes. On success `render_scene_json` returns 0 and
+// fills `out_buf` with the frame as straight RGBA, 4 bytes a pixel, row by row. Otherwise it
+// returns nonzero, and `out_buf` holds the UTF-8 error message with a width and height of 0.
+// Either way the buffer is the caller's to hand back to `free_render_buffer`. Requests are held to
+// what the server takes, frame size and switches alike, and anything past that is a bad request
+// before any of the frame is allocated: running out of memory would abort the host, which no
+// `catch_unwind` can stop.
+#[repr(C)]
+pub struct ShadersBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub const RENDER_OK: i32 = 0;
+pub const RENDER_BAD_REQUEST: i32 = 1;
+// The renderer panicked; the message is whatever the panic said
+pub const RENDER_FAILED: i32 = 2;
+
+impl ShadersBuffer {
+    fn new(bytes: Vec<u8>, width: usize, height: usize) -> Self {
+        let mut bytes = bytes.into_boxed_slice();
+        let (data, len) = (bytes.as_mut_ptr(), bytes.len());
+        std::mem::forget(bytes);
+        ShadersBuffer { data, len, width: width as u32, height: height as u32 }
+    }
+}
+
+/// # Safety
+/// `json` must point to `len` readable bytes, and `out_buf` to a `ShadersBuffer` to fill in.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn render_scene_json(json: *const u8, len: usize, out_buf: *mut ShadersBuffer) -> i32 {
+    if json.is_null() || out_buf.is_null() {
+        return RENDER_BAD_REQUEST;
+    }
+    let body = unsafe { std::slice::from_raw_parts(json, len) };
+    // Panics must not unwind into the caller's frames
+    let rendered = catch_unwind(AssertUnwindSafe(|| render_request(body, &[])));
+    let (status, buffer) = match rendered {
+        Ok(Ok(frame)) => {
+            let pixels: Vec<u8> = frame.buffer.iter().flat_map(|&pixel| unpremultiplied(pixel)).collect();
+            (RENDER_OK, ShadersBuffer::new(pixels, frame.width, frame.height))
+        }
+        Ok(Err(error)) => (RENDER_BAD_REQUEST, ShadersBuffer::new(error.into_bytes(), 0, 0)),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "the renderer panicked".to_string());
+            (RENDER_FAILED, ShadersBuffer::new(message.into_bytes(), 0, 0))
+        }
+    };
+    unsafe { out_buf.write(buffer) };
+    status
+}
+
+/// # Safety
+/// `buf` must have been filled in by `render_scene_json` and not freed since.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn free_render_buffer(buf: *mut ShadersBuffer) {
+    let Some(buf) = (unsafe { buf.as_mut() }) else {
+        return;
+    };
+    if !buf.data.is_null() {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(buf.data, buf.len)) });
+    }
+    *buf = ShadersBuffer { data: std::ptr::null_mut(), len: 0, width: 0, height: 0 };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(json: &str) -> (i32, String) {
+        let mut buffer = ShadersBuffer { data: std::ptr::null_mut(), len: 0, width: 0, height: 0 };
+        let status = unsafe { render_scene_json(json.as_ptr(), json.len(), &mut buffer) };
+        let message = match status {
+            RENDER_OK => String::new(),
+            _ => String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }).into_owned(),
+        };
+        unsafe { free_render_buffer(&mut buffer) };
+        (status, message)
+    }
+
+    #[test]
+    fn oversized_frames_are_bad_requests() {
+        assert_eq!(render(r#"{"planet": "rocky", "size": "60000x60000"}"#).0, RENDER_BAD_REQUEST);
+        assert_eq!(render(r#"{"planet": "rocky", "switches": ["--size", "60000x60000"]}"#).0, RENDER_BAD_REQUEST);
+    }
+
+    #[test]
+    fn file_switches_are_bad_requests() {
+        let (status, message) = render(r#"{"planet": "rocky", "switches": ["--matte", "/etc/passwd"]}"#);
+        assert_eq!(status, RENDER_BAD_REQUEST);
+        assert!(message.contains("--matte"), "{}", message);
+    }
+
+    #[test]
+    fn small_frames_render() {
+        let mut buffer = ShadersBuffer { data: std::ptr::null_mut(), len: 0, width: 0, height: 0 };
+        let json = r#"{"planet": "rocky", "size": "16x12"}"#;
+        let status = unsafe { render_scene_json(json.as_ptr(), json.len(), &mut buffer) };
+        assert_eq!((status, buffer.width, buffer.height, buffer.len), (RENDER_OK, 16, 12, 16 * 12 * 4));
+        unsafe { free_render_buffer(&mut buffer) };
+    }
+}
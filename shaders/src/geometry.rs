@@ -0,0 +1,383 @@
+use std::f32::consts::PI;
+
+use crate::math::{Mat4, Quat, Rng, Vec3};
+use crate::raster::{FAR_PLANE_Z, NEAR_PLANE_Z};
+use crate::scene::Moon;
+
+// Triangles over a shared vertex list, each vertex with its normal and texture coordinates.
+// Renderers walk `indices` in order, which matters for blended surfaces like rings.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    // Unshared corners with flat normals, for meshes that come as loose triangles
+    pub fn from_triangles(triangles: &[[Vec3; 3]]) -> Mesh {
+        let mut mesh = Mesh::default();
+        for (i, triangle) in triangles.iter().enumerate() {
+            let normal = (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0])).normalize();
+            mesh.vertices.extend_from_slice(triangle);
+            mesh.normals.extend([normal; 3]);
+            mesh.uvs.extend([(0.0, 0.0); 3]);
+            mesh.indices.push([i * 3, i * 3 + 1, i * 3 + 2]);
+        }
+        mesh
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.indices.iter().map(|&[a, b, c]| [self.vertices[a], self.vertices[b], self.vertices[c]])
+    }
+
+    // Distance of the first vertex from the origin: a sphere's radius
+    pub fn radius(&self) -> f32 {
+        self.vertices.first().map_or(1.0, |v| v.length())
+    }
+
+    // Two triangles for every quad of a grid `columns` vertices wide, so strips and
+    // latitude/longitude grids share their triangulation
+    fn grid_indices(columns: usize, rows: usize) -> Vec<[usize; 3]> {
+        let mut indices = Vec::with_capacity((columns - 1) * (rows - 1) * 2);
+        for row in 0..rows - 1 {
+            for column in 0..columns - 1 {
+                let idx = row * columns + column;
+                indices.push([idx, idx + 1, idx + columns]);
+                indices.push([idx + 1, idx + columns + 1, idx + columns]);
+            }
+        }
+        indices
+    }
+}
+
+// A UV sphere: rows of latitude from the north pole down, each a ring of longitudes with the
+// seam vertex repeated at the end
+pub fn generate_sphere(radius: f32, segments: usize) -> Mesh {
+    let mut mesh = Mesh::default();
+
+    for lat in 0..=segments {
+        let theta = PI * lat as f32 / segments as f32;
+        let sin_theta = theta.sin();
+        let cos_theta = theta.cos();
+
+        for lon in 0..=segments {
+            let phi = 2.0 * PI * lon as f32 / segments as f32;
+            let sin_phi = phi.sin();
+            let cos_phi = phi.cos();
+
+            let x = radius * sin_theta * cos_phi;
+            let y = radius * cos_theta;
+            let z = radius * sin_theta * sin_phi;
+
+            mesh.vertices.push(Vec3::new(x, y, z));
+            mesh.normals.push(Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi));
+            mesh.uvs.push((lon as f32 / segments as f32, lat as f32 / segments as f32));
+        }
+    }
+
+    mesh.indices = Mesh::grid_indices(segments + 1, segments + 1);
+    mesh
+}
+
+// Rubble of a tidally disrupted moon as small rocky octahedra, in the planet's frame before the
+// orbit turns: a clump where the moon was that stretches along the orbit into a thin ring as
+// `spread` goes from 0 to 1. The layout is seeded by the moon so animation frames stay coherent.
+pub fn generate_debris(moon: &Moon, spread: f32) -> Mesh {
+    let mut rng = Rng::new(debris_seed(moon));
+    let particles = 400;
+    let mut triangles = Vec::with_capacity(particles * 8);
+
+    for _ in 0..particles {
+        let angle = moon.orbit_angle + rng.range(-1.0, 1.0) * spread * PI;
+        let distance = moon.distance + rng.range(-1.0, 1.0) * moon.radius * (0.2 + spread);
+        let height = moon.height + rng.range(-1.0, 1.0) * moon.radius * 0.15;
+
+        // What is left of the moon's body shrinks as the arc grows
+        let clump = loop {
+            let p = Vec3::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0), rng.range(-1.0, 1.0));
+            if p.length() <= 1.0 {
+                break p * (moon.radius * (1.0 - spread));
+            }
+        };
+        let center = Vec3::new(distance * angle.cos(), height, distance * angle.sin()) + clump;
+        let size = moon.radius * rng.range(0.06, 0.16) * (1.0 - 0.4 * spread);
+
+        for sx in [-1.0, 1.0] {
+            for sy in [-1.0, 1.0] {
+                for sz in [-1.0, 1.0] {
+                    let a = center + Vec3::new(sx * size, 0.0, 0.0);
+                    let b = center + Vec3::new(0.0, sy * size, 0.0);
+                    let c = center + Vec3::new(0.0, 0.0, sz * size);
+                    // Wound so the face normal points away from the particle's center
+                    let outward = (b - a).cross(&(c - a)).dot(&Vec3::new(sx, sy, sz)) > 0.0;
+                    triangles.push(if outward { [a, b, c] } else { [a, c, b] });
+                }
+            }
+        }
+    }
+
+    Mesh::from_triangles(&triangles)
+}
+
+pub fn debris_seed(moon: &Moon) -> u64 {
+    moon.name.bytes().fold(0u64, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u64))
+}
+
+// A flat annulus in the xz plane, as (inner, outer) vertex pairs around the ring
+pub fn generate_ring(inner_radius: f32, outer_radius: f32, segments: usize) -> Mesh {
+    let mut mesh = Mesh::default();
+
+    for i in 0..=segments {
+        let angle = 2.0 * PI * i as f32 / segments as f32;
+        let cos_a = angle.cos();
+        let sin_a = angle.sin();
+        let u = i as f32 / segments as f32;
+
+        mesh.vertices.push(Vec3::new(inner_radius * cos_a, 0.0, inner_radius * sin_a));
+        mesh.vertices.push(Vec3::new(outer_radius * cos_a, 0.0, outer_radius * sin_a));
+        mesh.normals.extend([Vec3::new(0.0, 1.0, 0.0); 2]);
+        mesh.uvs.extend([(u, 0.0), (u, 1.0)]);
+    }
+
+    mesh.indices = Mesh::grid_indices(2, segments + 1);
+    mesh
+}
+
+// The vertical band around a ring's edge, as (bottom, top) pairs for the same strip layout as
+// `generate_ring`
+pub fn generate_ring_edge(radius: f32, thickness: f32, segments: usize) -> Mesh {
+    let half = thickness / 2.0;
+    let mut mesh = Mesh::default();
+
+    for i in 0..=segments {
+        let angle = 2.0 * PI * i as f32 / segments as f32;
+        let (x, z) = (radius * angle.cos(), radius * angle.sin());
+        let u = i as f32 / segments as f32;
+        mesh.vertices.push(Vec3::new(x, -half, z));
+        mesh.vertices.push(Vec3::new(x, half, z));
+        mesh.normals.extend([Vec3::new(angle.cos(), 0.0, angle.sin()); 2]);
+        mesh.uvs.extend([(u, 0.0), (u, 1.0)]);
+    }
+
+    mesh.indices = Mesh::grid_indices(2, segments + 1);
+    mesh
+}
+
+// Where the world is seen from: from `position` toward `target`, with `up` pointing to the top
+// of the frame. With a field of view the projection is perspective, and `near` and `far` are
+// distances from the position; without one it is the parallel projection the renderer started
+// with, where only the direction from the target matters. `zoom` magnifies the frame about its
+// center. View space has the target at the origin and the position on +z.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    // Across the shorter side of the frame, in radians
+    pub fov: Option<f32>,
+    pub near: f32,
+    pub far: f32,
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vec3::new(0.0, 0.0, 10.0),
+            target: Vec3::new(0.0, 0.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            fov: None,
+            near: 0.1,
+            far: 100.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    // The same camera swung around its target: turned about the vertical by `yaw`, then tipped
+    // over by `pitch`, as if the world had been turned the other way
+    pub fn orbited(&self, yaw: f32, pitch: f32) -> Camera {
+        let swing = |d: &Vec3| d.rotate_x(-pitch).rotate_y(-yaw);
+        Camera {
+            position: self.target + swing(&(self.position - self.target)),
+            up: swing(&self.up),
+            ..*self
+        }
+    }
+
+    // View space's x, y and z axes in the world
+    pub fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let back = (self.position - self.target).normalize();
+        let right = self.up.cross(&back).normalize();
+        (right, back.cross(&right), back)
+    }
+
+    // World to view: the target to the origin, then the basis onto the axes
+    pub fn view(&self) -> Mat4 {
+        let (right, up, back) = self.basis();
+        Mat4::from_rows(right, up, back).mul(&Mat4::translation(-self.target))
+    }
+
+    // View to world
+    pub fn inverse_view(&self) -> Mat4 {
+        let (right, up, back) = self.basis();
+        Mat4::translation(self.target).mul(&Mat4::from_rows(right, up, back).transpose())
+    }
+
+    // View space onto the plane of the frame, in the units of the parallel projection (where the
+    // shorter side of the frame spans 4), once divided by w
+    pub fn projection(&self) -> Mat4 {
+        let magnify = Mat4::scale(Vec3::new(self.zoom, self.zoom, 1.0));
+        match (self.fov, self.eye_z()) {
+            // The frame's shorter side is the field of view, so the aspect is 1
+            (Some(fov), Some(eye_z)) => magnify
+                .mul(&Mat4::scale(Vec3::new(2.0, 2.0, 1.0)))
+                .mul(&Mat4::perspective(fov, 1.0, self.near, self.far))
+                .mul(&Mat4::translation(Vec3::new(0.0, 0.0, -eye_z))),
+            _ => magnify,
+        }
+    }
+
+    pub fn apply(&self, p: &Vec3) -> Vec3 {
+        self.view().transform_point(p)
+    }
+
+    pub fn rotate(&self, d: &Vec3) -> Vec3 {
+        self.view().transform_direction(d)
+    }
+
+    pub fn invert(&self, p: &Vec3) -> Vec3 {
+        self.inverse_view().transform_point(p)
+    }
+
+    pub fn invert_direction(&self, d: &Vec3) -> Vec3 {
+        self.inverse_view().transform_direction(d)
+    }
+
+    // How far along view z the position is, for perspective cameras
+    pub fn eye_z(&self) -> Option<f32> {
+        self.fov.map(|_| (self.position - self.target).length())
+    }
+
+    // A view-space point on the plane of the frame; z is kept for depth
+    pub fn project(&self, p: &Vec3) -> Vec3 {
+        let projected = self.projection().project_point(p);
+        Vec3::new(projected.x, projected.y, p.z)
+    }
+
+    // The near and far planes as view-space z
+    pub fn near_z(&self) -> f32 {
+        self.eye_z().map_or(NEAR_PLANE_Z, |eye_z| eye_z - self.near)
+    }
+
+    pub fn far_z(&self) -> f32 {
+        self.eye_z().map_or(FAR_PLANE_Z, |eye_z| eye_z - self.far)
+    }
+
+    // 0 at the near plane to 1 at the far plane
+    pub fn normalized_depth(&self, z: f32) -> f32 {
+        let near_z = self.near_z();
+        ((near_z - z) / (near_z - self.far_z())).clamp(0.0, 1.0)
+    }
+
+    // Unit vector from a view-space point toward the eye
+    pub fn toward_eye(&self, p: &Vec3) -> Vec3 {
+        match self.eye_z() {
+            Some(eye_z) => Vec3::new(-p.x, -p.y, eye_z - p.z).normalize(),
+            None => Vec3::new(0.0, 0.0, 1.0),
+        }
+    }
+}
+
+// Places a body's object-space geometry in the world: spun about y, tilted, turned by
+// `orientation` (which carries the spin axis anywhere), then moved by `offset`
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub rotation: f32,
+    // Axial tilt: the north pole leans toward the camera by this angle
+    pub tilt: f32,
+    pub orientation: Quat,
+    pub offset: Vec3,
+}
+
+impl Transform {
+    pub fn new(rotation: f32, offset: Vec3) -> Self {
+        Transform { rotation, tilt: 0.0, orientation: Quat::identity(), offset }
+    }
+
+    pub fn spin(rotation: f32) -> Self {
+        Transform::new(rotation, Vec3::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn tilted(self, tilt: f32) -> Self {
+        Transform { tilt, ..self }
+    }
+
+    pub fn oriented(self, orientation: Quat) -> Self {
+        Transform { orientation, ..self }
+    }
+
+    // Object to world
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.offset)
+            .mul(&Mat4::rotation(&self.orientation))
+            .mul(&Mat4::rotation_x(self.tilt))
+            .mul(&Mat4::rotation_y(self.rotation))
+    }
+
+    // World to object
+    pub fn inverse(&self) -> Mat4 {
+        Mat4::rotation_y(-self.rotation)
+            .mul(&Mat4::rotation_x(-self.tilt))
+            .mul(&Mat4::rotation(&self.orientation.conjugate()))
+            .mul(&Mat4::translation(-self.offset))
+    }
+
+    pub fn apply(&self, p: &Vec3) -> Vec3 {
+        self.matrix().transform_point(p)
+    }
+
+    pub fn invert(&self, p: &Vec3) -> Vec3 {
+        self.inverse().transform_point(p)
+    }
+
+    pub fn invert_direction(&self, d: &Vec3) -> Vec3 {
+        self.inverse().transform_direction(d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_and_inverse_view_undo_each_other() {
+        let cameras = [
+            Camera::default(),
+            Camera {
+                position: Vec3::new(3.0, -2.0, 5.0),
+                target: Vec3::new(0.5, 1.0, -1.0),
+                up: Vec3::new(0.2, 1.0, 0.0),
+                ..Camera::default()
+            },
+            Camera::default().orbited(0.8, -0.4),
+        ];
+        for camera in cameras {
+            for product in [camera.view().mul(&camera.inverse_view()), camera.inverse_view().mul(&camera.view())] {
+                for (row, values) in product.m.iter().enumerate() {
+                    for (column, value) in values.iter().enumerate() {
+                        let expected = if row == column { 1.0 } else { 0.0 };
+                        assert!((value - expected).abs() < 1e-5, "{:?} at {}, {}", camera, row, column);
+                    }
+                }
+            }
+            // The target at the origin and the position on +z
+            let distance = (camera.position - camera.target).length();
+            assert!(camera.apply(&camera.target).length() < 1e-5);
+            assert!((camera.apply(&camera.position) - Vec3::new(0.0, 0.0, distance)).length() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::warn;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Exit status after an interrupted render, as for any program stopped by SIGINT
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+// Turns the first Ctrl-C into a request to stop: frames already being rendered are finished and
+// written, and the caller wraps up the output. A second Ctrl-C quits on the spot.
+pub fn install() {
+    let installed = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        warn!("Interrupted: finishing the frames in progress (Ctrl-C again to quit now)");
+    });
+    if let Err(error) = installed {
+        warn!("Could not install the Ctrl-C handler: {}", error);
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,1311 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "png")]
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+#[cfg(feature = "video")]
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Mutex;
+
+use crate::color::Color;
+use crate::geometry::Camera;
+use crate::json::Json;
+use crate::math::Vec3;
+use crate::post::{
+    Background, PixelArt, Sensor, Telescope, apply_background, apply_matte, apply_psf, apply_sensor, pixelate,
+};
+#[cfg(feature = "png")]
+use crate::post::{crop_to_aspect, downsample};
+use crate::profile::{Stage, profile};
+use crate::raster::{Fragment, Framebuffer};
+use crate::schema::SCHEMA_VERSION;
+use crate::settings::{Light, Lighting};
+
+// One row per frame: frame index, time, planet position, raw flux, and flux relative to the brightest frame
+pub fn save_light_curve_csv(filename: &str, samples: &[(f32, f32, f64)]) -> std::io::Result<()> {
+    let mut file = File::create(filename)?;
+    writeln!(file, "frame,time,planet_x,flux,relative_flux")?;
+
+    let baseline = samples.iter().map(|s| s.2).fold(0.0, f64::max);
+    for (frame, &(time, planet_x, flux)) in samples.iter().enumerate() {
+        let relative = if baseline > 0.0 { flux / baseline } else { 0.0 };
+        writeln!(file, "{},{:.4},{:.4},{:.3},{:.6}", frame, time, planet_x, flux, relative)?;
+    }
+
+    Ok(())
+}
+
+pub fn save_ppm(filename: &str, buffer: &[u32], width: usize, height: usize) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    writeln!(file, "P3")?;
+    writeln!(file, "{} {}", width, height)?;
+    writeln!(file, "255")?;
+    
+    for &pixel in buffer {
+        let r = (pixel >> 16) & 0xFF;
+        let g = (pixel >> 8) & 0xFF;
+        let b = pixel & 0xFF;
+        writeln!(file, "{} {} {}", r, g, b)?;
+    }
+    
+    file.flush()
+}
+
+// Binary PPM (P6): the same header, then three raw bytes per pixel, about a quarter the size of P3
+pub fn save_ppm_binary(filename: &str, buffer: &[u32], width: usize, height: usize) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    for &pixel in buffer {
+        file.write_all(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])?;
+    }
+    file.flush()
+}
+
+// File format for still renders. Renders with an alpha channel always go out as RGBA PNG.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    PpmBinary,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn parse(value: &str) -> Option<ImageFormat> {
+        match value {
+            "ppm" | "p3" => Some(ImageFormat::Ppm),
+            "p6" => Some(ImageFormat::PpmBinary),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm | ImageFormat::PpmBinary => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    // Writes an opaque render in this format
+    pub fn save(&self, filename: &str, buffer: &[u32], width: usize, height: usize) -> std::io::Result<()> {
+        match self {
+            ImageFormat::Ppm => save_ppm(filename, buffer, width, height),
+            ImageFormat::PpmBinary => save_ppm_binary(filename, buffer, width, height),
+            #[cfg(feature = "png")]
+            ImageFormat::Png => save_png(filename, buffer, width, height),
+            #[cfg(not(feature = "png"))]
+            ImageFormat::Png => Err(png_unsupported()),
+        }
+    }
+
+    // Opens `filename` for an image written a band of rows at a time; transparent images are
+    // always RGBA PNG
+    pub fn stream(&self, filename: &str, width: usize, height: usize, transparent: bool) -> std::io::Result<Box<dyn ImageStream>> {
+        Ok(match self {
+            #[cfg(feature = "png")]
+            _ if transparent => Box::new(PngStream::create(filename, width, height, true)?),
+            #[cfg(feature = "png")]
+            ImageFormat::Png => Box::new(PngStream::create(filename, width, height, false)?),
+            #[cfg(not(feature = "png"))]
+            _ if transparent => return Err(png_unsupported()),
+            #[cfg(not(feature = "png"))]
+            ImageFormat::Png => return Err(png_unsupported()),
+            ImageFormat::Ppm => Box::new(PpmStream::create(filename, width, height, false)?),
+            ImageFormat::PpmBinary => Box::new(PpmStream::create(filename, width, height, true)?),
+        })
+    }
+}
+
+// An image written to disk in bands of whole rows, top to bottom, so that only the band being
+// rendered has to be in memory. `finish` fails if fewer rows arrived than the image has.
+pub trait ImageStream {
+    fn write_rows(&mut self, rows: &[u32]) -> std::io::Result<()>;
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+// Counts the rows of a band against those the image still has room for
+fn take_rows(rows_left: &mut usize, pixels: usize, width: usize) -> std::io::Result<()> {
+    let rows = pixels / width.max(1);
+    if !pixels.is_multiple_of(width.max(1)) || rows > *rows_left {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "band does not fit the image"));
+    }
+    *rows_left -= rows;
+    Ok(())
+}
+
+fn check_all_rows(rows_left: usize) -> std::io::Result<()> {
+    if rows_left > 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, format!("{} rows never written", rows_left)));
+    }
+    Ok(())
+}
+
+pub struct PpmStream {
+    file: BufWriter<File>,
+    binary: bool,
+    width: usize,
+    rows_left: usize,
+}
+
+impl PpmStream {
+    pub fn create(filename: &str, width: usize, height: usize, binary: bool) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(filename)?);
+        write!(file, "{}\n{} {}\n255\n", if binary { "P6" } else { "P3" }, width, height)?;
+        Ok(PpmStream { file, binary, width, rows_left: height })
+    }
+}
+
+impl ImageStream for PpmStream {
+    fn write_rows(&mut self, rows: &[u32]) -> std::io::Result<()> {
+        take_rows(&mut self.rows_left, rows.len(), self.width)?;
+        for &pixel in rows {
+            let (r, g, b) = ((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8);
+            if self.binary {
+                self.file.write_all(&[r, g, b])?;
+            } else {
+                writeln!(self.file, "{} {} {}", r, g, b)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        check_all_rows(self.rows_left)?;
+        self.file.flush()
+    }
+}
+
+// The PNG encoder of `save_png_raw`, one IDAT chunk per band: the zlib stream is continued across
+// the chunks in stored blocks, with the running checksum written after the last band
+#[cfg(feature = "png")]
+pub struct PngStream {
+    file: File,
+    rgba: bool,
+    width: usize,
+    rows_left: usize,
+    adler: u32,
+}
+
+#[cfg(feature = "png")]
+impl PngStream {
+    pub fn create(filename: &str, width: usize, height: usize, rgba: bool) -> std::io::Result<Self> {
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        header.extend_from_slice(&[8, if rgba { PNG_RGBA } else { PNG_RGB }, 0, 0, 0]);
+
+        let mut file = File::create(filename)?;
+        file.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'])?;
+        write_png_chunk(&mut file, b"IHDR", &header)?;
+        write_png_chunk(&mut file, b"IDAT", &[0x78, 0x01])?;
+        Ok(PngStream { file, rgba, width, rows_left: height, adler: 1 })
+    }
+}
+
+#[cfg(feature = "png")]
+impl ImageStream for PngStream {
+    fn write_rows(&mut self, rows: &[u32]) -> std::io::Result<()> {
+        take_rows(&mut self.rows_left, rows.len(), self.width)?;
+        let mut scanlines = Vec::with_capacity(rows.len() * if self.rgba { 4 } else { 3 } + rows.len() / self.width.max(1));
+        for row in rows.chunks(self.width.max(1)) {
+            scanlines.push(0);
+            for &pixel in row {
+                if self.rgba {
+                    scanlines.extend_from_slice(&unpremultiplied(pixel));
+                } else {
+                    scanlines.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+                }
+            }
+        }
+        self.adler = adler32_update(self.adler, &scanlines);
+
+        let mut zlib = Vec::with_capacity(scanlines.len() + scanlines.len() / 65535 * 5 + 5);
+        for block in scanlines.chunks(65535) {
+            let len = block.len() as u16;
+            zlib.push(0);
+            zlib.extend_from_slice(&len.to_le_bytes());
+            zlib.extend_from_slice(&(!len).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        write_png_chunk(&mut self.file, b"IDAT", &zlib)
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        check_all_rows(self.rows_left)?;
+        // An empty final block closes the deflate stream
+        let mut end = vec![1, 0, 0, 0xFF, 0xFF];
+        end.extend_from_slice(&self.adler.to_be_bytes());
+        write_png_chunk(&mut self.file, b"IDAT", &end)?;
+        write_png_chunk(&mut self.file, b"IEND", &[])
+    }
+}
+
+// Everything needed to find and reproduce a render, written next to the image as JSON
+pub struct RenderMetadata {
+    pub body: String,
+    pub size: Framebuffer,
+    pub camera: Camera,
+    pub shader: &'static str,
+    pub seed: Option<u64>,
+    pub time: f32,
+    pub rotation: f32,
+    pub params: Vec<(&'static str, f32)>,
+    pub facts: Vec<(&'static str, String)>,
+    pub render_ms: f64,
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub fn save_metadata_json(filename: &str, image: &str, metadata: &RenderMetadata) -> std::io::Result<()> {
+    let params: Vec<String> = metadata
+        .params
+        .iter()
+        .map(|(key, value)| format!("    {}: {}", json_string(key), value))
+        .collect();
+
+    let mut file = File::create(filename)?;
+    writeln!(file, "{{")?;
+    writeln!(file, "  \"image\": {},", json_string(image))?;
+    writeln!(file, "  \"body\": {},", json_string(&metadata.body))?;
+    writeln!(file, "  \"shader\": {},", json_string(metadata.shader))?;
+    writeln!(file, "  \"shader_version\": {},", json_string(env!("CARGO_PKG_VERSION")))?;
+    writeln!(file, "  \"schema_version\": {},", SCHEMA_VERSION)?;
+    // Hand-placed bodies have no seed: they are reproducible from their parameters alone
+    match metadata.seed {
+        Some(seed) => writeln!(file, "  \"seed\": {},", seed)?,
+        None => writeln!(file, "  \"seed\": null,")?,
+    }
+    writeln!(file, "  \"time\": {},", metadata.time)?;
+    writeln!(file, "  \"rotation\": {},", metadata.rotation)?;
+    writeln!(file, "  \"params\": {{")?;
+    writeln!(file, "{}", params.join(",\n"))?;
+    writeln!(file, "  }},")?;
+    if !metadata.facts.is_empty() {
+        let facts: Vec<String> = metadata
+            .facts
+            .iter()
+            .map(|(key, value)| format!("    {}: {}", json_string(key), json_string(value)))
+            .collect();
+        writeln!(file, "  \"facts\": {{")?;
+        writeln!(file, "{}", facts.join(",\n"))?;
+        writeln!(file, "  }},")?;
+    }
+    let camera = &metadata.camera;
+    let point = |p: &Vec3| format!("[{}, {}, {}]", p.x, p.y, p.z);
+    writeln!(file, "  \"camera\": {{")?;
+    writeln!(file, "    \"projection\": {},", json_string(if camera.fov.is_some() { "perspective" } else { "orthographic" }))?;
+    writeln!(file, "    \"position\": {},", point(&camera.position))?;
+    writeln!(file, "    \"target\": {},", point(&camera.target))?;
+    writeln!(file, "    \"up\": {},", point(&camera.up))?;
+    match camera.fov {
+        Some(fov) => writeln!(file, "    \"fov_degrees\": {},", fov.to_degrees())?,
+        None => writeln!(file, "    \"fov_degrees\": null,")?,
+    }
+    writeln!(file, "    \"zoom\": {},", camera.zoom)?;
+    writeln!(file, "    \"scale\": {},", metadata.size.scale())?;
+    writeln!(file, "    \"width\": {},", metadata.size.width)?;
+    writeln!(file, "    \"height\": {}", metadata.size.height)?;
+    writeln!(file, "  }},")?;
+    writeln!(file, "  \"render_ms\": {:.1}", metadata.render_ms)?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+// PNG color types used by the writers below
+#[cfg(feature = "png")]
+pub const PNG_GRAYSCALE: u8 = 0;
+#[cfg(feature = "png")]
+pub const PNG_RGB: u8 = 2;
+#[cfg(feature = "png")]
+pub const PNG_RGBA: u8 = 6;
+
+// What the PNG readers and writers return in a build without them
+#[cfg(not(feature = "png"))]
+fn png_unsupported() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, "PNG images need a build with the `png` feature")
+}
+
+#[cfg(feature = "png")]
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(feature = "png")]
+pub fn adler32(bytes: &[u8]) -> u32 {
+    adler32_update(1, bytes)
+}
+
+#[cfg(feature = "png")]
+// Continues a checksum over more bytes, for data that arrives in pieces
+pub fn adler32_update(adler: u32, bytes: &[u8]) -> u32 {
+    let mut a = adler & 0xFFFF;
+    let mut b = adler >> 16;
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(feature = "png")]
+pub fn write_png_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut crc_input = Vec::with_capacity(data.len() + 4);
+    crc_input.extend_from_slice(kind);
+    crc_input.extend_from_slice(data);
+    file.write_all(&crc_input)?;
+    file.write_all(&crc32(&crc_input).to_be_bytes())
+}
+
+#[cfg(feature = "png")]
+pub fn save_png_raw(
+    filename: &str,
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    pixels: &[u8],
+) -> std::io::Result<()> {
+    std::fs::write(filename, png_bytes(width, height, bit_depth, color_type, pixels))
+}
+
+#[cfg(feature = "png")]
+// Minimal PNG encoder: unfiltered scanlines in uncompressed (stored) deflate blocks.
+// `pixels` holds tightly packed rows, with 16-bit samples in big-endian order.
+pub fn png_bytes(width: usize, height: usize, bit_depth: u8, color_type: u8, pixels: &[u8]) -> Vec<u8> {
+    let row_bytes = pixels.len() / height.max(1);
+
+    let mut scanlines = Vec::with_capacity(pixels.len() + height);
+    for row in pixels.chunks(row_bytes.max(1)) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = scanlines.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&scanlines).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+
+    // Writing to memory can't fail
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+    write_png_chunk(&mut png, b"IHDR", &header).unwrap();
+    write_png_chunk(&mut png, b"IDAT", &zlib).unwrap();
+    write_png_chunk(&mut png, b"IEND", &[]).unwrap();
+    png
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+// The four tokens of a PGM or PPM header (magic, width, height, max value), skipping comments,
+// and where the last one ends: binary pixel data starts one byte after it
+fn netpbm_header(data: &[u8]) -> (Vec<String>, usize) {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while tokens.len() < 4 && pos < data.len() {
+        if data[pos] == b'#' {
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+        } else if data[pos].is_ascii_whitespace() {
+            pos += 1;
+        } else {
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            tokens.push(String::from_utf8_lossy(&data[start..pos]).to_string());
+        }
+    }
+    (tokens, pos)
+}
+
+// Custom mask from a grayscale PGM (P2 or P5), sampled nearest-neighbor to the frame size
+pub fn load_pgm_matte(filename: &str, width: usize, height: usize) -> std::io::Result<Vec<f32>> {
+    let data = std::fs::read(filename)?;
+
+    let (tokens, pos) = netpbm_header(&data);
+    if tokens.len() < 4 {
+        return Err(invalid_data("truncated PGM header"));
+    }
+    let mask_width: usize = tokens[1].parse().map_err(|_| invalid_data("bad PGM width"))?;
+    let mask_height: usize = tokens[2].parse().map_err(|_| invalid_data("bad PGM height"))?;
+    let max_value: f32 = tokens[3].parse().map_err(|_| invalid_data("bad PGM max value"))?;
+
+    let values: Vec<f32> = match tokens[0].as_str() {
+        "P5" => data.get(pos + 1..).unwrap_or(&[]).iter().map(|&v| v as f32).collect(),
+        "P2" => String::from_utf8_lossy(&data[pos..])
+            .split_whitespace()
+            .filter_map(|v| v.parse().ok())
+            .collect(),
+        _ => return Err(invalid_data("only P2/P5 grayscale PGM masks are supported")),
+    };
+    if values.len() < mask_width * mask_height || mask_width == 0 || mask_height == 0 {
+        return Err(invalid_data("PGM mask has fewer pixels than its header declares"));
+    }
+
+    let mut mask = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let my = y * mask_height / height;
+        for x in 0..width {
+            let mx = x * mask_width / width;
+            mask.push((values[my * mask_width + mx] / max_value.max(1.0)).clamp(0.0, 1.0));
+        }
+    }
+    Ok(mask)
+}
+
+// An image written by `save_ppm`, `save_ppm_binary`, `save_png` or `save_png_rgba`, as straight
+// RGBA (opaque for formats without alpha). Only the uncompressed PNGs this program writes can be
+// read back, not PNGs in general.
+pub fn load_image(filename: &str) -> std::io::Result<(usize, usize, Vec<[u8; 4]>)> {
+    let data = std::fs::read(filename)?;
+    if !data.starts_with(&[0x89, b'P', b'N', b'G']) {
+        return load_ppm(&data);
+    }
+    #[cfg(feature = "png")]
+    return load_png(&data);
+    #[cfg(not(feature = "png"))]
+    return Err(png_unsupported());
+}
+
+fn load_ppm(data: &[u8]) -> std::io::Result<(usize, usize, Vec<[u8; 4]>)> {
+    let (tokens, pos) = netpbm_header(data);
+    if tokens.len() < 4 {
+        return Err(invalid_data("truncated PPM header"));
+    }
+    let width: usize = tokens[1].parse().map_err(|_| invalid_data("bad PPM width"))?;
+    let height: usize = tokens[2].parse().map_err(|_| invalid_data("bad PPM height"))?;
+    if tokens[3] != "255" {
+        return Err(invalid_data("only 8-bit PPM images are supported"));
+    }
+
+    let values: Vec<u8> = match tokens[0].as_str() {
+        "P6" => data.get(pos + 1..).unwrap_or(&[]).to_vec(),
+        "P3" => String::from_utf8_lossy(&data[pos..]).split_whitespace().filter_map(|v| v.parse().ok()).collect(),
+        _ => return Err(invalid_data("only P3/P6 color PPM images are supported")),
+    };
+    if values.len() < width * height * 3 {
+        return Err(invalid_data("PPM image has fewer pixels than its header declares"));
+    }
+    let pixels = values.chunks_exact(3).take(width * height).map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect();
+    Ok((width, height, pixels))
+}
+
+#[cfg(feature = "png")]
+fn load_png(data: &[u8]) -> std::io::Result<(usize, usize, Vec<[u8; 4]>)> {
+    let (mut header, mut zlib) = (None, Vec::new());
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let body = data.get(pos + 8..pos + 8 + length).ok_or_else(|| invalid_data("truncated PNG chunk"))?;
+        match &data[pos + 4..pos + 8] {
+            b"IHDR" if length == 13 => header = Some(body),
+            b"IDAT" => zlib.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos += length + 12;
+    }
+
+    let header = header.ok_or_else(|| invalid_data("PNG without a header"))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let channels = match (header[8], header[9], header[12]) {
+        (8, PNG_RGB, 0) => 3,
+        (8, PNG_RGBA, 0) => 4,
+        _ => return Err(invalid_data("only 8-bit RGB and RGBA PNG images without interlacing are supported")),
+    };
+
+    // Stored deflate blocks after the two-byte zlib header, until the one marked last
+    let mut scanlines = Vec::with_capacity(height * (width * channels + 1));
+    let mut pos = 2;
+    loop {
+        let block = zlib.get(pos..pos + 5).ok_or_else(|| invalid_data("truncated PNG image data"))?;
+        if block[0] & 0b110 != 0 {
+            return Err(invalid_data("compressed PNG image data is not supported"));
+        }
+        let len = u16::from_le_bytes([block[1], block[2]]) as usize;
+        let stored = zlib.get(pos + 5..pos + 5 + len).ok_or_else(|| invalid_data("truncated PNG image data"))?;
+        scanlines.extend_from_slice(stored);
+        pos += 5 + len;
+        if block[0] & 1 == 1 {
+            break;
+        }
+    }
+
+    let row_bytes = width * channels + 1;
+    if scanlines.len() < height * row_bytes {
+        return Err(invalid_data("PNG image has fewer pixels than its header declares"));
+    }
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in scanlines.chunks_exact(row_bytes).take(height) {
+        if row[0] != 0 {
+            return Err(invalid_data("filtered PNG scanlines are not supported"));
+        }
+        pixels.extend(row[1..].chunks_exact(channels).map(|p| [p[0], p[1], p[2], if channels == 4 { p[3] } else { 255 }]));
+    }
+    Ok((width, height, pixels))
+}
+
+// Raw frame dump: "BGRA" magic, little-endian u32 width and height, then the pixels as the
+// renderer keeps them, 0xAARRGGBB written little-endian, so B, G, R, A bytes. Nothing is converted
+// or copied on the way out.
+pub fn save_rgba_raw(filename: &str, buffer: &[u32], width: usize, height: usize) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(filename)?);
+    file.write_all(b"BGRA")?;
+    file.write_all(&(width as u32).to_le_bytes())?;
+    file.write_all(&(height as u32).to_le_bytes())?;
+    for pixel in buffer {
+        file.write_all(&pixel.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+// YUV4MPEG2 stream (4:4:4, BT.601 studio range) that any encoder can read from a file or a FIFO.
+// Frames may arrive out of order from the pipeline, so early ones wait until their turn.
+pub struct Y4mWriter {
+    pub out: BufWriter<File>,
+    pub width: usize,
+    pub height: usize,
+    pub next_frame: usize,
+    pub pending: BTreeMap<usize, Vec<u32>>,
+}
+
+impl Y4mWriter {
+    pub fn create(filename: &str, width: usize, height: usize, fps: u32) -> std::io::Result<Self> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        writeln!(out, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, fps)?;
+        Ok(Y4mWriter {
+            out,
+            width,
+            height,
+            next_frame: 0,
+            pending: BTreeMap::new(),
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: usize, buffer: &[u32]) -> std::io::Result<()> {
+        self.pending.insert(frame, buffer.to_vec());
+        while let Some(buffer) = self.pending.remove(&self.next_frame) {
+            self.write_planes(&buffer)?;
+            self.next_frame += 1;
+        }
+        Ok(())
+    }
+
+    pub fn write_planes(&mut self, buffer: &[u32]) -> std::io::Result<()> {
+        let plane = self.width * self.height;
+        let mut planes = vec![0u8; plane * 3];
+        for (i, &pixel) in buffer.iter().enumerate().take(plane) {
+            let r = ((pixel >> 16) & 0xFF) as f32 / 255.0;
+            let g = ((pixel >> 8) & 0xFF) as f32 / 255.0;
+            let b = (pixel & 0xFF) as f32 / 255.0;
+            planes[i] = (16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8;
+            planes[plane + i] = (128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8;
+            planes[2 * plane + i] = (128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8;
+        }
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&planes)
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        // Anything still pending had a gap before it; write it rather than dropping frames
+        let pending = std::mem::take(&mut self.pending);
+        for buffer in pending.values() {
+            self.write_planes(buffer)?;
+        }
+        self.out.flush()
+    }
+}
+
+// Frame rate of animation streams, and the bitrate handed to ffmpeg for video (like "8M"; ffmpeg
+// picks one if not given)
+#[derive(Clone)]
+pub struct VideoSettings {
+    pub fps: u32,
+    pub bitrate: Option<String>,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        VideoSettings { fps: 24, bitrate: None }
+    }
+}
+
+// MP4 (H.264) or WebM (VP9) through an `ffmpeg` child process, fed raw RGB frames on its stdin.
+// Frames are put back in order the same way as for `Y4mWriter`.
+#[cfg(feature = "video")]
+pub struct VideoExporter {
+    pub child: Child,
+    pub stdin: BufWriter<ChildStdin>,
+    pub next_frame: usize,
+    pub pending: BTreeMap<usize, Vec<u32>>,
+}
+
+#[cfg(feature = "video")]
+impl VideoExporter {
+    pub fn create(
+        filename: &str,
+        format: FrameFormat,
+        width: usize,
+        height: usize,
+        settings: &VideoSettings,
+    ) -> std::io::Result<Self> {
+        let codec: &[&str] = match format {
+            FrameFormat::WebM => &["-c:v", "libvpx-vp9"],
+            // Most players only take H.264 in 4:2:0, which needs even sizes; an odd width or
+            // height gets a black line of padding
+            _ => &["-c:v", "libx264", "-pix_fmt", "yuv420p", "-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"],
+        };
+        let mut command = Command::new("ffmpeg");
+        // Ctrl-C reaches the whole foreground process group. Keeping ffmpeg out of it lets an
+        // interrupted render close the video properly instead of ffmpeg quitting mid-stream.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        command
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", width, height), "-framerate", &settings.fps.to_string()])
+            .args(["-i", "-"])
+            .args(codec);
+        if let Some(bitrate) = &settings.bitrate {
+            command.args(["-b:v", bitrate]);
+        }
+        let mut child = command.arg(filename).stdin(Stdio::piped()).spawn().map_err(|error| {
+            std::io::Error::new(error.kind(), format!("could not start ffmpeg for video output: {}", error))
+        })?;
+        let stdin = BufWriter::new(child.stdin.take().expect("ffmpeg stdin is piped"));
+        Ok(VideoExporter { child, stdin, next_frame: 0, pending: BTreeMap::new() })
+    }
+
+    pub fn write_frame(&mut self, frame: usize, buffer: &[u32]) -> std::io::Result<()> {
+        self.pending.insert(frame, buffer.to_vec());
+        while let Some(buffer) = self.pending.remove(&self.next_frame) {
+            self.write_rgb(&buffer)?;
+            self.next_frame += 1;
+        }
+        Ok(())
+    }
+
+    pub fn write_rgb(&mut self, buffer: &[u32]) -> std::io::Result<()> {
+        let pixels: Vec<u8> =
+            buffer.iter().flat_map(|&pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]).collect();
+        self.stdin.write_all(&pixels)
+    }
+
+    // Closes ffmpeg's input and waits for it to finish the file
+    pub fn finish(mut self) -> std::io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        for buffer in pending.values() {
+            self.write_rgb(buffer)?;
+        }
+        self.stdin.flush()?;
+        drop(self.stdin);
+        let status = self.child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!("ffmpeg failed: {}", status)))
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+// Every frame shrunk into one cell of a grid image, plus a JSON atlas in the TexturePacker
+// array layout (with an Aseprite-style frame tag) that 2D engines import directly
+pub struct SpriteSheet {
+    pub image: String,
+    pub atlas: String,
+    pub cell: usize,
+    pub columns: usize,
+    pub frames: usize,
+    pub fps: u32,
+    pub pixels: Vec<u32>,
+}
+
+#[cfg(feature = "png")]
+impl SpriteSheet {
+    pub fn new(image: &str, frames: usize, cell: usize, fps: u32) -> Self {
+        let columns = (frames as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = frames.div_ceil(columns).max(1);
+        SpriteSheet {
+            image: image.to_string(),
+            atlas: std::path::Path::new(image).with_extension("json").to_string_lossy().into_owned(),
+            cell,
+            columns,
+            frames,
+            fps,
+            pixels: vec![0; columns * cell * rows * cell],
+        }
+    }
+
+    pub fn sheet_size(&self) -> (usize, usize) {
+        let rows = self.pixels.len() / (self.columns * self.cell * self.cell);
+        (self.columns * self.cell, rows * self.cell)
+    }
+
+    pub fn cell_origin(&self, frame: usize) -> (usize, usize) {
+        ((frame % self.columns) * self.cell, (frame / self.columns) * self.cell)
+    }
+
+    // Cells are square, so frames of any other shape lose their sides or top and bottom
+    pub fn write_frame(&mut self, frame: usize, buffer: &[u32], size: Framebuffer) {
+        if frame >= self.frames {
+            return;
+        }
+        let (square, side, _) = crop_to_aspect(buffer, size.width, size.height, 1, 1);
+        let cell = downsample(&square, side, side, self.cell, self.cell);
+        let (sheet_width, _) = self.sheet_size();
+        let (x0, y0) = self.cell_origin(frame);
+        for (row, pixels) in cell.chunks(self.cell).enumerate() {
+            let start = (y0 + row) * sheet_width + x0;
+            self.pixels[start..start + self.cell].copy_from_slice(pixels);
+        }
+    }
+
+    pub fn finish(self, transparent: bool) -> std::io::Result<()> {
+        let (width, height) = self.sheet_size();
+        if transparent {
+            save_png_rgba(&self.image, &self.pixels, width, height)?;
+        } else {
+            save_png(&self.image, &self.pixels, width, height)?;
+        }
+
+        let path = std::path::Path::new(&self.image);
+        let image_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        // Frames last as long as at the animation's frame rate, like the Y4M stream's
+        let duration_ms = (1000.0 / self.fps.max(1) as f32).round() as u32;
+        let frames: Vec<String> = (0..self.frames)
+            .map(|frame| {
+                let (x, y) = self.cell_origin(frame);
+                format!(
+                    "    {{ \"filename\": {}, \"frame\": {{ \"x\": {}, \"y\": {}, \"w\": {cell}, \"h\": {cell} }}, \
+                     \"rotated\": false, \"trimmed\": false, \
+                     \"spriteSourceSize\": {{ \"x\": 0, \"y\": 0, \"w\": {cell}, \"h\": {cell} }}, \
+                     \"sourceSize\": {{ \"w\": {cell}, \"h\": {cell} }}, \"duration\": {} }}",
+                    json_string(&format!("{}_{:04}", stem, frame)),
+                    x,
+                    y,
+                    duration_ms,
+                    cell = self.cell,
+                )
+            })
+            .collect();
+
+        let mut file = File::create(&self.atlas)?;
+        writeln!(file, "{{")?;
+        writeln!(file, "  \"frames\": [")?;
+        writeln!(file, "{}", frames.join(",\n"))?;
+        writeln!(file, "  ],")?;
+        writeln!(file, "  \"meta\": {{")?;
+        writeln!(file, "    \"app\": \"shaders\",")?;
+        writeln!(file, "    \"version\": {},", json_string(env!("CARGO_PKG_VERSION")))?;
+        writeln!(file, "    \"image\": {},", json_string(&image_name))?;
+        writeln!(file, "    \"format\": \"RGBA8888\",")?;
+        writeln!(file, "    \"size\": {{ \"w\": {}, \"h\": {} }},", width, height)?;
+        writeln!(file, "    \"scale\": \"1\",")?;
+        writeln!(
+            file,
+            "    \"frameTags\": [{{ \"name\": {}, \"from\": 0, \"to\": {}, \"direction\": \"forward\" }}]",
+            json_string(&stem),
+            self.frames.saturating_sub(1)
+        )?;
+        writeln!(file, "  }}")?;
+        writeln!(file, "}}")?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FrameFormat {
+    Png,
+    Rgba,
+    Y4m,
+    Sprites,
+    Mp4,
+    WebM,
+}
+
+impl FrameFormat {
+    // As given to --frame-format
+    pub fn name(self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Rgba => "rgba",
+            FrameFormat::Y4m => "y4m",
+            FrameFormat::Sprites => "sprites",
+            FrameFormat::Mp4 => "mp4",
+            FrameFormat::WebM => "webm",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<FrameFormat> {
+        [FrameFormat::Png, FrameFormat::Rgba, FrameFormat::Y4m, FrameFormat::Sprites, FrameFormat::Mp4, FrameFormat::WebM]
+            .into_iter()
+            .find(|format| format.name() == name)
+    }
+}
+
+// Where animation frames go: numbered files in a directory, a single ordered Y4M stream or
+// video, or a sprite sheet that is written once every frame is in
+pub enum FrameTarget {
+    Files { dir: String, format: FrameFormat },
+    Y4m(Mutex<Y4mWriter>),
+    #[cfg(feature = "video")]
+    Video(Mutex<VideoExporter>),
+    #[cfg(feature = "png")]
+    Sprites(Mutex<SpriteSheet>),
+}
+
+pub struct FrameSink {
+    pub target: FrameTarget,
+    pub format: FrameFormat,
+    // The directory or file the frames go to
+    pub path: String,
+    pub size: Framebuffer,
+    pub background: Background,
+    pub matte: Option<Vec<f32>>,
+    pub pixel_art: Option<PixelArt>,
+    pub telescope: Option<Telescope>,
+    pub sensor: Option<Sensor>,
+}
+
+impl FrameSink {
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(not(feature = "png"), allow(unused_variables))]
+    pub fn create(
+        format: FrameFormat,
+        path: &str,
+        size: Framebuffer,
+        frames: usize,
+        sprite_size: usize,
+        background: Background,
+        matte: Option<Vec<f32>>,
+        pixel_art: Option<PixelArt>,
+        telescope: Option<Telescope>,
+        sensor: Option<Sensor>,
+        video: &VideoSettings,
+    ) -> std::io::Result<Self> {
+        let target = match format {
+            FrameFormat::Y4m => {
+                FrameTarget::Y4m(Mutex::new(Y4mWriter::create(path, size.width, size.height, video.fps)?))
+            }
+            #[cfg(feature = "video")]
+            FrameFormat::Mp4 | FrameFormat::WebM => {
+                FrameTarget::Video(Mutex::new(VideoExporter::create(path, format, size.width, size.height, video)?))
+            }
+            #[cfg(not(feature = "video"))]
+            FrameFormat::Mp4 | FrameFormat::WebM => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "video output needs a build with the `video` feature",
+                ));
+            }
+            #[cfg(not(feature = "png"))]
+            FrameFormat::Png | FrameFormat::Sprites => return Err(png_unsupported()),
+            #[cfg(feature = "png")]
+            FrameFormat::Sprites => {
+                if let Some(dir) = std::path::Path::new(path).parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                FrameTarget::Sprites(Mutex::new(SpriteSheet::new(path, frames, sprite_size, video.fps)))
+            }
+            _ => {
+                std::fs::create_dir_all(path)?;
+                FrameTarget::Files { dir: path.to_string(), format }
+            }
+        };
+        Ok(FrameSink { target, format, path: path.to_string(), size, background, matte, pixel_art, telescope, sensor })
+    }
+
+    pub fn write(&self, frame: usize, buffer: &[u32]) -> std::io::Result<()> {
+        let mut buffer = buffer.to_vec();
+        let Framebuffer { width, height } = self.size;
+        #[cfg(feature = "png")]
+        let transparent = matches!(self.background, Background::Transparent);
+        profile(Stage::Blend, || {
+            if let Some(telescope) = &self.telescope {
+                apply_psf(&mut buffer, width, height, telescope);
+            }
+            if let Some(mask) = &self.matte {
+                apply_matte(&mut buffer, mask);
+            }
+            apply_background(&mut buffer, width, self.background);
+            if let Some(sensor) = &self.sensor {
+                apply_sensor(&mut buffer, width, height, sensor, frame as u64);
+            }
+            if let Some(style) = &self.pixel_art {
+                buffer = pixelate(&buffer, width, height, style);
+            }
+        });
+
+        profile(Stage::Encode, || match &self.target {
+            FrameTarget::Files { dir, format: FrameFormat::Rgba } => {
+                save_rgba_raw(&format!("{}/frame_{:04}.rgba", dir, frame), &buffer, width, height)
+            }
+            #[cfg(feature = "png")]
+            FrameTarget::Files { dir, .. } if transparent => {
+                save_png_rgba(&format!("{}/frame_{:04}.png", dir, frame), &buffer, width, height)
+            }
+            #[cfg(feature = "png")]
+            FrameTarget::Files { dir, .. } => {
+                save_png(&format!("{}/frame_{:04}.png", dir, frame), &buffer, width, height)
+            }
+            // Only raw frames get this far without PNG
+            #[cfg(not(feature = "png"))]
+            FrameTarget::Files { .. } => Err(png_unsupported()),
+            // Y4M has no alpha channel, so transparent areas come out black
+            FrameTarget::Y4m(writer) => writer.lock().unwrap().write_frame(frame, &buffer),
+            #[cfg(feature = "video")]
+            FrameTarget::Video(exporter) => exporter.lock().unwrap().write_frame(frame, &buffer),
+            #[cfg(feature = "png")]
+            FrameTarget::Sprites(sheet) => {
+                sheet.lock().unwrap().write_frame(frame, &buffer, self.size);
+                Ok(())
+            }
+        })
+    }
+
+    // Flushes the output once `frames` frames, counted from the first, have been written. That
+    // is fewer than asked for when the render was interrupted, and the sprite sheet's atlas then
+    // only lists those.
+    #[cfg_attr(not(feature = "png"), allow(unused_variables))]
+    pub fn finish(self, frames: usize) -> std::io::Result<()> {
+        #[cfg(feature = "png")]
+        let transparent = matches!(self.background, Background::Transparent);
+        match self.target {
+            FrameTarget::Y4m(writer) => writer.into_inner().unwrap().finish(),
+            #[cfg(feature = "video")]
+            FrameTarget::Video(exporter) => exporter.into_inner().unwrap().finish(),
+            #[cfg(feature = "png")]
+            FrameTarget::Sprites(sheet) => profile(Stage::Encode, || {
+                let mut sheet = sheet.into_inner().unwrap();
+                sheet.frames = sheet.frames.min(frames);
+                sheet.finish(transparent)
+            }),
+            FrameTarget::Files { .. } => Ok(()),
+        }
+    }
+
+    // Whether each frame is a file of its own, so an interrupted render can pick up where it
+    // stopped without rewriting the frames before
+    pub fn numbered(&self) -> bool {
+        matches!(self.target, FrameTarget::Files { .. })
+    }
+
+    // How far the frames got, for the manifest an interrupted render leaves behind
+    pub fn manifest(&self, frames: usize, completed: usize, command: Vec<String>) -> FrameManifest {
+        let path = match self.target {
+            FrameTarget::Files { .. } => format!("{}/manifest.json", self.path),
+            _ => std::path::Path::new(&self.path).with_extension("manifest.json").to_string_lossy().into_owned(),
+        };
+        FrameManifest { path, output: self.path.clone(), format: self.format, size: self.size, frames, completed, command }
+    }
+}
+
+// The state an interrupted animation is left in: frames 0 to `completed - 1` are in `output`,
+// and `command` is what started it. Saved inside a frame directory, or next to a single output
+// file.
+pub struct FrameManifest {
+    pub path: String,
+    pub output: String,
+    pub format: FrameFormat,
+    pub size: Framebuffer,
+    pub frames: usize,
+    pub completed: usize,
+    pub command: Vec<String>,
+}
+
+impl FrameManifest {
+    pub fn save(&self) -> std::io::Result<()> {
+        let command: Vec<String> = self.command.iter().map(|arg| json_string(arg)).collect();
+        let mut file = File::create(&self.path)?;
+        writeln!(file, "{{")?;
+        writeln!(file, "  \"output\": {},", json_string(&self.output))?;
+        writeln!(file, "  \"format\": {},", json_string(self.format.name()))?;
+        writeln!(file, "  \"width\": {},", self.size.width)?;
+        writeln!(file, "  \"height\": {},", self.size.height)?;
+        writeln!(file, "  \"frames\": {},", self.frames)?;
+        writeln!(file, "  \"completed_frames\": {},", self.completed)?;
+        writeln!(file, "  \"complete\": {},", self.completed >= self.frames)?;
+        writeln!(file, "  \"command\": [{}]", command.join(", "))?;
+        writeln!(file, "}}")?;
+        Ok(())
+    }
+
+    // The manifest saved at `path`, or None when there is none or it can't be read
+    pub fn load(path: &str) -> Option<FrameManifest> {
+        let json = Json::parse(&std::fs::read_to_string(path).ok()?).ok()?;
+        let number = |key: &str| json.get(key).and_then(Json::as_f64).map(|value| value as usize);
+        let command = match json.get("command")? {
+            Json::Array(items) => items.iter().map(|item| item.as_str().map(str::to_string)).collect::<Option<_>>()?,
+            _ => return None,
+        };
+        Some(FrameManifest {
+            path: path.to_string(),
+            output: json.get("output")?.as_str()?.to_string(),
+            format: FrameFormat::parse(json.get("format")?.as_str()?)?,
+            size: Framebuffer::new(number("width")?, number("height")?),
+            frames: number("frames")?,
+            completed: number("completed_frames")?,
+            command,
+        })
+    }
+
+    // Whether this is an earlier, unfinished run of the same render as `other`: the same output,
+    // frames and arguments (whatever the program was called as)
+    pub fn continues(&self, other: &FrameManifest) -> bool {
+        self.completed < self.frames
+            && self.output == other.output
+            && self.format == other.format
+            && self.size == other.size
+            && self.frames == other.frames
+            && self.command.get(1..) == other.command.get(1..)
+    }
+}
+
+#[cfg(feature = "png")]
+pub fn save_png(filename: &str, buffer: &[u32], width: usize, height: usize) -> std::io::Result<()> {
+    std::fs::write(filename, encode_png(buffer, width, height, false))
+}
+
+#[cfg(feature = "png")]
+// Straight (non-premultiplied) RGBA, for renders on a transparent background
+pub fn save_png_rgba(filename: &str, buffer: &[u32], width: usize, height: usize) -> std::io::Result<()> {
+    std::fs::write(filename, encode_png(buffer, width, height, true))
+}
+
+#[cfg(feature = "png")]
+// A finished frame as the bytes of an 8-bit PNG, RGBA or RGB
+pub fn encode_png(buffer: &[u32], width: usize, height: usize, rgba: bool) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(width * height * if rgba { 4 } else { 3 });
+    for &pixel in buffer {
+        if rgba {
+            pixels.extend_from_slice(&unpremultiplied(pixel));
+        } else {
+            pixels.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+        }
+    }
+    png_bytes(width, height, 8, if rgba { PNG_RGBA } else { PNG_RGB }, &pixels)
+}
+
+// A premultiplied pixel as straight RGBA bytes
+pub fn unpremultiplied(pixel: u32) -> [u8; 4] {
+    let alpha = (pixel >> 24) & 0xFF;
+    let unpremultiply = |shift: u32| {
+        let channel = (pixel >> shift) & 0xFF;
+        (channel * 255).checked_div(alpha).map_or(0, |value| value.min(255) as u8)
+    };
+    [unpremultiply(16), unpremultiply(8), unpremultiply(0), alpha as u8]
+}
+
+// Evaluates a shader over a width x height texel grid, 2x2 supersampled, as packed RGB bytes.
+// `direction` maps a texel-space sample to a point on the unit sphere. With `lighting` the
+// surface is shaded by the scene light; otherwise it is the full-intensity albedo.
+pub fn bake_surface(
+    shader: &dyn Fn(&Fragment) -> Color,
+    time: f32,
+    lighting: Option<(&Lighting, &Light)>,
+    width: usize,
+    height: usize,
+    direction: impl Fn(f32, f32) -> Vec3,
+) -> Vec<u8> {
+    let origin = Vec3::new(0.0, 0.0, 0.0);
+    let offsets = [0.25, 0.75];
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 3];
+            for dy in offsets {
+                for dx in offsets {
+                    let (u, v) = (x as f32 + dx, y as f32 + dy);
+                    let p = direction(u, v).normalize();
+                    let light_dir = lighting.map_or(p, |(_, light)| light.direction_from(&origin));
+                    let intensity = lighting.map_or(1.0, |(lighting, light)| {
+                        lighting.intensity(&p, &light_dir, light.intensity)
+                    });
+                    let color = shader(&Fragment {
+                        position: p,
+                        object_position: p,
+                        normal: p,
+                        intensity,
+                        time,
+                        screen: (u, v),
+                        depth: 0.0,
+                        heat: 0.0,
+                        light_dir,
+                        view_dir: p,
+                        specular: lighting.map_or(0.0, |(lighting, _)| lighting.specular),
+                        roughness: lighting.map_or(1.0, |(lighting, _)| lighting.roughness),
+                    });
+                    let color = lighting.map_or(color, |(_, light)| light.tint(color));
+                    sum[0] += color.r as f32;
+                    sum[1] += color.g as f32;
+                    sum[2] += color.b as f32;
+                }
+            }
+            pixels.extend(sum.map(|channel| (channel / 4.0).round() as u8));
+        }
+    }
+    pixels
+}
+
+#[cfg(feature = "png")]
+// Equirectangular bake. Every sample is a point on the unit sphere, so the texture wraps
+// seamlessly in longitude, and since no sample center lies exactly on a pole the pole rows
+// never hit a degenerate longitude.
+pub fn export_equirect_texture(
+    filename: &str,
+    shader: &dyn Fn(&Fragment) -> Color,
+    time: f32,
+    lighting: Option<(&Lighting, &Light)>,
+    width: usize,
+    height: usize,
+) -> std::io::Result<()> {
+    let pixels = bake_surface(shader, time, lighting, width, height, |u, v| {
+        let theta = PI * v / height as f32;
+        let phi = 2.0 * PI * u / width as f32;
+        Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+    });
+    save_png_raw(filename, width, height, 8, PNG_RGB, &pixels)
+}
+
+// Cube-map faces in the usual OpenGL order and orientation
+pub const CUBE_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// Direction through a face point, with s running left to right and t top to bottom, both in -1..1
+pub fn cube_face_direction(face: usize, s: f32, t: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -t, -s),
+        1 => Vec3::new(-1.0, -t, s),
+        2 => Vec3::new(s, 1.0, t),
+        3 => Vec3::new(s, -1.0, -t),
+        4 => Vec3::new(s, -t, 1.0),
+        _ => Vec3::new(-s, -t, -1.0),
+    }
+}
+
+#[cfg(feature = "png")]
+// Six square faces written as <prefix>_<face>.png; unlike the equirectangular map there is no
+// pole stretching, only a mild density change toward the face corners
+pub fn export_cube_map(
+    prefix: &str,
+    shader: &dyn Fn(&Fragment) -> Color,
+    time: f32,
+    lighting: Option<(&Lighting, &Light)>,
+    size: usize,
+) -> std::io::Result<()> {
+    for (face, suffix) in CUBE_FACES.iter().enumerate() {
+        let pixels = bake_surface(shader, time, lighting, size, size, |u, v| {
+            cube_face_direction(face, 2.0 * u / size as f32 - 1.0, 2.0 * v / size as f32 - 1.0)
+        });
+        save_png_raw(&format!("{}_{}.png", prefix, suffix), size, size, 8, PNG_RGB, &pixels)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "png")]
+// Samples a height function over an equirectangular longitude/latitude grid
+// and writes it as a 16-bit grayscale PNG, stretched to the full value range
+pub fn export_heightmap(
+    filename: &str,
+    height_fn: impl Fn(&Vec3) -> f32,
+    width: usize,
+    height: usize,
+) -> std::io::Result<()> {
+    let mut heights = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let theta = PI * (y as f32 + 0.5) / height as f32;
+        for x in 0..width {
+            let phi = 2.0 * PI * (x as f32 + 0.5) / width as f32;
+            let p = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+            heights.push(height_fn(&p));
+        }
+    }
+
+    let min = heights.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = heights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = if max > min { max - min } else { 1.0 };
+
+    let mut pixels = Vec::with_capacity(heights.len() * 2);
+    for h in heights {
+        let value = (((h - min) / range).clamp(0.0, 1.0) * 65535.0) as u16;
+        pixels.extend_from_slice(&value.to_be_bytes());
+    }
+
+    save_png_raw(filename, width, height, 16, PNG_GRAYSCALE, &pixels)
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn adler32_matches_known_values_in_one_piece_or_several() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // Long enough to wrap the sums several times over
+        let bytes = vec![0xFF; 100_000];
+        let whole = adler32(&bytes);
+        let pieces = bytes.chunks(7_000).fold(1, adler32_update);
+        assert_eq!(whole, pieces);
+    }
+
+    #[test]
+    fn png_chunks_carry_their_length_and_crc() {
+        let mut chunk = Vec::new();
+        write_png_chunk(&mut chunk, b"IEND", &[]).unwrap();
+        assert_eq!(chunk, [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+    }
+}
@@ -0,0 +1,248 @@
+// Just enough JSON to read render requests: every value the format has, with objects kept in the
+// order they were written
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { bytes: text.as_bytes(), pos: 0, depth: 0 };
+        let value = parser.nested()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.bytes.len() {
+            return Err(format!("unexpected {:?} after the value at byte {}", parser.bytes[parser.pos] as char, parser.pos));
+        }
+        Ok(value)
+    }
+
+    // The member called `key`, when this is an object that has one
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+}
+
+// Each array or object costs a level of recursion, so a run of brackets could otherwise exhaust the
+// stack before the input runs out
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.bytes.get(self.pos) {
+            Some(&byte) => format!("expected {} at byte {}, found {:?}", expected, self.pos, byte as char),
+            None => format!("expected {} at the end of the input", expected),
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("{:?}", byte as char)))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error(word))
+        }
+    }
+
+    fn nested(&mut self) -> Result<Json, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("values nested more than {} deep at byte {}", MAX_DEPTH, self.pos));
+        }
+        self.depth += 1;
+        let value = self.value();
+        self.depth -= 1;
+        value
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.nested()?);
+                    self.skip_whitespace();
+                    match self.bytes.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("',' or ']'")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.bytes.get(self.pos) != Some(&b'"') {
+                        return Err(self.error("a member name"));
+                    }
+                    let name = self.string()?;
+                    self.expect(b':')?;
+                    members.push((name, self.nested()?));
+                    self.skip_whitespace();
+                    match self.bytes.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.error("',' or '}'")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.pos;
+                while self.pos < self.bytes.len() && matches!(self.bytes[self.pos], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+                    self.pos += 1;
+                }
+                let text = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap();
+                text.parse().map(Json::Number).map_err(|_| format!("bad number {:?} at byte {}", text, start))
+            }
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    // A string starting at the opening quote
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut string = Vec::new();
+        loop {
+            match self.bytes.get(self.pos) {
+                None => return Err(self.error("the end of the string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return String::from_utf8(string).map_err(|_| "a string isn't valid UTF-8".to_string());
+                }
+                Some(b'\\') => {
+                    let escaped = match self.bytes.get(self.pos + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let hex = self.bytes.get(self.pos + 2..self.pos + 6).and_then(|hex| std::str::from_utf8(hex).ok());
+                            let code = hex.and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                            // Surrogate pairs aren't put back together; they come out as U+FFFD
+                            let character = code.map(|code| char::from_u32(code).unwrap_or('\u{fffd}'));
+                            self.pos += 4;
+                            character.ok_or_else(|| self.error("four hex digits"))?
+                        }
+                        _ => return Err(self.error("an escape")),
+                    };
+                    self.pos += 2;
+                    string.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(&byte) => {
+                    string.push(byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values_in_order() {
+        let json = Json::parse(r#" { "b": [1, -2.5e1, true, null], "a": "x\u0041\n" } "#).unwrap();
+        let Json::Object(members) = &json else { panic!("not an object") };
+        assert_eq!(members[0].0, "b");
+        assert_eq!(
+            json.get("b"),
+            Some(&Json::Array(vec![Json::Number(1.0), Json::Number(-25.0), Json::Bool(true), Json::Null]))
+        );
+        assert_eq!(json.get("a").and_then(Json::as_str), Some("xA\n"));
+    }
+
+    #[test]
+    fn reports_where_the_input_goes_wrong() {
+        let error = |text: &str| Json::parse(text).unwrap_err();
+        assert_eq!(error(""), "expected a value at the end of the input");
+        assert_eq!(error("[1, 2"), "expected ',' or ']' at the end of the input");
+        assert_eq!(error("{\"a\" 1}"), "expected ':' at byte 5, found '1'");
+        assert_eq!(error("{1: 2}"), "expected a member name at byte 1, found '1'");
+        assert_eq!(error("[1] 2"), "unexpected '2' after the value at byte 4");
+        assert_eq!(error("nul"), "expected null at byte 0, found 'n'");
+        assert_eq!(error("-"), "bad number \"-\" at byte 0");
+        assert_eq!(error("\"abc"), "expected the end of the string at the end of the input");
+        assert!(error("\"\\q\"").starts_with("expected an escape"));
+        assert!(error("\"\\u12\"").starts_with("expected four hex digits"));
+    }
+
+    #[test]
+    fn refuses_values_nested_too_deep() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(Json::parse(&nested(MAX_DEPTH + 1)).unwrap_err(), "values nested more than 64 deep at byte 64");
+        assert!(Json::parse(&"[{\"a\":".repeat(500_000)).unwrap_err().starts_with("values nested"));
+    }
+}
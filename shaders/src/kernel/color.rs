@@ -0,0 +1,106 @@
+#[cfg(not(feature = "std"))]
+use super::float::Float;
+
+// Color struct
+#[derive(Clone, Copy, Debug)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    pub fn from_float(r: f32, g: f32, b: f32) -> Self {
+        Color {
+            r: (r.clamp(0.0, 1.0) * 255.0) as u8,
+            g: (g.clamp(0.0, 1.0) * 255.0) as u8,
+            b: (b.clamp(0.0, 1.0) * 255.0) as u8,
+        }
+    }
+
+    pub fn mix(&self, other: &Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        Color::new(
+            ((self.r as f32) * (1.0 - t) + (other.r as f32) * t) as u8,
+            ((self.g as f32) * (1.0 - t) + (other.g as f32) * t) as u8,
+            ((self.b as f32) * (1.0 - t) + (other.b as f32) * t) as u8,
+        )
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let hex = hex.trim_start_matches('#');
+        if hex.len() != 6 {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        Some(Color::new((value >> 16) as u8, (value >> 8) as u8, value as u8))
+    }
+
+    // Packed as 0xAARRGGBB; shaded colors are always fully opaque
+    pub fn to_u32(self) -> u32 {
+        0xFF00_0000 | ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
+    }
+}
+
+// Named looks for a whole set of renders: every body's colors go through the same remap after
+// its shader, so the planets of a system or an icon set match each other
+#[derive(Clone, Copy, PartialEq)]
+pub enum Theme {
+    Realistic,
+    Pastel,
+    Synthwave,
+    Monochrome,
+}
+
+// Synthwave's ramp from shadow to highlight, as (brightness, color)
+const SYNTHWAVE_RAMP: [(f32, [f32; 3]); 4] = [
+    (0.0, [0.04, 0.0, 0.1]),
+    (0.3, [0.5, 0.05, 0.55]),
+    (0.65, [1.0, 0.3, 0.55]),
+    (1.0, [0.45, 0.95, 1.0]),
+];
+
+impl Theme {
+    pub fn parse(value: &str) -> Option<Theme> {
+        match value {
+            "realistic" => Some(Theme::Realistic),
+            "pastel" => Some(Theme::Pastel),
+            "synthwave" => Some(Theme::Synthwave),
+            "monochrome" | "mono" => Some(Theme::Monochrome),
+            _ => None,
+        }
+    }
+
+    // Remaps a shaded color. Every theme keeps its brightness ordering, so shading survives.
+    pub fn apply(&self, color: Color) -> Color {
+        let (r, g, b) = (color.r as f32 / 255.0, color.g as f32 / 255.0, color.b as f32 / 255.0);
+        let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        match self {
+            Theme::Realistic => color,
+            // Softer saturation, then lifted toward white more the brighter it is, so shadows stay dark
+            Theme::Pastel => {
+                let soften = |c: f32| {
+                    let c = luminance + (c - luminance) * 0.7;
+                    c + (1.0 - c) * 0.6 * luminance.sqrt()
+                };
+                Color::from_float(soften(r), soften(g), soften(b))
+            }
+            // Brightness read through the ramp, keeping a trace of the original hue
+            Theme::Synthwave => {
+                let upper = SYNTHWAVE_RAMP.iter().position(|&(stop, _)| stop >= luminance).unwrap_or(3).max(1);
+                let (low, low_color) = SYNTHWAVE_RAMP[upper - 1];
+                let (high, high_color) = SYNTHWAVE_RAMP[upper];
+                let t = ((luminance - low) / (high - low)).clamp(0.0, 1.0);
+                let channel = |i: usize, original: f32| {
+                    (low_color[i] + (high_color[i] - low_color[i]) * t) * 0.85 + original * 0.15
+                };
+                Color::from_float(channel(0, r), channel(1, g), channel(2, b))
+            }
+            Theme::Monochrome => Color::from_float(luminance, luminance, luminance),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use core::f32::consts::TAU;
+
+#[cfg(not(feature = "std"))]
+use super::float::Float;
+use super::math::Vec3;
+
+// Straight blend from `from` at t = 0 to `to` at t = 1; t outside that runs on past them
+pub fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+// How far `value` is from `from` to `to`, as lerp's t: 0 at `from`, 1 at `to`
+pub fn inverse_lerp(from: f32, to: f32, value: f32) -> f32 {
+    (value - from) / (to - from)
+}
+
+// `inverse_lerp` held to 0..1, for color ramps over one band of a noise value
+pub fn ramp(from: f32, to: f32, value: f32) -> f32 {
+    inverse_lerp(from, to, value).clamp(0.0, 1.0)
+}
+
+// Constant-speed turn from direction `from` at t = 0 to `to` at t = 1, the short way round, with
+// the length blended straight. Directions that nearly agree, or point exactly apart and so have
+// no one way round, are blended straight instead.
+pub fn slerp(from: &Vec3, to: &Vec3, t: f32) -> Vec3 {
+    let (from_length, to_length) = (from.length(), to.length());
+    if from_length == 0.0 || to_length == 0.0 {
+        return *from + (*to - *from) * t;
+    }
+    let cos_angle = (from.dot(to) / (from_length * to_length)).clamp(-1.0, 1.0);
+    if cos_angle.abs() > 0.9995 {
+        return *from + (*to - *from) * t;
+    }
+    let angle = cos_angle.acos();
+    let sin_angle = angle.sin();
+    let (a, b) = (((1.0 - t) * angle).sin() / sin_angle, (t * angle).sin() / sin_angle);
+    let direction = *from * (a / from_length) + *to * (b / to_length);
+    direction * lerp(from_length, to_length, t)
+}
+
+// The easing curves below all take t from 0 to 1 (held there) and give 0 at the start and 1 at
+// the end
+
+pub fn linear(t: f32) -> f32 {
+    t.clamp(0.0, 1.0)
+}
+
+// Starts and stops with no speed
+pub fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+pub fn ease_in_cubic(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * t
+}
+
+pub fn ease_out_cubic(t: f32) -> f32 {
+    1.0 - ease_in_cubic(1.0 - t)
+}
+
+pub fn ease_in_out_cubic(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.5 { 4.0 * t * t * t } else { 1.0 - ease_in_cubic(2.0 - 2.0 * t) / 2.0 }
+}
+
+// Overshoots the end and settles onto it in a few dying swings, like a plucked spring
+pub fn elastic(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    if t == 0.0 || t == 1.0 {
+        return t;
+    }
+    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * TAU / 3.0).sin() + 1.0
+}
+
+// One of the curves by name, for keys and settings that pick how to get from one value to the next
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    Smoothstep,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Elastic,
+}
+
+pub const EASING_NAMES: [&str; 6] = ["linear", "smoothstep", "ease_in", "ease_out", "ease_in_out", "elastic"];
+
+impl Easing {
+    pub fn parse(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "smoothstep" => Some(Easing::Smoothstep),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            "elastic" => Some(Easing::Elastic),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => linear(t),
+            Easing::Smoothstep => smoothstep(t),
+            Easing::EaseIn => ease_in_cubic(t),
+            Easing::EaseOut => ease_out_cubic(t),
+            Easing::EaseInOut => ease_in_out_cubic(t),
+            Easing::Elastic => elastic(t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easings_run_from_zero_to_one() {
+        for name in EASING_NAMES {
+            let easing = Easing::parse(name).unwrap();
+            assert_eq!(easing.apply(0.0), 0.0, "{} at 0", name);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{} at 1", name);
+            // Held there past either end
+            assert_eq!(easing.apply(-1.0), easing.apply(0.0), "{} before 0", name);
+            assert_eq!(easing.apply(2.0), easing.apply(1.0), "{} after 1", name);
+        }
+    }
+
+    #[test]
+    fn slerp_keeps_length_on_the_way_round() {
+        let (from, to) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let half = slerp(&from, &to, 0.5);
+        assert!((half.length() - 1.0).abs() < 1e-5);
+        assert!((half.x - half.z).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slerp_blends_opposite_directions_straight() {
+        let (from, to) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let start = slerp(&from, &to, 0.0);
+        let end = slerp(&from, &to, 1.0);
+        let half = slerp(&from, &to, 0.5);
+        assert_eq!((start.x, start.y, start.z), (1.0, 0.0, 0.0));
+        assert_eq!((end.x, end.y, end.z), (-1.0, 0.0, 0.0));
+        assert!(half.length() < 1e-6);
+    }
+}
@@ -0,0 +1,77 @@
+// Without std, `f32` has no transcendental functions of its own; this puts libm's behind the same
+// method names so the rest of `kernel` reads the same either way. With std the inherent methods win
+// and this isn't used.
+pub trait Float {
+    fn sqrt(self) -> f32;
+    fn sin(self) -> f32;
+    fn cos(self) -> f32;
+    fn sin_cos(self) -> (f32, f32);
+    fn tan(self) -> f32;
+    fn asin(self) -> f32;
+    fn acos(self) -> f32;
+    fn atan2(self, other: f32) -> f32;
+    fn exp(self) -> f32;
+    fn powf(self, n: f32) -> f32;
+    fn powi(self, n: i32) -> f32;
+    fn floor(self) -> f32;
+    fn fract(self) -> f32;
+    fn abs(self) -> f32;
+}
+
+impl Float for f32 {
+    fn sqrt(self) -> f32 {
+        libm::sqrtf(self)
+    }
+
+    fn sin(self) -> f32 {
+        libm::sinf(self)
+    }
+
+    fn cos(self) -> f32 {
+        libm::cosf(self)
+    }
+
+    fn sin_cos(self) -> (f32, f32) {
+        libm::sincosf(self)
+    }
+
+    fn tan(self) -> f32 {
+        libm::tanf(self)
+    }
+
+    fn asin(self) -> f32 {
+        libm::asinf(self)
+    }
+
+    fn acos(self) -> f32 {
+        libm::acosf(self)
+    }
+
+    fn atan2(self, other: f32) -> f32 {
+        libm::atan2f(self, other)
+    }
+
+    fn exp(self) -> f32 {
+        libm::expf(self)
+    }
+
+    fn powf(self, n: f32) -> f32 {
+        libm::powf(self, n)
+    }
+
+    fn powi(self, n: i32) -> f32 {
+        libm::powf(self, n as f32)
+    }
+
+    fn floor(self) -> f32 {
+        libm::floorf(self)
+    }
+
+    fn fract(self) -> f32 {
+        self - libm::truncf(self)
+    }
+
+    fn abs(self) -> f32 {
+        libm::fabsf(self)
+    }
+}
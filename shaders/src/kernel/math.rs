@@ -0,0 +1,456 @@
+use core::ops::{Add, AddAssign, Index, Mul, Neg, Sub};
+
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use super::float::Float;
+
+// 3D Vector
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    pub fn dot(&self, other: &Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn normalize(&self) -> Vec3 {
+        let len = self.length();
+        // Zero, denormal or non-finite lengths would turn into NaN/inf components
+        if len.is_finite() && len > 1e-12 {
+            Vec3::new(self.x / len, self.y / len, self.z / len)
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    pub fn rotate_y(&self, angle: f32) -> Vec3 {
+        let cos_a = angle.cos();
+        let sin_a = angle.sin();
+        Vec3::new(
+            self.x * cos_a + self.z * sin_a,
+            self.y,
+            -self.x * sin_a + self.z * cos_a,
+        )
+    }
+
+    pub fn rotate_x(&self, angle: f32) -> Vec3 {
+        let cos_a = angle.cos();
+        let sin_a = angle.sin();
+        Vec3::new(
+            self.x,
+            self.y * cos_a - self.z * sin_a,
+            self.y * sin_a + self.z * cos_a,
+        )
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, other: Vec3) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, scalar: f32) -> Vec3 {
+        Vec3::new(self.x * scalar, self.y * scalar, self.z * scalar)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+// Components by number: 0 is x, 1 is y, 2 is z
+impl Index<usize> for Vec3 {
+    type Output = f32;
+
+    fn index(&self, component: usize) -> &f32 {
+        match component {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 has no component {}", component),
+        }
+    }
+}
+
+// 4x4 matrix acting on column vectors, stored row by row. Products apply right to left, so
+// `a.mul(&b)` is b then a.
+#[derive(Clone, Copy, Debug)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Self {
+        Mat4::scale(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut matrix = Mat4::identity();
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        matrix
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        Mat4 {
+            m: [
+                [factors.x, 0.0, 0.0, 0.0],
+                [0.0, factors.y, 0.0, 0.0],
+                [0.0, 0.0, factors.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    // Same sense as `Vec3::rotate_x`
+    pub fn rotation_x(angle: f32) -> Self {
+        let (sin_a, cos_a) = angle.sin_cos();
+        Mat4 {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, cos_a, -sin_a, 0.0],
+                [0.0, sin_a, cos_a, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    // Same sense as `Vec3::rotate_y`
+    pub fn rotation_y(angle: f32) -> Self {
+        let (sin_a, cos_a) = angle.sin_cos();
+        Mat4 {
+            m: [
+                [cos_a, 0.0, sin_a, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [-sin_a, 0.0, cos_a, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn rotation(q: &Quat) -> Self {
+        let Quat { w, x, y, z } = *q;
+        Mat4 {
+            m: [
+                [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+                [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+                [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    // Rows are where the x, y and z axes of the new frame lie in the old one
+    pub fn from_rows(x: Vec3, y: Vec3, z: Vec3) -> Self {
+        Mat4 {
+            m: [
+                [x.x, x.y, x.z, 0.0],
+                [y.x, y.y, y.z, 0.0],
+                [z.x, z.y, z.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    // Looking down -z from the origin: x and y come out over w = -z, scaled so the field of view
+    // `fov` (radians, vertical) spans -1 to 1, and z maps the near and far distances to -1 and 1
+    pub fn perspective(fov: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let focal = 1.0 / (fov / 2.0).tan();
+        Mat4 {
+            m: [
+                [focal / aspect, 0.0, 0.0, 0.0],
+                [0.0, focal, 0.0, 0.0],
+                [0.0, 0.0, (far + near) / (near - far), 2.0 * far * near / (near - far)],
+                [0.0, 0.0, -1.0, 0.0],
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut matrix = *self;
+        for (row, values) in matrix.m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = self.m[column][row];
+            }
+        }
+        matrix
+    }
+
+    pub fn mul(&self, other: &Mat4) -> Mat4 {
+        let mut matrix = Mat4 { m: [[0.0; 4]; 4] };
+        for (row, values) in matrix.m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * other.m[k][column]).sum();
+            }
+        }
+        matrix
+    }
+
+    // The point (w = 1) through the matrix, without the perspective divide
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let row = |r: &[f32; 4]| p.x * r[0] + p.y * r[1] + p.z * r[2] + r[3];
+        Vec3::new(row(&self.m[0]), row(&self.m[1]), row(&self.m[2]))
+    }
+
+    // The direction (w = 0) through the matrix: translation doesn't apply
+    pub fn transform_direction(&self, d: &Vec3) -> Vec3 {
+        let row = |r: &[f32; 4]| d.x * r[0] + d.y * r[1] + d.z * r[2];
+        Vec3::new(row(&self.m[0]), row(&self.m[1]), row(&self.m[2]))
+    }
+
+    // The point through the matrix and divided by the w that comes out
+    pub fn project_point(&self, p: &Vec3) -> Vec3 {
+        let w = p.x * self.m[3][0] + p.y * self.m[3][1] + p.z * self.m[3][2] + self.m[3][3];
+        self.transform_point(p) * (1.0 / w)
+    }
+}
+
+// Unit quaternion for rotations about any axis. Rotations compose like matrices: `a.mul(&b)` is b
+// then a.
+#[derive(Clone, Copy, Debug)]
+pub struct Quat {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quat {
+    pub fn identity() -> Self {
+        Quat { w: 1.0, x: 0.0, y: 0.0, z: 0.0 }
+    }
+
+    // Right-handed, so about +y this turns the same way as `Vec3::rotate_y`
+    pub fn from_axis_angle(axis: &Vec3, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (sin_half, cos_half) = (angle / 2.0).sin_cos();
+        Quat { w: cos_half, x: axis.x * sin_half, y: axis.y * sin_half, z: axis.z * sin_half }
+    }
+
+    // The shortest turn taking the direction `from` onto `to`
+    pub fn between(from: &Vec3, to: &Vec3) -> Self {
+        let (from, to) = (from.normalize(), to.normalize());
+        let cos_angle = from.dot(&to).clamp(-1.0, 1.0);
+        if cos_angle < -0.9999 {
+            // Opposite directions: any axis at right angles will do
+            let across = if from.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+            return Quat::from_axis_angle(&from.cross(&across), core::f32::consts::PI);
+        }
+        let axis = from.cross(&to);
+        Quat { w: 1.0 + cos_angle, x: axis.x, y: axis.y, z: axis.z }.normalize()
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Quat { w: self.w, x: -self.x, y: -self.y, z: -self.z }
+    }
+
+    pub fn dot(&self, other: &Quat) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalize(&self) -> Self {
+        let len = self.dot(self).sqrt();
+        if len.is_finite() && len > 1e-12 {
+            Quat { w: self.w / len, x: self.x / len, y: self.y / len, z: self.z / len }
+        } else {
+            Quat::identity()
+        }
+    }
+
+    pub fn mul(&self, other: &Quat) -> Self {
+        Quat {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        // v + 2w (u x v) + 2u x (u x v), with u the vector part
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        *v + t * self.w + u.cross(&t)
+    }
+
+    // Constant-speed turn from `self` at t = 0 to `other` at t = 1, the short way round
+    pub fn slerp(&self, other: &Quat, t: f32) -> Self {
+        let mut cos_angle = self.dot(other);
+        let mut other = *other;
+        if cos_angle < 0.0 {
+            other = Quat { w: -other.w, x: -other.x, y: -other.y, z: -other.z };
+            cos_angle = -cos_angle;
+        }
+        // Nearly the same rotation: a straight blend is as good and avoids dividing by ~0
+        let (a, b) = if cos_angle > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos_angle.acos();
+            let sin_angle = angle.sin();
+            (((1.0 - t) * angle).sin() / sin_angle, (t * angle).sin() / sin_angle)
+        };
+        Quat {
+            w: self.w * a + other.w * b,
+            x: self.x * a + other.x * b,
+            y: self.y * a + other.y * b,
+            z: self.z * a + other.z * b,
+        }
+        .normalize()
+    }
+}
+
+// "x,y,z"
+pub fn parse_vec3(value: &str) -> Option<Vec3> {
+    let parts: Vec<f32> = value.split(',').map(|part| part.trim().parse().ok()).collect::<Option<_>>()?;
+    match parts[..] {
+        [x, y, z] if x.is_finite() && y.is_finite() && z.is_finite() => Some(Vec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+// Small deterministic xorshift generator for seeded random systems
+pub struct Rng {
+    pub state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 scramble so nearby seeds give unrelated sequences
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng { state: (z ^ (z >> 31)) | 1 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} is not {:?}", a, b);
+    }
+
+    fn directions() -> [Vec3; 5] {
+        [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.3, -0.8, 0.5).normalize(),
+            Vec3::new(-0.6, 0.2, 0.1).normalize(),
+        ]
+    }
+
+    #[test]
+    fn between_turns_one_direction_onto_the_other() {
+        for from in directions() {
+            // Every pair both ways round, the same direction, and straight back
+            for to in directions() {
+                assert_near(Quat::between(&from, &to).rotate(&from), to);
+            }
+            assert_near(Quat::between(&from, &-from).rotate(&from), -from);
+        }
+    }
+
+    #[test]
+    fn rotation_matrices_turn_points_like_their_quaternions() {
+        let q = Quat::from_axis_angle(&Vec3::new(1.0, 2.0, -0.5), 1.3)
+            .mul(&Quat::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), -0.4));
+        let matrix = Mat4::rotation(&q);
+        for p in directions() {
+            let p = p * 2.5;
+            assert_near(matrix.transform_point(&p), q.rotate(&p));
+            assert_near(q.conjugate().rotate(&q.rotate(&p)), p);
+            assert_near(Quat::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), 0.7).rotate(&p), p.rotate_y(0.7));
+        }
+    }
+
+    #[test]
+    fn slerp_turns_at_a_constant_rate() {
+        let axis = Vec3::new(0.2, 1.0, 0.4);
+        let (start, end) = (Quat::from_axis_angle(&axis, 0.3), Quat::from_axis_angle(&axis, 2.1));
+        for (t, angle) in [(0.0, 0.3), (0.25, 0.75), (0.5, 1.2), (1.0, 2.1)] {
+            let p = Vec3::new(1.0, 0.0, 0.0);
+            assert_near(start.slerp(&end, t).rotate(&p), Quat::from_axis_angle(&axis, angle).rotate(&p));
+        }
+    }
+
+    #[test]
+    fn perspective_maps_the_view_volume_onto_the_unit_cube() {
+        let (fov, aspect, near, far) = (1.1f32, 2.0, 0.5, 20.0);
+        let projection = Mat4::perspective(fov, aspect, near, far);
+        let edge = (fov / 2.0).tan();
+        for distance in [near, 3.0, far] {
+            let corner = Vec3::new(edge * aspect * distance, -edge * distance, -distance);
+            let projected = projection.project_point(&corner);
+            assert!((projected.x - 1.0).abs() < 1e-4 && (projected.y + 1.0).abs() < 1e-4, "{:?}", projected);
+        }
+        assert!((projection.project_point(&Vec3::new(0.0, 0.0, -near)).z + 1.0).abs() < 1e-4);
+        assert!((projection.project_point(&Vec3::new(0.0, 0.0, -far)).z - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,16 @@
+// The parts of the renderer that need nothing but `core` and `alloc`: vectors and colors, noise,
+// easing curves, Keplerian orbits, and the planet shaders, which only ever see a `Fragment`. They
+// build without std (with the `libm` feature standing in for its float functions), so the shaders
+// can run on embedded targets or in WASM, writing into whatever framebuffer the host has.
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("`kernel` needs the float functions of either the `std` or the `libm` feature");
+
+pub mod color;
+pub mod easing;
+#[cfg(not(feature = "std"))]
+pub mod float;
+pub mod math;
+pub mod noise;
+pub mod orbit;
+pub mod shaders;
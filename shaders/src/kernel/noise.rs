@@ -0,0 +1,134 @@
+use super::easing::{lerp, smoothstep};
+#[cfg(not(feature = "std"))]
+use super::float::Float;
+use super::math::Vec3;
+
+// Noise functions
+#[allow(clippy::excessive_precision)]
+pub fn noise_3d(p: &Vec3) -> f32 {
+    let x = p.x.sin() * 43758.5453;
+    let y = p.y.sin() * 22578.1459;
+    let z = p.z.sin() * 19134.3872;
+    (x + y + z).fract()
+}
+
+pub fn fbm(p: &Vec3, octaves: i32) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 0.5;
+    let mut frequency = 1.0;
+    let mut max_value = 0.0;
+
+    for _ in 0..octaves {
+        let sample_point = Vec3::new(
+            p.x * frequency,
+            p.y * frequency,
+            p.z * frequency,
+        );
+        value += noise_3d(&sample_point) * amplitude;
+        max_value += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    value / max_value
+}
+
+// `fbm` for features that only show above a threshold, like lava: None as soon as the octaves
+// still to come couldn't lift the sum over `threshold`, so most points skip the finer octaves.
+// Otherwise the value is exactly what `fbm` gives. Each octave adds less than its amplitude,
+// since the noise is below 1 (and negative about half the time).
+pub fn fbm_above(p: &Vec3, octaves: i32, threshold: f32) -> Option<f32> {
+    let mut max_value = 0.0;
+    let mut amplitude = 0.5;
+    for _ in 0..octaves {
+        max_value += amplitude;
+        amplitude *= 0.5;
+    }
+
+    let mut value = 0.0;
+    let mut amplitude = 0.5;
+    let mut frequency = 1.0;
+    let mut remaining = max_value;
+    for _ in 0..octaves {
+        if (value + remaining) / max_value < threshold - THRESHOLD_MARGIN {
+            return None;
+        }
+        let sample_point = Vec3::new(
+            p.x * frequency,
+            p.y * frequency,
+            p.z * frequency,
+        );
+        value += noise_3d(&sample_point) * amplitude;
+        remaining -= amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    Some(value / max_value)
+}
+
+// Leaves room for rounding in the running bounds, so a point is only given up on when it is
+// clearly below the threshold
+const THRESHOLD_MARGIN: f32 = 1e-4;
+
+pub fn turbulence(p: &Vec3, octaves: i32) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+
+    for _ in 0..octaves {
+        let sample_point = Vec3::new(
+            p.x * frequency,
+            p.y * frequency,
+            p.z * frequency,
+        );
+        value += (noise_3d(&sample_point) * 2.0 - 1.0).abs() * amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    value
+}
+
+// Value noise that changes smoothly between whole-number lattice points, for features that
+// have to stay connected over many pixels, like long cracks
+pub fn smooth_noise_3d(p: &Vec3) -> f32 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (u, v, w) = (smoothstep(p.x - x0), smoothstep(p.y - y0), smoothstep(p.z - z0));
+    // Offset off the integers so no lattice point lands on sin(0)
+    let corner = |dx: f32, dy: f32, dz: f32| noise_3d(&Vec3::new(x0 + dx + 0.37, y0 + dy + 0.61, z0 + dz + 0.23));
+
+    let x00 = lerp(corner(0.0, 0.0, 0.0), corner(1.0, 0.0, 0.0), u);
+    let x10 = lerp(corner(0.0, 1.0, 0.0), corner(1.0, 1.0, 0.0), u);
+    let x01 = lerp(corner(0.0, 0.0, 1.0), corner(1.0, 0.0, 1.0), u);
+    let x11 = lerp(corner(0.0, 1.0, 1.0), corner(1.0, 1.0, 1.0), u);
+    lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+}
+
+// Cellular noise: one jittered point per unit cell, returning the distances to the nearest and
+// second-nearest points and the cell of the nearest, to give each cell its own random values
+pub fn worley_3d(p: &Vec3) -> (f32, f32, Vec3) {
+    let base = Vec3::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let (mut nearest, mut second, mut nearest_cell) = (f32::MAX, f32::MAX, base);
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let cell = base + Vec3::new(dx as f32, dy as f32, dz as f32);
+                let jitter = Vec3::new(
+                    noise_3d(&(cell + Vec3::new(0.37, 0.61, 0.23))),
+                    noise_3d(&(cell + Vec3::new(1.73, 0.29, 0.91))),
+                    noise_3d(&(cell + Vec3::new(0.53, 1.47, 0.11))),
+                );
+                let distance = (*p - (cell + jitter)).length();
+                if distance < nearest {
+                    second = nearest;
+                    nearest = distance;
+                    nearest_cell = cell;
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+        }
+    }
+    (nearest, second, nearest_cell)
+}
@@ -0,0 +1,107 @@
+use core::f32::consts::{PI, TAU};
+
+#[cfg(not(feature = "std"))]
+use super::float::Float;
+use super::math::{Quat, Vec3};
+
+// A Keplerian orbit around whatever the body circles, which sits at one focus. The ellipse lies in
+// the xz plane with its closest approach along +x, tipped about the x axis by `inclination`, and
+// the body goes round the same way the planets spin, fastest near the closest approach.
+#[derive(Clone, Copy, Debug)]
+pub struct Orbit {
+    pub semi_major_axis: f32,
+    // 0 for a circle, up to (not including) 1
+    pub eccentricity: f32,
+    // Seconds of shader time per revolution
+    pub period: f32,
+    // Radians
+    pub inclination: f32,
+    // Mean anomaly at time 0, in radians: 0 starts the body at its closest approach
+    pub phase: f32,
+}
+
+impl Orbit {
+    // A circular orbit in the equatorial plane, starting at its closest approach
+    pub fn circular(radius: f32, period: f32) -> Orbit {
+        Orbit { semi_major_axis: radius, eccentricity: 0.0, period, inclination: 0.0, phase: 0.0 }
+    }
+
+    // The angle a body moving at the average rate would have covered since the closest approach
+    pub fn mean_anomaly(&self, time: f32) -> f32 {
+        let anomaly = (self.phase + TAU * time / self.period) % TAU;
+        if anomaly < 0.0 { anomaly + TAU } else { anomaly }
+    }
+
+    // Kepler's equation, M = E - e sin E, solved for E by Newton's method. Starting from π for
+    // very eccentric orbits keeps it from overshooting near the closest approach.
+    pub fn eccentric_anomaly(&self, time: f32) -> f32 {
+        let (mean, e) = (self.mean_anomaly(time), self.eccentricity);
+        let mut anomaly = if e > 0.8 { PI } else { mean };
+        for _ in 0..8 {
+            let step = (anomaly - e * anomaly.sin() - mean) / (1.0 - e * anomaly.cos());
+            anomaly -= step;
+            if step.abs() < 1e-6 {
+                break;
+            }
+        }
+        anomaly
+    }
+
+    // The angle from the closest approach to where the body actually is
+    pub fn true_anomaly(&self, time: f32) -> f32 {
+        let (eccentric, e) = (self.eccentric_anomaly(time), self.eccentricity);
+        let (sin, cos) = eccentric.sin_cos();
+        ((1.0 - e * e).sqrt() * sin).atan2(cos - e)
+    }
+
+    // Distance from the focus
+    pub fn radius(&self, time: f32) -> f32 {
+        self.semi_major_axis * (1.0 - self.eccentricity * self.eccentric_anomaly(time).cos())
+    }
+
+    // Turns the equatorial plane into the plane of the orbit
+    pub fn plane(&self) -> Quat {
+        Quat::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), self.inclination)
+    }
+
+    // Where the body is relative to the focus, before the orbit is tipped by its inclination
+    pub fn in_plane_position(&self, time: f32) -> Vec3 {
+        let (radius, anomaly) = (self.radius(time), self.true_anomaly(time));
+        Vec3::new(radius * anomaly.cos(), 0.0, -radius * anomaly.sin())
+    }
+
+    pub fn position(&self, time: f32) -> Vec3 {
+        self.plane().rotate(&self.in_plane_position(time))
+    }
+
+    // The phase that starts the body `true_anomaly` past its closest approach
+    pub fn phase_for(true_anomaly: f32, eccentricity: f32) -> f32 {
+        let (sin, cos) = true_anomaly.sin_cos();
+        let eccentric = ((1.0 - eccentricity * eccentricity).sqrt() * sin).atan2(eccentricity + cos);
+        eccentric - eccentricity * eccentric.sin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kepler_solver_converges_for_very_eccentric_orbits() {
+        let orbit = Orbit { eccentricity: 0.95, ..Orbit::circular(2.0, 10.0) };
+        for step in 0..200 {
+            let time = step as f32 * 0.05;
+            let (mean, eccentric) = (orbit.mean_anomaly(time), orbit.eccentric_anomaly(time));
+            let residual = eccentric - 0.95 * eccentric.sin() - mean;
+            assert!(residual.abs() < 1e-4, "off by {} at t = {}", residual, time);
+            let radius = orbit.radius(time);
+            assert!((0.1 - 1e-4..=3.9 + 1e-4).contains(&radius), "radius {} at t = {}", radius, time);
+        }
+    }
+
+    #[test]
+    fn phase_for_starts_the_body_at_its_true_anomaly() {
+        let orbit = Orbit { eccentricity: 0.6, phase: Orbit::phase_for(1.2, 0.6), ..Orbit::circular(1.0, 5.0) };
+        assert!((orbit.true_anomaly(0.0) - 1.2).abs() < 1e-4);
+    }
+}
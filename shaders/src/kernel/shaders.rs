@@ -0,0 +1,1332 @@
+use core::f32::consts::PI;
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::color::Color;
+use super::easing::{lerp, ramp, smoothstep};
+#[cfg(not(feature = "std"))]
+use super::float::Float;
+use super::math::{Rng, Vec3};
+use super::noise::{fbm, fbm_above, noise_3d, smooth_noise_3d, turbulence, worley_3d};
+
+// Fragment struct
+#[derive(Clone, Copy)]
+pub struct Fragment {
+    // World-space surface point
+    pub position: Vec3,
+    // The same point in the body's own frame; patterns should use this so they stay attached
+    // to the body however it is moved or spun
+    pub object_position: Vec3,
+    pub normal: Vec3,
+    pub intensity: f32,
+    pub time: f32,
+    // Pixel coordinates, for screen-space effects like dithering and hatching
+    pub screen: (f32, f32),
+    // 0 at the near plane to 1 at the far plane
+    pub depth: f32,
+    // Extra internal heat of the body (tidal heating), 0 for none; volcanic shaders turn it into
+    // more and brighter hotspots
+    pub heat: f32,
+    // Direction to the light in the body's frame, for shaders that depend on where their star is
+    pub light_dir: Vec3,
+    // Direction to the camera in the body's frame, and the body's `Lighting::specular` and
+    // `Lighting::roughness`, for shaders with highlights
+    pub view_dir: Vec3,
+    pub specular: f32,
+    pub roughness: f32,
+}
+
+// Shader 1: Sun
+pub fn sun_shader(fragment: &Fragment) -> Color {
+    let radial = (fragment.object_position.x.powi(2) + fragment.object_position.y.powi(2) + fragment.object_position.z.powi(2)).sqrt();
+    let radial_normalized = (radial * 2.0).clamp(0.0, 1.0);
+    
+    let core_color = Color::from_float(1.0, 1.0, 0.9);
+    let surface_color = Color::from_float(1.0, 0.6, 0.1);
+    let edge_color = Color::from_float(1.0, 0.2, 0.0);
+    
+    let base_color = if radial_normalized < 0.5 {
+        core_color.mix(&surface_color, radial_normalized * 2.0)
+    } else {
+        surface_color.mix(&edge_color, (radial_normalized - 0.5) * 2.0)
+    };
+
+    let turb_pos = Vec3::new(
+        fragment.object_position.x * 3.0,
+        fragment.object_position.y * 3.0 + fragment.time * 0.5,
+        fragment.object_position.z * 3.0,
+    );
+    let plasma = turbulence(&turb_pos, 4);
+    
+    let flare_pos = Vec3::new(
+        fragment.object_position.x * 8.0 + fragment.time * 0.8,
+        fragment.object_position.y * 8.0,
+        fragment.object_position.z * 8.0,
+    );
+    let flares = noise_3d(&flare_pos).powf(3.0);
+    
+    let edge_intensity = 1.0 - fragment.normal.dot(&Vec3::new(0.0, 0.0, 1.0)).abs();
+    let corona = edge_intensity.powf(3.0);
+    
+    let brightness = fragment.intensity * (0.6 + plasma * 0.3 + flares * 0.5 + corona * 0.8);
+    
+    Color::from_float(
+        base_color.r as f32 / 255.0 * brightness * (1.0 + corona * 0.5),
+        base_color.g as f32 / 255.0 * brightness * (1.0 + flares * 0.3),
+        base_color.b as f32 / 255.0 * brightness * (1.0 + plasma * 0.2),
+    )
+}
+
+// Shader 2: Rocky Planet
+pub fn rocky_planet_shader(fragment: &Fragment) -> Color {
+    rocky_planet_shader_with(fragment, 0.0)
+}
+
+// Where the line of sight through a surface point crosses a shell `altitude` above it, in planet
+// radii, as a point on the unit sphere, and how many times longer that sight line runs through a
+// thin layer there than it would looking straight down. Away from the point under the camera the
+// crossing slides outward, and at the limb the line grazes the layer.
+fn cloud_shell_crossing(fragment: &Fragment, altitude: f32) -> (Vec3, f32) {
+    let p = fragment.object_position.normalize();
+    let shell = 1.0 + altitude;
+    let mu = p.dot(&fragment.view_dir);
+    let t = -mu + (mu * mu + shell * shell - 1.0).sqrt();
+    let crossing = (p + fragment.view_dir * t) * (1.0 / shell);
+    let grazing = crossing.dot(&fragment.view_dir).max(0.15);
+    (crossing, 1.0 / grazing)
+}
+
+// `cloud_altitude` lifts the clouds off the ground, so they shift against the surface as it turns
+// and pile up toward the limb; at 0 they are painted on
+pub fn rocky_planet_shader_with(fragment: &Fragment, cloud_altitude: f32) -> Color {
+    let ocean_deep = Color::from_float(0.0, 0.1, 0.3);
+    let ocean_shallow = Color::from_float(0.0, 0.3, 0.6);
+    
+    let continent_pos = Vec3::new(
+        fragment.object_position.x * 2.0,
+        fragment.object_position.y * 2.0,
+        fragment.object_position.z * 2.0,
+    );
+    let continent_noise = fbm(&continent_pos, 5);
+    let is_land = continent_noise > 0.48;
+    
+    let terrain_pos = Vec3::new(
+        fragment.object_position.x * 10.0,
+        fragment.object_position.y * 10.0,
+        fragment.object_position.z * 10.0,
+    );
+    let terrain = fbm(&terrain_pos, 4);
+    
+    let beach = Color::from_float(0.85, 0.8, 0.6);
+    let lowland = Color::from_float(0.2, 0.5, 0.1);
+    let highland = Color::from_float(0.4, 0.3, 0.2);
+    let mountain = Color::from_float(0.6, 0.6, 0.6);
+    
+    let land_color = if terrain < 0.3 {
+        beach.mix(&lowland, ramp(0.0, 0.3, terrain))
+    } else if terrain < 0.6 {
+        lowland.mix(&highland, ramp(0.3, 0.6, terrain))
+    } else {
+        highland.mix(&mountain, ramp(0.6, 1.0, terrain))
+    };
+    
+    let (cloud_point, cloud_path) = if cloud_altitude > 0.0 {
+        cloud_shell_crossing(fragment, cloud_altitude)
+    } else {
+        (fragment.object_position, 1.0)
+    };
+    let cloud_pos = Vec3::new(
+        cloud_point.x * 5.0 + fragment.time * 0.1,
+        cloud_point.y * 5.0,
+        cloud_point.z * 5.0,
+    );
+    let clouds = fbm(&cloud_pos, 3);
+    let has_cloud = clouds > 0.6;
+    let cloud_density = ramp(0.6, 1.0, clouds);
+    
+    let mut final_color = if is_land {
+        land_color
+    } else {
+        let depth = (continent_noise - 0.3) / 0.18;
+        ocean_deep.mix(&ocean_shallow, depth.clamp(0.0, 1.0))
+    };
+    
+    if has_cloud {
+        let cloud_color = Color::from_float(0.95, 0.95, 1.0);
+        // Seen at a slant, the light crosses more of the layer
+        let opacity = 1.0 - (1.0 - cloud_density * 0.7).powf(cloud_path);
+        final_color = final_color.mix(&cloud_color, opacity);
+    }
+    
+    let lit = fragment.intensity * (0.4 + 0.6 * fragment.intensity);
+    
+    Color::from_float(
+        final_color.r as f32 / 255.0 * lit,
+        final_color.g as f32 / 255.0 * lit,
+        final_color.b as f32 / 255.0 * lit,
+    )
+}
+
+// Shader 3: Gas Giant
+// A storm on a giant planet, placed by latitude and longitude in degrees. `size` is the radius on
+// the unit sphere, `drift` moves it along its latitude in radians per unit of time and `rotation`
+// is how fast its spiral arms turn.
+#[derive(Clone, Copy)]
+pub struct Storm {
+    pub latitude: f32,
+    pub longitude: f32,
+    pub size: f32,
+    pub color: Color,
+    pub drift: f32,
+    pub rotation: f32,
+}
+
+impl Storm {
+    // "lat:lon:size:#rrggbb[:drift[:rotation]]"
+    pub fn parse(value: &str) -> Option<Storm> {
+        let parts: Vec<&str> = value.split(':').collect();
+        if parts.len() < 4 || parts.len() > 6 {
+            return None;
+        }
+        let number = |index: usize| parts.get(index).map_or(Some(0.0), |part| part.parse::<f32>().ok());
+        Some(Storm {
+            latitude: number(0)?.clamp(-90.0, 90.0),
+            longitude: number(1)?,
+            size: number(2)?.max(0.0),
+            color: Color::from_hex(parts[3])?,
+            drift: number(4)?,
+            rotation: number(5)?,
+        })
+    }
+
+    // How strongly the storm colors a surface point: solid inside with a soft edge, streaked by
+    // spiral arms that turn over time
+    pub fn coverage(&self, p: &Vec3, time: f32) -> f32 {
+        if self.size <= 0.0 {
+            return 0.0;
+        }
+        let latitude = self.latitude.to_radians();
+        let longitude = self.longitude.to_radians() + self.drift * time;
+        let center = Vec3::new(
+            latitude.cos() * longitude.cos(),
+            latitude.sin(),
+            latitude.cos() * longitude.sin(),
+        );
+        let offset = p.normalize() - center;
+        let distance = offset.length() / self.size;
+        if distance >= 1.0 {
+            return 0.0;
+        }
+
+        let east = Vec3::new(-longitude.sin(), 0.0, longitude.cos());
+        let north = east.cross(&center);
+        let angle = offset.dot(&north).atan2(offset.dot(&east));
+        let arms = 0.85 + 0.15 * (2.0 * angle + 4.0 * distance - self.rotation * time).sin();
+
+        smoothstep((1.0 - distance) / 0.25) * arms
+    }
+}
+
+// The Great Red Spot-like storm of the default gas giant
+pub const GAS_GIANT_STORMS: [Storm; 1] = [Storm {
+    latitude: -13.2,
+    longitude: 69.4,
+    size: 0.25,
+    color: Color { r: 178, g: 51, b: 25 },
+    drift: 0.0,
+    rotation: 1.0,
+}];
+
+// The dark spot of the default ice giant
+pub const ICE_GIANT_STORMS: [Storm; 1] = [Storm {
+    latitude: 20.4,
+    longitude: 119.7,
+    size: 0.16,
+    color: Color { r: 25, g: 51, b: 102 },
+    drift: 0.0,
+    rotation: 1.0,
+}];
+
+// A band of a giant planet's zonal winds: a solid stripe of color centered on a latitude, with
+// latitude and width in degrees
+#[derive(Clone, Copy)]
+pub struct ZonalBand {
+    pub latitude: f32,
+    pub width: f32,
+    pub color: Color,
+}
+
+// How a giant planet's color varies with latitude
+#[derive(Clone)]
+pub enum ZonalProfile {
+    // Sine waves in the sine of latitude cycling through three colors, sliding by `drift` radians per unit of time
+    Waves { frequency: f32, drift: f32, colors: [Color; 3] },
+    // Stripes of solid color that blend smoothly into each other across the gaps between them
+    Bands(Vec<ZonalBand>),
+}
+
+impl ZonalProfile {
+    // A preset name, or "lat:width:#rrggbb" bands, comma separated
+    pub fn parse(value: &str) -> Option<ZonalProfile> {
+        if let Some(profile) = ZonalProfile::preset(value) {
+            return Some(profile);
+        }
+        let bands = value
+            .split(',')
+            .filter(|band| !band.is_empty())
+            .map(|band| {
+                let parts: Vec<&str> = band.split(':').collect();
+                let [latitude, width, color] = parts[..] else { return None };
+                Some(ZonalBand {
+                    latitude: latitude.parse::<f32>().ok()?.clamp(-90.0, 90.0),
+                    width: width.parse::<f32>().ok()?.max(0.0),
+                    color: Color::from_hex(color)?,
+                })
+            })
+            .collect::<Option<Vec<ZonalBand>>>()?;
+        (!bands.is_empty()).then_some(ZonalProfile::Bands(bands))
+    }
+
+    // Rough band layouts of the solar system's giants, north to south
+    pub fn preset(name: &str) -> Option<ZonalProfile> {
+        let bands: &[(f32, f32, &str)] = match name {
+            "jupiter" => &[
+                (75.0, 30.0, "#8a7d6e"),
+                (48.0, 8.0, "#b49878"),
+                (36.0, 8.0, "#e2d4ba"),
+                (24.0, 6.0, "#9c6a48"),
+                (15.0, 8.0, "#efe4cf"),
+                (8.0, 6.0, "#a8603a"),
+                (0.0, 8.0, "#f2e6d0"),
+                (-10.0, 8.0, "#b06a40"),
+                (-20.0, 8.0, "#ede0c8"),
+                (-28.0, 5.0, "#a07458"),
+                (-40.0, 10.0, "#d8c8ac"),
+                (-75.0, 30.0, "#8a7d6e"),
+            ],
+            "saturn" => &[
+                (75.0, 30.0, "#a89a70"),
+                (50.0, 10.0, "#c8b27c"),
+                (35.0, 8.0, "#e0cc98"),
+                (20.0, 10.0, "#d2b67e"),
+                (0.0, 20.0, "#f0e0b0"),
+                (-20.0, 10.0, "#d2b67e"),
+                (-35.0, 8.0, "#e0cc98"),
+                (-50.0, 10.0, "#c8b27c"),
+                (-75.0, 30.0, "#a89a70"),
+            ],
+            "uranus" => &[(60.0, 60.0, "#9fe3e8"), (0.0, 30.0, "#8ad4dc"), (-60.0, 60.0, "#a6e8ea")],
+            "neptune" => &[
+                (70.0, 40.0, "#2a4fb0"),
+                (40.0, 10.0, "#3f6fd8"),
+                (20.0, 8.0, "#5a8ef0"),
+                (0.0, 20.0, "#3a66d0"),
+                (-20.0, 8.0, "#5a8ef0"),
+                (-45.0, 10.0, "#2f58c0"),
+                (-70.0, 30.0, "#6fa0f0"),
+            ],
+            _ => return None,
+        };
+        Some(ZonalProfile::Bands(
+            bands
+                .iter()
+                .map(|&(latitude, width, color)| ZonalBand { latitude, width, color: Color::from_hex(color).unwrap() })
+                .collect(),
+        ))
+    }
+
+    // Color at a point on the planet in object space, by its latitude about the spin axis
+    pub fn sample(&self, p: &Vec3, time: f32) -> Color {
+        let y = p.normalize().y;
+        match self {
+            ZonalProfile::Waves { frequency, drift, colors } => {
+                let band = (y * frequency + time * drift).sin() * 0.5 + 0.5;
+                if band < 0.33 {
+                    colors[0].mix(&colors[1], band * 3.0)
+                } else if band < 0.66 {
+                    colors[1].mix(&colors[2], (band - 0.33) * 3.0)
+                } else {
+                    colors[2].mix(&colors[0], (band - 0.66) * 3.0)
+                }
+            }
+            ZonalProfile::Bands(bands) => {
+                let latitude = y.clamp(-1.0, 1.0).asin().to_degrees();
+                // Nearest band edges on either side, with their colors
+                let mut below: Option<(f32, Color)> = None;
+                let mut above: Option<(f32, Color)> = None;
+                for band in bands {
+                    let (low, high) = (band.latitude - band.width / 2.0, band.latitude + band.width / 2.0);
+                    if (low..=high).contains(&latitude) {
+                        return band.color;
+                    }
+                    if high < latitude && below.is_none_or(|(edge, _)| high > edge) {
+                        below = Some((high, band.color));
+                    }
+                    if low > latitude && above.is_none_or(|(edge, _)| low < edge) {
+                        above = Some((low, band.color));
+                    }
+                }
+                match (below, above) {
+                    (Some((low, from)), Some((high, to))) => {
+                        from.mix(&to, smoothstep(ramp(low, high, latitude)))
+                    }
+                    (Some((_, color)), None) | (None, Some((_, color))) => color,
+                    (None, None) => Color::new(0, 0, 0),
+                }
+            }
+        }
+    }
+}
+
+pub static GAS_GIANT_BANDS: ZonalProfile = ZonalProfile::Waves {
+    frequency: 8.0,
+    drift: 0.0,
+    colors: [Color { r: 204, g: 153, b: 102 }, Color { r: 127, g: 76, b: 51 }, Color { r: 229, g: 178, b: 127 }],
+};
+
+pub static ICE_GIANT_BANDS: ZonalProfile = ZonalProfile::Waves {
+    frequency: 12.0,
+    drift: 0.3,
+    colors: [Color { r: 51, g: 102, b: 204 }, Color { r: 25, g: 153, b: 229 }, Color { r: 76, g: 178, b: 255 }],
+};
+
+pub fn gas_giant_shader(fragment: &Fragment) -> Color {
+    gas_giant_shader_with(fragment, &GAS_GIANT_STORMS, &GAS_GIANT_BANDS)
+}
+
+pub fn gas_giant_shader_with(fragment: &Fragment, storms: &[Storm], bands: &ZonalProfile) -> Color {
+    let base_band = bands.sample(&fragment.object_position, fragment.time);
+    
+    let flow_pos = Vec3::new(
+        fragment.object_position.x * 6.0 + fragment.time * 0.2,
+        fragment.object_position.y * 12.0,
+        fragment.object_position.z * 6.0,
+    );
+    let flow = turbulence(&flow_pos, 4);
+    
+    let detail_pos = Vec3::new(
+        fragment.object_position.x * 20.0,
+        fragment.object_position.y * 20.0,
+        fragment.object_position.z * 20.0,
+    );
+    let detail = noise_3d(&detail_pos) * 0.3;
+    
+    let mut final_color = base_band;
+    
+    let flow_influence = flow * 0.2 - 0.1;
+    final_color = Color::from_float(
+        (final_color.r as f32 / 255.0 + flow_influence).clamp(0.0, 1.0),
+        (final_color.g as f32 / 255.0 + flow_influence).clamp(0.0, 1.0),
+        (final_color.b as f32 / 255.0 + flow_influence).clamp(0.0, 1.0),
+    );
+    
+    for storm in storms {
+        final_color = final_color.mix(&storm.color, storm.coverage(&fragment.object_position, fragment.time) * 0.8);
+    }
+    
+    let brightness = fragment.intensity * (0.7 + detail);
+    
+    Color::from_float(
+        final_color.r as f32 / 255.0 * brightness,
+        final_color.g as f32 / 255.0 * brightness,
+        final_color.b as f32 / 255.0 * brightness,
+    )
+}
+
+// Shader for Ring System (procedural bands)
+// Light reflected off the planet onto its rings: the planet's average surface color, and how
+// strongly it shows at the rings' inner edge
+#[derive(Clone, Copy)]
+pub struct PlanetShine {
+    pub color: Color,
+    pub strength: f32,
+}
+
+// A named gap in the rings, like the Cassini division. `softness` is the fraction of the width
+// over which the edges fade back to full density.
+#[derive(Clone)]
+pub struct RingGap {
+    pub name: String,
+    pub radius: f32,
+    pub width: f32,
+    pub softness: f32,
+}
+
+impl RingGap {
+    pub fn new(name: &str, radius: f32, width: f32, softness: f32) -> RingGap {
+        RingGap { name: name.to_string(), radius, width, softness }
+    }
+
+    // "saturn", "none", or "name:radius:width[:softness]" gaps, comma separated
+    pub fn parse_list(value: &str) -> Option<Vec<RingGap>> {
+        match value {
+            "saturn" => return Some(RingGap::saturn()),
+            "none" => return Some(Vec::new()),
+            _ => {}
+        }
+        value
+            .split(',')
+            .filter(|gap| !gap.is_empty())
+            .map(|gap| {
+                let parts: Vec<&str> = gap.split(':').collect();
+                if parts.len() < 3 || parts.len() > 4 || parts[0].is_empty() {
+                    return None;
+                }
+                let number = |index: usize| parts[index].parse::<f32>().ok();
+                let softness = if parts.len() == 4 { number(3)? } else { 0.3 };
+                Some(RingGap::new(parts[0], number(1)?, number(2)?.max(0.0), softness.clamp(0.0, 1.0)))
+            })
+            .collect()
+    }
+
+    // Saturn's main gaps, with its rings from the C ring to the A ring squeezed into 1.3-2.0
+    pub fn saturn() -> Vec<RingGap> {
+        vec![
+            RingGap::new("maxwell", 1.44, 0.006, 0.3),
+            RingGap::new("cassini", 1.81, 0.05, 0.25),
+            RingGap::new("encke", 1.96, 0.008, 0.2),
+            RingGap::new("keeler", 1.995, 0.003, 0.2),
+        ]
+    }
+
+    // How much of the ring's density is left at a radius: 0 in the middle of the gap
+    pub fn density(&self, radius: f32) -> f32 {
+        let half_width = self.width / 2.0;
+        let distance = (radius - self.radius).abs();
+        if distance >= half_width {
+            return 1.0;
+        }
+        let edge = half_width * self.softness;
+        if edge <= 0.0 {
+            return 0.0;
+        }
+        smoothstep(ramp(half_width - edge, half_width, distance))
+    }
+}
+
+// A narrow ringlet that need not be a uniform circle: `eccentricity` makes it an ellipse around
+// the planet, and `clumping` kinks it and breaks it into bright clumps and faint arcs, from
+// noise seeded by `seed`
+#[derive(Clone, Copy)]
+pub struct Ringlet {
+    pub radius: f32,
+    pub width: f32,
+    pub eccentricity: f32,
+    pub clumping: f32,
+    pub seed: u64,
+}
+
+impl Ringlet {
+    // "saturn", "none", or "radius:width[:eccentricity[:clumping[:seed]]]" ringlets, comma separated
+    pub fn parse_list(value: &str) -> Option<Vec<Ringlet>> {
+        match value {
+            "saturn" => return Some(Ringlet::saturn()),
+            "none" => return Some(Vec::new()),
+            _ => {}
+        }
+        value
+            .split(',')
+            .filter(|ringlet| !ringlet.is_empty())
+            .enumerate()
+            .map(|(index, ringlet)| {
+                let parts: Vec<&str> = ringlet.split(':').collect();
+                if parts.len() < 2 || parts.len() > 5 {
+                    return None;
+                }
+                let number = |index: usize| parts.get(index).map_or(Some(0.0), |part| part.parse::<f32>().ok());
+                let seed = match parts.get(4) {
+                    Some(seed) => seed.parse::<u64>().ok()?,
+                    None => index as u64,
+                };
+                Some(Ringlet {
+                    radius: number(0)?,
+                    width: number(1)?.max(0.0),
+                    eccentricity: number(2)?.clamp(0.0, 0.9),
+                    clumping: number(3)?.clamp(0.0, 1.0),
+                    seed,
+                })
+            })
+            .collect()
+    }
+
+    // Eccentric ringlets in Saturn's gaps, exaggerated enough to show at this scale
+    pub fn saturn() -> Vec<Ringlet> {
+        vec![
+            // Huygens ringlet, near the inner edge of the Cassini division
+            Ringlet { radius: 1.795, width: 0.008, eccentricity: 0.006, clumping: 0.0, seed: 1 },
+            // The kinked, clumpy ringlets in the Encke gap
+            Ringlet { radius: 1.96, width: 0.004, eccentricity: 0.0, clumping: 0.8, seed: 2 },
+        ]
+    }
+
+    // Opacity and relative brightness of the ringlet at a point on the ring plane
+    pub fn sample(&self, x: f32, z: f32) -> (f32, f32) {
+        if self.width <= 0.0 {
+            return (0.0, 1.0);
+        }
+        let mut rng = Rng::new(self.seed);
+        let periapsis = rng.range(0.0, 2.0 * PI);
+        let offset = rng.range(0.0, 100.0);
+
+        // Noise along the arc, sampled on a circle so it wraps around without a seam
+        let angle = z.atan2(x);
+        let arc = |frequency: f32| {
+            noise_3d(&Vec3::new(angle.cos() * frequency + offset, offset, angle.sin() * frequency))
+        };
+
+        let e = self.eccentricity;
+        let mut center = self.radius * (1.0 - e * e) / (1.0 + e * (angle - periapsis).cos());
+        if self.clumping > 0.0 {
+            center += self.width * self.clumping * (arc(6.0) - 0.5) * 2.0;
+        }
+
+        let distance = ((x * x + z * z).sqrt() - center).abs() / (self.width / 2.0);
+        if distance >= 1.0 {
+            return (0.0, 1.0);
+        }
+        let density = 1.0 - self.clumping + self.clumping * arc(20.0) * 2.0;
+        ((1.0 - distance * distance) * density.min(1.0) * 0.9, density)
+    }
+}
+
+// What circles a ringed planet: icy rings, or the hot rubble of a giant impact that moons are
+// still forming out of
+#[derive(Clone, Copy, PartialEq)]
+pub enum RingStyle {
+    Rings,
+    DebrisDisk,
+}
+
+impl RingStyle {
+    pub fn parse(value: &str) -> Option<RingStyle> {
+        match value {
+            "rings" => Some(RingStyle::Rings),
+            "debris" | "debris_disk" => Some(RingStyle::DebrisDisk),
+            _ => None,
+        }
+    }
+
+    // Inner and outer radius; a fresh debris disk reaches down to just above the surface
+    pub fn extent(self) -> (f32, f32) {
+        match self {
+            RingStyle::Rings => (1.3, 2.0),
+            RingStyle::DebrisDisk => (1.08, 1.95),
+        }
+    }
+
+    // Key for per-body lighting overrides
+    pub fn body(self) -> &'static str {
+        match self {
+            RingStyle::Rings => "ring",
+            RingStyle::DebrisDisk => "debris_disk",
+        }
+    }
+}
+
+pub fn ring_shader(fragment: &Fragment, planet_shine: &PlanetShine, gaps: &[RingGap], ringlets: &[Ringlet]) -> (Color, f32) {
+    let radius = (fragment.object_position.x.powi(2) + fragment.object_position.z.powi(2)).sqrt();
+    
+    let inner_radius = 1.3;
+    let outer_radius = 2.0;
+    
+    if radius < inner_radius || radius > outer_radius {
+        return (Color::new(0, 0, 0), 0.0);
+    }
+    
+    let band_pattern = (radius * 15.0).sin() * 0.5 + 0.5;
+    
+    let ring_color1 = Color::from_float(0.9, 0.8, 0.6);
+    let ring_color2 = Color::from_float(0.7, 0.6, 0.4);
+    let ring_color3 = Color::from_float(0.5, 0.4, 0.3);
+    
+    let base_color = if band_pattern < 0.3 {
+        ring_color1.mix(&ring_color2, ramp(0.0, 0.3, band_pattern))
+    } else if band_pattern < 0.7 {
+        ring_color2.mix(&ring_color3, ramp(0.3, 0.7, band_pattern))
+    } else {
+        ring_color3.mix(&ring_color1, ramp(0.7, 1.0, band_pattern))
+    };
+    
+    let gap_effect = gaps.iter().map(|gap| gap.density(radius)).fold(1.0, f32::min);
+
+    // Fine ringlets left over between the gaps
+    let residual_pos = Vec3::new(radius * 60.0, 0.0, 0.0);
+    let residual = 0.85 + 0.15 * fbm(&residual_pos, 3);
+    
+    let particle_pos = Vec3::new(
+        fragment.object_position.x * 25.0,
+        0.0,
+        fragment.object_position.z * 25.0,
+    );
+    let particles = noise_3d(&particle_pos);
+    
+    let alpha = ((outer_radius - radius) / (outer_radius - inner_radius)) * residual * particles;
+    let alpha = alpha.clamp(0.3, 0.95) * gap_effect;
+
+    // Ringlets show in the gaps, and over the main rings where they cross them
+    let (ringlet_alpha, ringlet_brightness) = ringlets
+        .iter()
+        .map(|ringlet| ringlet.sample(fragment.object_position.x, fragment.object_position.z))
+        .fold((0.0, 1.0), |densest, sample| if sample.0 > densest.0 { sample } else { densest });
+    let alpha = alpha.max(ringlet_alpha);
+    
+    let brightness = fragment.intensity * (0.6 + particles * 0.4) * (1.0 + (ringlet_brightness - 1.0) * ringlet_alpha);
+
+    // The planet's glow falls off with the square of the distance, so it mostly tints the inner rings
+    let shine = planet_shine.strength * (inner_radius / radius).powi(2);
+    let shine_color = planet_shine.color;
+    
+    let final_color = Color::from_float(
+        base_color.r as f32 / 255.0 * (brightness + shine * shine_color.r as f32 / 255.0),
+        base_color.g as f32 / 255.0 * (brightness + shine * shine_color.g as f32 / 255.0),
+        base_color.b as f32 / 255.0 * (brightness + shine * shine_color.b as f32 / 255.0),
+    );
+    
+    (final_color, alpha)
+}
+
+// A circumplanetary debris disk after a giant impact: dust and molten rock, hottest and glowing
+// near the planet, cooling outward, torn into clumps that the disk's shear drags into spirals
+pub fn debris_disk_shader(fragment: &Fragment, inner_radius: f32, outer_radius: f32) -> (Color, f32) {
+    let p = fragment.object_position;
+    let radius = (p.x * p.x + p.z * p.z).sqrt();
+    if radius < inner_radius || radius > outer_radius {
+        return (Color::new(0, 0, 0), 0.0);
+    }
+    let t = (radius - inner_radius) / (outer_radius - inner_radius);
+
+    // Inner orbits run faster, so clumps shear into trailing arcs as time goes on
+    let shear = fragment.time * 0.4 * (inner_radius / radius).powf(1.5);
+    let sheared = Vec3::new(p.x, 0.0, p.z).rotate_y(shear);
+    let clumps = smooth_noise_3d(&(sheared * 3.0)) * 0.6 + turbulence(&(sheared * 8.0), 4) * 0.4;
+    let grain = fbm(&Vec3::new(radius * 40.0, 0.0, 0.0), 3);
+
+    // Thin at the inner edge where the rock vaporizes, thinning out again toward the outside
+    let density = (t / 0.05).min(1.0) * (1.0 - t).powf(1.5);
+    let alpha = (density * clumps * 1.2 * (0.8 + 0.2 * grain)).clamp(0.0, 0.95);
+
+    // Temperature falls off like a viscous disk's, ~r^-3/4
+    let temperature = 2200.0 * (inner_radius / radius).powf(0.75) * (0.85 + 0.3 * clumps);
+    let glow = ((temperature - 900.0) / 1700.0).clamp(0.0, 1.0).powf(1.5);
+    let glow_color = blackbody_color(temperature);
+
+    let dust = Color::from_float(0.45, 0.36, 0.3);
+    let brightness = fragment.intensity * (0.6 + clumps * 0.4);
+    let final_color = Color::from_float(
+        dust.r as f32 / 255.0 * brightness + glow_color.r as f32 / 255.0 * glow,
+        dust.g as f32 / 255.0 * brightness + glow_color.g as f32 / 255.0 * glow,
+        dust.b as f32 / 255.0 * brightness + glow_color.b as f32 / 255.0 * glow,
+    );
+
+    (final_color, alpha)
+}
+
+// Shader for Moon (cratered rocky surface)
+pub fn moon_shader(fragment: &Fragment) -> Color {
+    let base_gray = Color::from_float(0.5, 0.5, 0.5);
+    let dark_gray = Color::from_float(0.3, 0.3, 0.3);
+    let light_gray = Color::from_float(0.7, 0.7, 0.7);
+    
+    let surface_pos = Vec3::new(
+        fragment.object_position.x * 4.0,
+        fragment.object_position.y * 4.0,
+        fragment.object_position.z * 4.0,
+    );
+    let surface_variation = fbm(&surface_pos, 4);
+    
+    let base_color = if surface_variation < 0.4 {
+        dark_gray.mix(&base_gray, ramp(0.0, 0.4, surface_variation))
+    } else {
+        base_gray.mix(&light_gray, ramp(0.4, 1.0, surface_variation))
+    };
+    
+    let crater_pos = Vec3::new(
+        fragment.object_position.x * 12.0,
+        fragment.object_position.y * 12.0,
+        fragment.object_position.z * 12.0,
+    );
+    let craters = turbulence(&crater_pos, 4);
+    
+    let is_crater = craters > 0.7;
+    let crater_depth = if is_crater {
+        ramp(0.7, 1.0, craters)
+    } else {
+        0.0
+    };
+    
+    let detail_pos = Vec3::new(
+        fragment.object_position.x * 30.0,
+        fragment.object_position.y * 30.0,
+        fragment.object_position.z * 30.0,
+    );
+    let detail = noise_3d(&detail_pos) * 0.15;
+    
+    let mut final_color = base_color;
+    
+    let crater_color = Color::from_float(0.2, 0.2, 0.2);
+    final_color = final_color.mix(&crater_color, crater_depth * 0.6);
+    
+    final_color = Color::from_float(
+        (final_color.r as f32 / 255.0 + detail - 0.075).clamp(0.0, 1.0),
+        (final_color.g as f32 / 255.0 + detail - 0.075).clamp(0.0, 1.0),
+        (final_color.b as f32 / 255.0 + detail - 0.075).clamp(0.0, 1.0),
+    );
+    
+    let brightness = fragment.intensity * (0.3 + 0.7 * fragment.intensity);
+    
+    Color::from_float(
+        final_color.r as f32 / 255.0 * brightness,
+        final_color.g as f32 / 255.0 * brightness,
+        final_color.b as f32 / 255.0 * brightness,
+    )
+}
+
+// Shader for an icy moon like Europa: bright young ice crossed by long reddish-brown lineae,
+// with patches of chaos terrain where the crust broke into drifting blocks
+pub fn europa_shader(fragment: &Fragment) -> Color {
+    // Moon meshes are built at their own radius, so work on the unit sphere
+    let p = fragment.object_position.normalize();
+    let ice_dark = Color::from_float(0.78, 0.8, 0.83);
+    let ice_light = Color::from_float(0.95, 0.94, 0.92);
+    let stain = Color::from_float(0.55, 0.33, 0.2);
+
+    let mut final_color = ice_dark.mix(&ice_light, smooth_noise_3d(&(p * 3.0)));
+
+    // Lineae follow tidal stress, so they run along wobbly great circles; ridged noise adds the
+    // shorter cracks in between
+    let mut linea: f32 = 0.0;
+    for index in 0..7 {
+        let seed = index as f32 * 3.1;
+        let pole = Vec3::new(
+            noise_3d(&Vec3::new(seed, 0.3, 0.7)) - 0.5,
+            noise_3d(&Vec3::new(0.9, seed, 0.1)) - 0.5,
+            noise_3d(&Vec3::new(0.2, 0.6, seed)) - 0.5,
+        )
+        .normalize();
+        let wobble = (smooth_noise_3d(&(p * 2.5 + Vec3::new(seed, 0.0, 0.0))) - 0.5) * 0.08;
+        let width = 0.012 + 0.01 * noise_3d(&Vec3::new(seed, seed, 0.5));
+        linea = linea.max(1.0 - (p.dot(&pole) + wobble).abs() / width);
+    }
+    let ridge = (smooth_noise_3d(&(p * 7.0)) * 2.0 - 1.0).abs();
+    linea = linea.max(1.0 - ridge / 0.035).clamp(0.0, 1.0);
+
+    // Chaos terrain: blocks of the old surface, tilted and scattered in a darker matrix that has
+    // wiped out the lineae
+    let chaos = ((smooth_noise_3d(&(p * 2.0 + Vec3::new(5.2, 1.3, 7.7))) - 0.5) / 0.06).clamp(0.0, 1.0);
+    let (nearest, second, cell) = worley_3d(&(p * 25.0));
+    let block = ((second - nearest) / 0.15).clamp(0.0, 1.0) * (0.6 + 0.4 * noise_3d(&cell));
+    let matrix = stain.mix(&Color::from_float(0.45, 0.3, 0.22), fbm(&(p * 20.0), 3));
+    let chaos_color = matrix.mix(&ice_dark, block);
+
+    final_color = final_color.mix(&stain, linea * 0.85 * (1.0 - chaos));
+    final_color = final_color.mix(&chaos_color, chaos);
+
+    Color::from_float(
+        final_color.r as f32 / 255.0 * fragment.intensity,
+        final_color.g as f32 / 255.0 * fragment.intensity,
+        final_color.b as f32 / 255.0 * fragment.intensity,
+    )
+}
+
+// Shader 4: Ice Giant
+pub fn ice_giant_shader(fragment: &Fragment) -> Color {
+    ice_giant_shader_with(fragment, &ICE_GIANT_STORMS, &ICE_GIANT_BANDS)
+}
+
+pub fn ice_giant_shader_with(fragment: &Fragment, storms: &[Storm], bands: &ZonalProfile) -> Color {
+    let base_color = bands.sample(&fragment.object_position, fragment.time);
+    
+    let cloud_pos = Vec3::new(
+        fragment.object_position.x * 4.0 + fragment.time * 0.15,
+        fragment.object_position.y * 8.0,
+        fragment.object_position.z * 4.0,
+    );
+    let clouds = fbm(&cloud_pos, 4);
+    
+    let mut final_color = base_color;
+    
+    let cloud_influence = clouds * 0.15;
+    final_color = Color::from_float(
+        (final_color.r as f32 / 255.0 + cloud_influence).clamp(0.0, 1.0),
+        (final_color.g as f32 / 255.0 + cloud_influence).clamp(0.0, 1.0),
+        (final_color.b as f32 / 255.0 + cloud_influence * 0.8).clamp(0.0, 1.0),
+    );
+    
+    for storm in storms {
+        final_color = final_color.mix(&storm.color, storm.coverage(&fragment.object_position, fragment.time) * 0.6);
+    }
+    
+    let brightness = fragment.intensity * (0.6 + clouds * 0.2);
+    
+    Color::from_float(
+        final_color.r as f32 / 255.0 * brightness,
+        final_color.g as f32 / 255.0 * brightness,
+        final_color.b as f32 / 255.0 * brightness,
+    )
+}
+
+// Shader 5: Desert Planet
+pub fn desert_planet_shader(fragment: &Fragment) -> Color {
+    let rust_light = Color::from_float(0.8, 0.4, 0.2);
+    let rust_dark = Color::from_float(0.5, 0.2, 0.1);
+    let rust_sand = Color::from_float(0.9, 0.6, 0.3);
+    
+    let terrain_pos = Vec3::new(
+        fragment.object_position.x * 3.0,
+        fragment.object_position.y * 3.0,
+        fragment.object_position.z * 3.0,
+    );
+    let terrain = fbm(&terrain_pos, 5);
+    
+    let base_color = if terrain < 0.3 {
+        rust_dark.mix(&rust_light, ramp(0.0, 0.3, terrain))
+    } else if terrain < 0.7 {
+        rust_light.mix(&rust_sand, ramp(0.3, 0.7, terrain))
+    } else {
+        rust_sand.mix(&rust_dark, ramp(0.7, 1.0, terrain))
+    };
+    
+    let crater_pos = Vec3::new(
+        fragment.object_position.x * 8.0,
+        fragment.object_position.y * 8.0,
+        fragment.object_position.z * 8.0,
+    );
+    let craters = turbulence(&crater_pos, 3);
+    let crater_effect = (craters - 0.7).max(0.0) * 3.0;
+    
+    let polar = fragment.object_position.y.abs();
+    let ice_threshold = 0.7;
+    let ice_color = Color::from_float(0.95, 0.95, 1.0);
+    let has_ice = polar > ice_threshold;
+    let ice_amount = if has_ice {
+        ((polar - ice_threshold) / (1.0 - ice_threshold)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    
+    let mut final_color = base_color;
+    
+    final_color = Color::from_float(
+        (final_color.r as f32 / 255.0 * (1.0 - crater_effect * 0.3)).clamp(0.0, 1.0),
+        (final_color.g as f32 / 255.0 * (1.0 - crater_effect * 0.3)).clamp(0.0, 1.0),
+        (final_color.b as f32 / 255.0 * (1.0 - crater_effect * 0.3)).clamp(0.0, 1.0),
+    );
+    
+    final_color = final_color.mix(&ice_color, ice_amount * 0.8);
+    
+    let brightness = fragment.intensity * (0.5 + terrain * 0.3);
+    
+    Color::from_float(
+        final_color.r as f32 / 255.0 * brightness,
+        final_color.g as f32 / 255.0 * brightness,
+        final_color.b as f32 / 255.0 * brightness,
+    )
+}
+
+// Shader 6: Volcanic Planet
+// A patch of a volcanic body's surface where hotspots and lava can break out. The middle is kept
+// as a direction on the unit sphere, worked out ahead from its latitude and longitude (in the
+// comments) like a storm's, and `size` is its radius on the unit sphere. Regions are opt-in: a
+// body given a list skips the hotspot and lava noise outside every patch, one without keeps them
+// over its whole surface.
+#[derive(Clone, Copy)]
+pub struct EmissiveRegion {
+    pub center: Vec3,
+    pub size: f32,
+}
+
+impl EmissiveRegion {
+    // 1 inside, fading to 0 over the outer part so the glow doesn't stop at a hard line
+    pub fn coverage(&self, p: &Vec3) -> f32 {
+        let distance = (*p - self.center).length() / self.size;
+        smoothstep((1.0 - distance) / 0.3)
+    }
+}
+
+// How much of the emissive features show at a surface point: the most any region gives it
+pub fn emissive_coverage(regions: &[EmissiveRegion], p: &Vec3) -> f32 {
+    let p = p.normalize();
+    regions.iter().map(|region| region.coverage(&p)).fold(0.0, f32::max)
+}
+
+// Io's, spread around the equator like its paterae
+pub const IO_REGIONS: [EmissiveRegion; 4] = [
+    // 12°N 80°E
+    EmissiveRegion { center: Vec3 { x: 0.1699, y: 0.2079, z: 0.9633 }, size: 0.5 },
+    // 8°S 200°E
+    EmissiveRegion { center: Vec3 { x: -0.9305, y: -0.1392, z: -0.3387 }, size: 0.5 },
+    // 20°N 255°E
+    EmissiveRegion { center: Vec3 { x: -0.2432, y: 0.3420, z: -0.9077 }, size: 0.4 },
+    // 5°N 10°E
+    EmissiveRegion { center: Vec3 { x: 0.9811, y: 0.0872, z: 0.1730 }, size: 0.4 },
+];
+
+// What the hotspot noise averages to over a whole body
+const RESTING_GLOW: f32 = 0.56;
+
+pub fn volcanic_planet_shader(fragment: &Fragment) -> Color {
+    volcanic_shader_with(fragment, None)
+}
+
+pub fn io_shader(fragment: &Fragment) -> Color {
+    volcanic_shader_with(fragment, Some(&IO_REGIONS))
+}
+
+pub fn volcanic_shader_with(fragment: &Fragment, regions: Option<&[EmissiveRegion]>) -> Color {
+    let sulfur_yellow = Color::from_float(0.9, 0.8, 0.2);
+    let sulfur_orange = Color::from_float(0.8, 0.5, 0.1);
+    let sulfur_white = Color::from_float(0.95, 0.9, 0.7);
+    
+    let surface_pos = Vec3::new(
+        fragment.object_position.x * 2.5,
+        fragment.object_position.y * 2.5,
+        fragment.object_position.z * 2.5,
+    );
+    let surface_variation = fbm(&surface_pos, 4);
+    
+    let base_color = if surface_variation < 0.4 {
+        sulfur_yellow.mix(&sulfur_orange, ramp(0.0, 0.4, surface_variation))
+    } else {
+        sulfur_orange.mix(&sulfur_white, ramp(0.4, 1.0, surface_variation))
+    };
+    
+    // Away from the regions the surface keeps the hotspots' average glow, without their noise.
+    // The last value is how much of the hotspots glows in the dark: the resting glow doesn't
+    let (hotspot_intensity, lava_amount, heated) = match regions {
+        None => {
+            let (hotspot_intensity, lava_amount) = volcanic_emission(fragment);
+            (hotspot_intensity, lava_amount, hotspot_intensity)
+        }
+        Some(regions) => {
+            let coverage = emissive_coverage(regions, &fragment.object_position);
+            if coverage > 0.0 {
+                let (hotspot_intensity, lava_amount) = volcanic_emission(fragment);
+                (lerp(RESTING_GLOW, hotspot_intensity, coverage), lava_amount * coverage, hotspot_intensity * coverage)
+            } else {
+                (RESTING_GLOW, 0.0, 0.0)
+            }
+        }
+    };
+    
+    let edge_intensity = 1.0 - fragment.normal.dot(&Vec3::new(0.0, 0.0, 1.0)).abs();
+    let atmosphere_glow = edge_intensity.powf(2.0) * 0.3;
+    
+    let mut final_color = base_color;
+    
+    let lava_color = Color::from_float(1.0, 0.3, 0.0);
+    final_color = final_color.mix(&lava_color, lava_amount * 0.7);
+    
+    let hotspot_color = Color::from_float(1.0, 0.5, 0.0);
+    final_color = final_color.mix(&hotspot_color, hotspot_intensity * 0.9);
+    
+    // Heated hotspots glow on their own, even on the night side
+    let brightness = fragment.intensity * (0.7 + hotspot_intensity * 0.8 + atmosphere_glow)
+        + heated * fragment.heat * 0.15;
+    
+    Color::from_float(
+        final_color.r as f32 / 255.0 * brightness * (1.0 + hotspot_intensity * 0.5),
+        final_color.g as f32 / 255.0 * brightness * (1.0 + hotspot_intensity * 0.3),
+        final_color.b as f32 / 255.0 * brightness,
+    )
+}
+
+// Hotspot and lava strength at a point of the surface
+fn volcanic_emission(fragment: &Fragment) -> (f32, f32) {
+    let volcano_pos = Vec3::new(
+        fragment.object_position.x * 6.0,
+        fragment.object_position.y * 6.0,
+        fragment.object_position.z * 6.0 + fragment.time * 0.5,
+    );
+    let volcano_noise = turbulence(&volcano_pos, 4);
+    let hotspot_threshold = (0.75 - fragment.heat * 0.04).max(0.65);
+    let is_hotspot = volcano_noise > hotspot_threshold;
+    let hotspot_intensity = if is_hotspot {
+        ((volcano_noise - hotspot_threshold) * 4.0).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    
+    let lava_pos = Vec3::new(
+        fragment.object_position.x * 10.0,
+        fragment.object_position.y * 10.0 + fragment.time * 0.3,
+        fragment.object_position.z * 10.0,
+    );
+    // Lava only shows where its noise clears 0.65, so the noise stops early wherever it can't
+    let lava_amount = match fbm_above(&lava_pos, 3, 0.65) {
+        Some(lava_flow) if lava_flow > 0.65 => ((lava_flow - 0.65) * 2.86).clamp(0.0, 1.0),
+        _ => 0.0,
+    };
+
+    (hotspot_intensity, lava_amount)
+}
+
+// Shader 7: Hot Jupiter
+// A tidally locked giant roasting close to its star, parameterized by its equilibrium temperature
+// in kelvin. Winds push the hottest spot east of the point under the star, and carry the heat
+// around in streaks; the night side is far cooler.
+pub fn hot_jupiter_shader(fragment: &Fragment) -> Color {
+    hot_jupiter_shader_with(fragment, 1500.0)
+}
+
+pub fn hot_jupiter_shader_with(fragment: &Fragment, equilibrium_temperature: f32) -> Color {
+    let p = fragment.object_position.normalize();
+    let latitude = p.y.clamp(-1.0, 1.0).asin();
+
+    // Jets stretch the clouds into streaks along the latitude lines
+    let streak_pos = Vec3::new(p.x * 2.0 + fragment.time * 0.1, p.y * 30.0, p.z * 2.0);
+    let streaks = fbm(&streak_pos, 4);
+    let bands = (latitude * 9.0).sin() * 0.5 + 0.5;
+
+    // The hotspot sits about 30 degrees east of the substellar point, and the streaks smear it
+    let hotspot = fragment.light_dir.rotate_y(-0.5);
+    let facing = p.dot(&hotspot) + (streaks - 0.5) * 0.3;
+    let day = facing.max(0.0).powf(0.5);
+    let temperature = equilibrium_temperature * (0.55 + 0.75 * day) * (0.95 + bands * 0.1);
+
+    // Reflected light off dark, hazy clouds, then the thermal glow on top
+    let cloud_dark = Color::from_float(0.12, 0.08, 0.07);
+    let cloud_light = Color::from_float(0.3, 0.22, 0.18);
+    let clouds = cloud_dark.mix(&cloud_light, bands * 0.6 + streaks * 0.4);
+    let glow = blackbody_color(temperature);
+    let emission = ((temperature - 700.0) / 2400.0).clamp(0.0, 1.0).powf(1.5);
+
+    Color::from_float(
+        clouds.r as f32 / 255.0 * fragment.intensity + glow.r as f32 / 255.0 * emission,
+        clouds.g as f32 / 255.0 * fragment.intensity + glow.g as f32 / 255.0 * emission,
+        clouds.b as f32 / 255.0 * fragment.intensity + glow.b as f32 / 255.0 * emission,
+    )
+}
+
+// Shader 8: Rogue Planet
+// A planet with no star, frozen over and lit only by starlight. Internal heat (`Fragment::heat`)
+// glows through the cracks in its crust.
+pub fn rogue_planet_shader(fragment: &Fragment) -> Color {
+    let ice_dark = Color::from_float(0.25, 0.27, 0.32);
+    let ice_light = Color::from_float(0.5, 0.53, 0.6);
+
+    let crust_pos = fragment.object_position * 3.0;
+    let crust = fbm(&crust_pos, 5);
+    let base_color = ice_dark.mix(&ice_light, crust);
+
+    // Ridged noise is near zero along thin, connected lines: the cracks
+    let crack_pos = fragment.object_position * 5.0;
+    let ridge = (smooth_noise_3d(&crack_pos) * 2.0 - 1.0).abs() + (smooth_noise_3d(&(crack_pos * 4.0)) - 0.5) * 0.08;
+    let crack = (1.0 - ridge / 0.05).clamp(0.0, 1.0);
+
+    let glow_color = Color::from_float(1.0, 0.35, 0.05);
+    let glow = crack * fragment.heat.min(3.0);
+
+    let final_color = base_color.mix(&Color::from_float(0.05, 0.04, 0.04), crack);
+    Color::from_float(
+        final_color.r as f32 / 255.0 * fragment.intensity + glow_color.r as f32 / 255.0 * glow,
+        final_color.g as f32 / 255.0 * fragment.intensity + glow_color.g as f32 / 255.0 * glow,
+        final_color.b as f32 / 255.0 * fragment.intensity + glow_color.b as f32 / 255.0 * glow,
+    )
+}
+
+// Shader 9: Carbon Planet
+// Dark graphite plains scattered with diamond facets that glint in the light, under a thin
+// brownish smog
+pub fn carbon_planet_shader(fragment: &Fragment) -> Color {
+    let p = fragment.object_position.normalize();
+    let graphite_dark = Color::from_float(0.07, 0.07, 0.08);
+    let graphite_light = Color::from_float(0.2, 0.19, 0.2);
+
+    let terrain = fbm(&(p * 3.0), 4) * 0.5 + smooth_noise_3d(&(p * 4.0)) * 0.5;
+    let mut final_color = graphite_dark.mix(&graphite_light, terrain);
+
+    // Each cell is a facet tilted its own way; only some of them are diamond
+    let (nearest, second, cell) = worley_3d(&(p * 40.0));
+    let random = |offset: f32| noise_3d(&(cell + Vec3::new(offset, offset * 0.7, offset * 1.3)));
+    let tilt = Vec3::new(random(2.1) - 0.5, random(3.7) - 0.5, random(5.3) - 0.5);
+    let facet_normal = (p + tilt * 0.8).normalize();
+    let diamond = random(7.9) > 0.55;
+
+    // Facet edges are dark seams
+    let seam = (1.0 - (second - nearest) / 0.08).clamp(0.0, 1.0);
+    final_color = final_color.mix(&Color::from_float(0.02, 0.02, 0.02), seam * 0.6);
+
+    let glint = if diamond { specular_highlight(fragment, &facet_normal) } else { 0.0 };
+    let sheen = specular_highlight(fragment, &p) * 0.15;
+    let highlight = (glint + sheen) * fragment.intensity;
+
+    // Smog thickens toward the limb, where we look through more of it
+    let limb = 1.0 - p.dot(&fragment.view_dir).clamp(0.0, 1.0);
+    let smog = Color::from_float(0.4, 0.32, 0.2);
+    let haze = limb.powf(3.0) * 0.5 * fragment.intensity;
+
+    Color::from_float(
+        final_color.r as f32 / 255.0 * fragment.intensity + highlight * 0.9 + smog.r as f32 / 255.0 * haze,
+        final_color.g as f32 / 255.0 * fragment.intensity + highlight * 0.95 + smog.g as f32 / 255.0 * haze,
+        final_color.b as f32 / 255.0 * fragment.intensity + highlight + smog.b as f32 / 255.0 * haze,
+    )
+}
+
+// Blinn-Phong highlight off a surface normal in the body's frame, scaled by the body's specular
+// strength. Rougher surfaces spread it wider and dimmer.
+pub fn specular_highlight(fragment: &Fragment, normal: &Vec3) -> f32 {
+    if fragment.specular <= 0.0 || normal.dot(&fragment.light_dir) <= 0.0 {
+        return 0.0;
+    }
+    let half = (fragment.light_dir + fragment.view_dir).normalize();
+    let exponent = (2.0 / fragment.roughness.powi(2) - 2.0).max(1.0);
+    fragment.specular * normal.dot(&half).max(0.0).powf(exponent) * (exponent + 8.0) / (8.0 * PI)
+}
+
+// Rough color of something glowing at a temperature in kelvin, from dull red to yellow-white
+pub fn blackbody_color(temperature: f32) -> Color {
+    let dull_red = Color::from_float(0.35, 0.03, 0.0);
+    let orange = Color::from_float(1.0, 0.4, 0.05);
+    let yellow_white = Color::from_float(1.0, 0.9, 0.7);
+    let t = ((temperature - 800.0) / 2200.0).clamp(0.0, 1.0);
+    if t < 0.5 {
+        dull_red.mix(&orange, t * 2.0)
+    } else {
+        orange.mix(&yellow_white, (t - 0.5) * 2.0)
+    }
+}
+
+// Terrain heights: the same noise the surface shaders use, reduced to a single elevation value
+pub fn rocky_planet_height(p: &Vec3) -> f32 {
+    let continent_noise = fbm(&(*p * 2.0), 5);
+    let terrain = fbm(&(*p * 10.0), 4);
+
+    if continent_noise > 0.48 {
+        0.5 + terrain * 0.5
+    } else {
+        (continent_noise / 0.48) * 0.5
+    }
+}
+
+pub fn desert_planet_height(p: &Vec3) -> f32 {
+    let terrain = fbm(&(*p * 3.0), 5);
+    let craters = turbulence(&(*p * 8.0), 3);
+    let crater_effect = (craters - 0.7).max(0.0) * 3.0;
+
+    terrain - crater_effect * 0.1
+}
+
+pub fn volcanic_planet_height(p: &Vec3) -> f32 {
+    let surface_variation = fbm(&(*p * 2.5), 4);
+    let volcano_noise = turbulence(&(*p * 6.0), 4);
+    let peak = ((volcano_noise - 0.75) * 4.0).clamp(0.0, 1.0);
+
+    surface_variation + peak * 0.3
+}
+
+pub fn moon_height(p: &Vec3) -> f32 {
+    let surface_variation = fbm(&(*p * 4.0), 4);
+    let craters = turbulence(&(*p * 12.0), 4);
+    let crater_depth = ramp(0.7, 1.0, craters);
+
+    surface_variation - crater_depth * 0.2
+}
+
+// Auroral ovals around the magnetic poles, which need not sit on the rotation poles
+#[derive(Clone, Copy)]
+pub struct Aurora {
+    // How far magnetic north is from the rotation pole, and toward which longitude, in degrees
+    pub pole_offset: f32,
+    pub pole_longitude: f32,
+    // Angular radius of the ovals around the magnetic poles, in degrees
+    pub oval_radius: f32,
+    // Brightness, and how much it flares and fades over time
+    pub activity: f32,
+    pub variability: f32,
+}
+
+impl Default for Aurora {
+    // Earth's: the magnetic pole is about 11° off and the oval rings it some 20° out
+    fn default() -> Self {
+        Aurora { pole_offset: 11.0, pole_longitude: -72.0, oval_radius: 20.0, activity: 1.0, variability: 0.5 }
+    }
+}
+
+impl Aurora {
+    // Magnetic north in the body's frame
+    pub fn pole(&self) -> Vec3 {
+        let (offset, longitude) = (self.pole_offset.to_radians(), self.pole_longitude.to_radians());
+        Vec3::new(offset.sin() * longitude.cos(), offset.cos(), offset.sin() * longitude.sin())
+    }
+
+    pub fn glow(&self, fragment: &Fragment) -> f32 {
+        let p = fragment.object_position.normalize();
+
+        // Distance from the nearer magnetic pole, so the southern oval mirrors the northern one
+        let colatitude = p.dot(&self.pole()).abs().min(1.0).acos().to_degrees();
+        let width = self.oval_radius * 0.2;
+        let oval = (-((colatitude - self.oval_radius) / width).powi(2)).exp();
+        if oval < 0.01 {
+            return 0.0;
+        }
+
+        // Curtains folding along the oval, drifting, and the whole display flaring and fading
+        let curtains = smooth_noise_3d(&(p * 12.0 + Vec3::new(fragment.time * 0.3, 0.0, 0.0)));
+        let flare = 1.0 + self.variability * (smooth_noise_3d(&Vec3::new(fragment.time * 0.5, 3.7, 1.1)) * 2.0 - 1.0);
+
+        // Washed out on the day side
+        let daylight = (p.dot(&fragment.light_dir) * 4.0 + 0.5).clamp(0.0, 1.0);
+        oval * (0.4 + 0.6 * curtains) * self.activity * flare.max(0.0) * (1.0 - 0.85 * daylight)
+    }
+
+    // Oxygen's green low down, and its red on the poleward fringe where the curtains reach highest
+    pub fn color(&self, fragment: &Fragment) -> Color {
+        let p = fragment.object_position.normalize();
+        let colatitude = p.dot(&self.pole()).abs().min(1.0).acos().to_degrees();
+        let fringe = ((self.oval_radius - colatitude) / (self.oval_radius * 0.2)).clamp(0.0, 1.0);
+        Color::from_float(0.3, 1.0, 0.55).mix(&Color::from_float(1.0, 0.25, 0.35), fringe * 0.7)
+    }
+}
+
+// Adds an aurora on top of a lit planet; it shines by itself, so the light doesn't tint it
+pub fn with_aurora(
+    shader: impl Fn(&Fragment) -> (Color, f32),
+    aurora: Option<Aurora>,
+) -> impl Fn(&Fragment) -> (Color, f32) {
+    move |fragment| {
+        let (color, alpha) = shader(fragment);
+        let Some(aurora) = &aurora else { return (color, alpha) };
+        let glow = aurora.glow(fragment);
+        if glow <= 0.0 {
+            return (color, alpha);
+        }
+        let light = aurora.color(fragment);
+        let add = |base: u8, light: u8| (base as f32 + light as f32 * glow).min(255.0) as u8;
+        (Color::new(add(color.r, light.r), add(color.g, light.g), add(color.b, light.b)), alpha)
+    }
+}
+
+// Shader lookup by the names used in metadata and on the command line
+pub fn shader_by_name(name: &str) -> Option<fn(&Fragment) -> Color> {
+    match name {
+        "sun" => Some(sun_shader),
+        "rocky" | "rocky_planet" => Some(rocky_planet_shader),
+        "gas_giant" => Some(gas_giant_shader),
+        "ice_giant" => Some(ice_giant_shader),
+        "desert" | "desert_planet" => Some(desert_planet_shader),
+        "volcanic" | "volcanic_planet" => Some(volcanic_planet_shader),
+        "moon" => Some(moon_shader),
+        "europa" => Some(europa_shader),
+        "hot_jupiter" => Some(hot_jupiter_shader),
+        "rogue" | "rogue_planet" => Some(rogue_planet_shader),
+        "carbon" | "carbon_planet" => Some(carbon_planet_shader),
+        _ => None,
+    }
+}
+
+// Canonical name of every shader that shader_by_name knows
+pub const SHADER_NAMES: [&str; 11] = [
+    "sun",
+    "rocky_planet",
+    "gas_giant",
+    "ice_giant",
+    "desert_planet",
+    "volcanic_planet",
+    "moon",
+    "hot_jupiter",
+    "rogue_planet",
+    "carbon_planet",
+    "europa",
+];
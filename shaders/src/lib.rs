@@ -0,0 +1,85 @@
+// Software rasterizer and procedural planet shaders. The modules build on each other roughly in
+// this order: `kernel` (plain color and vector math, noise and the planet shaders), meshes, the
+// per-frame settings and the timeline that keys them over an animation, the rasterizer and the
+// draw commands recorded for it, the shaders' configuration from those settings, whole scenes,
+// post-processing of finished frames, and writing them out.
+// `cli` and `app` are the command-line driver that the binary runs, `viewer` its live window and
+// `server` its HTTP front end.
+//
+// Everything but `kernel` needs std; building without the default `std` feature leaves just
+// `kernel`, for targets that only have an allocator. (It isn't called `core` so that it doesn't
+// shadow the built-in crate.) The command-line driver and PNG output are default features that
+// an embedder can leave out, and outputs and front ends with dependencies of their own (video,
+// scene files, the preview window) have features of their own, off by default; see Cargo.toml.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod kernel;
+#[cfg(feature = "cli")]
+pub mod app;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "std")]
+pub mod commands;
+#[cfg(feature = "std")]
+pub mod estimate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod geometry;
+#[cfg(feature = "cli")]
+pub mod interrupt;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "std")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod logging;
+#[cfg(feature = "std")]
+pub mod mesh_cache;
+#[cfg(feature = "std")]
+pub mod post;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod raster;
+#[cfg(feature = "std")]
+pub mod scene;
+#[cfg(feature = "scene-files")]
+pub mod scene_file;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "cli")]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod settings;
+#[cfg(feature = "std")]
+pub mod shaders;
+#[cfg(feature = "std")]
+pub mod timeline;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+
+// `kernel`'s modules at the paths they had before it was split out
+pub use kernel::{color, math, noise};
+pub use kernel::orbit::Orbit;
+
+pub use color::{Color, Theme};
+pub use math::Vec3;
+pub use kernel::shaders::{Fragment, SHADER_NAMES, shader_by_name};
+#[cfg(feature = "std")]
+pub use geometry::{Mesh, Transform, generate_ring, generate_sphere};
+#[cfg(feature = "std")]
+pub use io::{ImageFormat, save_ppm, save_ppm_binary};
+#[cfg(feature = "png")]
+pub use io::{save_png, save_png_rgba};
+#[cfg(feature = "std")]
+pub use raster::{BlendMode, Framebuffer, Material, RasterTarget, lit, opaque, render_triangle};
+#[cfg(feature = "std")]
+pub use scene::{Moon, Scene, Turntable, render_planet_with_moon, render_planet_with_rings, render_sphere};
+#[cfg(feature = "std")]
+pub use settings::{Light, Lighting, RenderSettings};
+#[cfg(feature = "std")]
+pub use shaders::configured_shader;
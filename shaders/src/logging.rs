@@ -0,0 +1,47 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Progress (info) goes to stdout as plain lines, so the default output reads as it always has.
+// Everything else goes to stderr with its level in front, and debug and trace also name the
+// module they come from.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Info => println!("{}", record.args()),
+            Level::Error => eprintln!("error: {}", record.args()),
+            Level::Warn => eprintln!("warning: {}", record.args()),
+            Level::Debug => eprintln!("debug [{}]: {}", record.target(), record.args()),
+            Level::Trace => eprintln!("trace [{}]: {}", record.target(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+// Installs the logger. Only the first call does anything, so a driver can call it freely.
+pub fn init(level: LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+// The level for a number of -v (positive) or -q (negative) switches, from info by default
+pub fn level_for(verbosity: i32) -> LevelFilter {
+    match verbosity {
+        i32::MIN..=-2 => LevelFilter::Error,
+        -1 => LevelFilter::Warn,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
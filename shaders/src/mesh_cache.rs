@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+
+use crate::geometry::{Mesh, debris_seed, generate_debris, generate_ring, generate_ring_edge, generate_sphere};
+use crate::scene::Moon;
+
+// Meshes by everything they are generated from, so animation frames and batch renders build each
+// one once instead of once per frame. Floats are keyed by their bits: meshes built from the same
+// numbers are the same mesh.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MeshKey {
+    Sphere { radius: u32, segments: usize },
+    Ring { inner_radius: u32, outer_radius: u32, segments: usize },
+    RingEdge { radius: u32, thickness: u32, segments: usize },
+    // The rubble of a disrupted moon: its layout is seeded by the moon's name
+    Debris { seed: u64, radius: u32, distance: u32, height: u32, orbit_angle: u32, spread: u32 },
+}
+
+impl fmt::Display for MeshKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = f32::from_bits;
+        match *self {
+            MeshKey::Sphere { radius, segments } => write!(f, "sphere of radius {} ({} segments)", value(radius), segments),
+            MeshKey::Ring { inner_radius, outer_radius, segments } => {
+                write!(f, "ring from {} to {} ({} segments)", value(inner_radius), value(outer_radius), segments)
+            }
+            MeshKey::RingEdge { radius, thickness, segments } => {
+                write!(f, "ring edge at {}, {} thick ({} segments)", value(radius), value(thickness), segments)
+            }
+            MeshKey::Debris { seed, spread, .. } => write!(f, "debris {:x} spread {}", seed, value(spread)),
+        }
+    }
+}
+
+// How many meshes are kept; once full, further meshes are built every time they are asked for
+pub const DEFAULT_CAPACITY: usize = 64;
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_CAPACITY);
+static MESHES: Mutex<Option<HashMap<MeshKey, Arc<Mesh>>>> = Mutex::new(None);
+
+// 0 turns the cache off and drops what it holds
+pub fn set_capacity(meshes: usize) {
+    CAPACITY.store(meshes, Ordering::Relaxed);
+    if meshes == 0 {
+        *MESHES.lock().unwrap() = None;
+    }
+}
+
+// The mesh for `key`, built by `build` the first time. The lock isn't held while building, so
+// two threads asking at once may both build it; the first one stored wins.
+fn cached(key: MeshKey, build: impl FnOnce() -> Mesh) -> Arc<Mesh> {
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return Arc::new(build());
+    }
+    if let Some(mesh) = MESHES.lock().unwrap().as_ref().and_then(|meshes| meshes.get(&key)) {
+        return Arc::clone(mesh);
+    }
+
+    let mesh = Arc::new(build());
+    let mut meshes = MESHES.lock().unwrap();
+    let meshes = meshes.get_or_insert_with(HashMap::new);
+    if meshes.len() >= capacity {
+        return mesh;
+    }
+    match meshes.entry(key) {
+        Entry::Occupied(stored) => Arc::clone(stored.get()),
+        Entry::Vacant(slot) => {
+            debug!("keeping the {}: {} vertices, {} triangles", key, mesh.vertices.len(), mesh.indices.len());
+            Arc::clone(slot.insert(mesh))
+        }
+    }
+}
+
+pub fn sphere(radius: f32, segments: usize) -> Arc<Mesh> {
+    cached(MeshKey::Sphere { radius: radius.to_bits(), segments }, || generate_sphere(radius, segments))
+}
+
+pub fn ring(inner_radius: f32, outer_radius: f32, segments: usize) -> Arc<Mesh> {
+    let key = MeshKey::Ring { inner_radius: inner_radius.to_bits(), outer_radius: outer_radius.to_bits(), segments };
+    cached(key, || generate_ring(inner_radius, outer_radius, segments))
+}
+
+pub fn ring_edge(radius: f32, thickness: f32, segments: usize) -> Arc<Mesh> {
+    let key = MeshKey::RingEdge { radius: radius.to_bits(), thickness: thickness.to_bits(), segments };
+    cached(key, || generate_ring_edge(radius, thickness, segments))
+}
+
+pub fn debris(moon: &Moon, spread: f32) -> Arc<Mesh> {
+    let key = MeshKey::Debris {
+        seed: debris_seed(moon),
+        radius: moon.radius.to_bits(),
+        distance: moon.distance.to_bits(),
+        height: moon.height.to_bits(),
+        orbit_angle: moon.orbit_angle.to_bits(),
+        spread: spread.to_bits(),
+    };
+    cached(key, || generate_debris(moon, spread))
+}
@@ -0,0 +1,573 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::math::Rng;
+
+// Caption strip: a tiny 5x7 bitmap font, one bit per pixel with the leftmost column in bit 4
+pub fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '|' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        '/' => [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10],
+        _ => [0x00; 7],
+    }
+}
+
+pub fn draw_text(buffer: &mut [u32], width: usize, x: usize, y: usize, text: &str, scale: usize, color: Color) {
+    let height = buffer.len() / width;
+    for (i, c) in text.chars().enumerate() {
+        let origin_x = x + i * 6 * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..5 {
+                if bits & (0x10 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let px = origin_x + col * scale + dx;
+                        let py = y + row * scale + dy;
+                        if px < width && py < height {
+                            buffer[py * width + px] = color.to_u32();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Appends a dark strip with one line of text per entry under a render
+pub fn add_caption_strip(buffer: &[u32], width: usize, height: usize, lines: &[String]) -> (Vec<u32>, usize) {
+    let scale = 2;
+    let line_height = 10 * scale;
+    let strip_height = lines.len() * line_height + 2 * line_height;
+
+    let mut captioned = buffer.to_vec();
+    captioned.resize(width * (height + strip_height), Color::new(16, 16, 24).to_u32());
+
+    for (i, line) in lines.iter().enumerate() {
+        let y = height + line_height + i * line_height;
+        draw_text(&mut captioned, width, line_height, y, line, scale, Color::new(220, 220, 230));
+    }
+
+    (captioned, height + strip_height)
+}
+
+// What shows through where no body covers the frame
+#[derive(Clone, Copy)]
+pub enum Background {
+    Solid(Color),
+    // Vertical gradient, top color first
+    Gradient(Color, Color),
+    Transparent,
+}
+
+impl Background {
+    // "transparent", "#rrggbb", or "gradient:#rrggbb:#rrggbb"
+    pub fn parse(value: &str) -> Option<Background> {
+        if value == "transparent" {
+            return Some(Background::Transparent);
+        }
+        if let Some(colors) = value.strip_prefix("gradient:") {
+            let (top, bottom) = colors.split_once(':')?;
+            return Some(Background::Gradient(Color::from_hex(top)?, Color::from_hex(bottom)?));
+        }
+        if value == "black" {
+            return Some(Background::Solid(Color::new(0, 0, 0)));
+        }
+        Color::from_hex(value).map(Background::Solid)
+    }
+}
+
+// Composites the premultiplied render over the background; transparent backgrounds keep their alpha
+pub fn apply_background(buffer: &mut [u32], width: usize, background: Background) {
+    let height = buffer.len() / width.max(1);
+    apply_background_rows(buffer, width, 0, height, background);
+}
+
+// The background behind a band of a frame `height` rows tall, starting at `first_row`
+pub fn apply_background_rows(buffer: &mut [u32], width: usize, first_row: usize, height: usize, background: Background) {
+    if let Background::Transparent = background {
+        return;
+    }
+
+    for (i, pixel) in buffer.iter_mut().enumerate() {
+        let bg = match background {
+            Background::Solid(color) => color,
+            Background::Gradient(top, bottom) => {
+                top.mix(&bottom, (first_row + i / width) as f32 / (height.max(2) - 1) as f32)
+            }
+            Background::Transparent => unreachable!(),
+        };
+        let coverage = 1.0 - ((*pixel >> 24) & 0xFF) as f32 / 255.0;
+        let over = |shift: u32, bg: u8| ((*pixel >> shift) & 0xFF) + (bg as f32 * coverage) as u32;
+        *pixel = 0xFF00_0000 | over(16, bg.r).min(255) << 16 | over(8, bg.g).min(255) << 8 | over(0, bg.b).min(255);
+    }
+}
+
+// Mattes: per-pixel coverage in [0, 1] multiplied into the render before the background goes under it
+pub fn circle_matte(width: usize, height: usize, radius: f32, feather: f32) -> Vec<f32> {
+    let center_x = width as f32 / 2.0;
+    let center_y = height as f32 / 2.0;
+    let mut mask = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let dx = x as f32 + 0.5 - center_x;
+            let dy = y as f32 + 0.5 - center_y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            mask.push(((radius - distance) / feather.max(1e-3) + 0.5).clamp(0.0, 1.0));
+        }
+    }
+    mask
+}
+
+pub fn apply_matte(buffer: &mut [u32], mask: &[f32]) {
+    for (pixel, &coverage) in buffer.iter_mut().zip(mask) {
+        let scale = |shift: u32| ((((*pixel >> shift) & 0xFF) as f32 * coverage) as u32) << shift;
+        *pixel = scale(24) | scale(16) | scale(8) | scale(0);
+    }
+}
+
+// Box-filter weights mapping `src` samples onto `dst` samples by exact area overlap
+pub fn area_weights(src: usize, dst: usize) -> Vec<Vec<(usize, f32)>> {
+    let ratio = src as f32 / dst as f32;
+    (0..dst)
+        .map(|i| {
+            let start = i as f32 * ratio;
+            let end = start + ratio;
+            let mut weights = Vec::new();
+            let mut j = start.floor() as usize;
+            while (j as f32) < end && j < src {
+                let overlap = (end.min(j as f32 + 1.0) - start.max(j as f32)).max(0.0);
+                if overlap > 0.0 {
+                    weights.push((j, overlap / ratio));
+                }
+                j += 1;
+            }
+            weights
+        })
+        .collect()
+}
+
+// A telescope's point spread function, applied to whatever is bright enough in the render:
+// diffraction spikes from the vanes holding the secondary mirror, and a soft halo
+#[derive(Clone, Copy)]
+pub struct Telescope {
+    // 4 gives Hubble's cross; 6 gives Webb's six spikes, with its fainter horizontal pair
+    pub vanes: u32,
+    // How far the spikes reach before fading to about a third, in pixels
+    pub spike_length: f32,
+    pub spike_strength: f32,
+    pub halo: f32,
+    // Brightness above which pixels spread into spikes and halo
+    pub threshold: f32,
+}
+
+impl Telescope {
+    pub fn preset(name: &str) -> Option<Telescope> {
+        match name {
+            "hst" | "hubble" => Some(Telescope { vanes: 4, spike_length: 160.0, spike_strength: 0.3, halo: 0.15, threshold: 0.9 }),
+            "jwst" | "webb" => Some(Telescope { vanes: 6, spike_length: 200.0, spike_strength: 0.35, halo: 0.1, threshold: 0.9 }),
+            _ => None,
+        }
+    }
+
+    // Spike directions in screen space, as (angle, strength)
+    pub fn spikes(&self) -> Vec<(f32, f32)> {
+        match self.vanes {
+            4 => vec![(0.0, 1.0), (PI / 2.0, 1.0)],
+            _ => vec![(PI / 2.0, 1.0), (PI / 6.0, 1.0), (5.0 * PI / 6.0, 1.0), (0.0, 0.35)],
+        }
+    }
+}
+
+// Spreads the bright parts of a premultiplied render through the telescope's PSF. The PSF is
+// wide, so it is worked out at half resolution and added back smoothly.
+pub fn apply_psf(buffer: &mut [u32], width: usize, height: usize, telescope: &Telescope) {
+    let (low_width, low_height) = (width / 2, height / 2);
+    if low_width == 0 || low_height == 0 {
+        return;
+    }
+    let small = downsample(buffer, width, height, low_width, low_height);
+
+    let bright: Vec<[f32; 3]> = small
+        .iter()
+        .map(|&pixel| {
+            let [r, g, b] = [16, 8, 0].map(|shift| ((pixel >> shift) & 0xFF) as f32 / 255.0);
+            // By the brightest channel, so saturated colors like the sun's orange still count
+            let excess = ((r.max(g).max(b) - telescope.threshold) / (1.0 - telescope.threshold).max(1e-3)).max(0.0);
+            [r * excess, g * excess, b * excess]
+        })
+        .collect();
+
+    // Spikes: every bright pixel sends out exponentially fading rays along the vanes' directions,
+    // each ray weighted so a spike's total light stays the same whatever its length
+    let length = (telescope.spike_length / 2.0).max(1.0);
+    let mut glow = vec![[0.0f32; 3]; low_width * low_height];
+    let spikes = telescope.spikes();
+    for y in 0..low_height {
+        for x in 0..low_width {
+            let source = bright[y * low_width + x];
+            if source == [0.0; 3] {
+                continue;
+            }
+            for &(angle, strength) in &spikes {
+                let weight = telescope.spike_strength * strength / length;
+                for sign in [-1.0, 1.0] {
+                    let (dx, dy) = (angle.cos() * sign, -angle.sin() * sign);
+                    for step in 1..(length * 3.0) as usize {
+                        let (sx, sy) = (x as f32 + dx * step as f32, y as f32 + dy * step as f32);
+                        if sx < 0.0 || sy < 0.0 || sx >= low_width as f32 || sy >= low_height as f32 {
+                            break;
+                        }
+                        let falloff = weight * (-(step as f32) / length).exp();
+                        let target = &mut glow[sy as usize * low_width + sx as usize];
+                        for k in 0..3 {
+                            target[k] += source[k] * falloff;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Halo: three box blurs make a wide, roughly Gaussian glow
+    let mut halo = bright;
+    for _ in 0..3 {
+        halo = box_blur(&halo, low_width, low_height, 12);
+    }
+    for (target, halo) in glow.iter_mut().zip(&halo) {
+        for k in 0..3 {
+            target[k] += halo[k] * telescope.halo * 4.0;
+        }
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            // Bilinear lookup of the half-resolution glow
+            let fx = ((x as f32 + 0.5) / 2.0 - 0.5).clamp(0.0, (low_width - 1) as f32);
+            let fy = ((y as f32 + 0.5) / 2.0 - 0.5).clamp(0.0, (low_height - 1) as f32);
+            let (x0, y0) = (fx as usize, fy as usize);
+            let (x1, y1) = ((x0 + 1).min(low_width - 1), (y0 + 1).min(low_height - 1));
+            let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+            let sample = |k: usize| {
+                let top = glow[y0 * low_width + x0][k] * (1.0 - tx) + glow[y0 * low_width + x1][k] * tx;
+                let bottom = glow[y1 * low_width + x0][k] * (1.0 - tx) + glow[y1 * low_width + x1][k] * tx;
+                top * (1.0 - ty) + bottom * ty
+            };
+            let added = [sample(0), sample(1), sample(2)];
+            if added.iter().all(|&c| c < 1.0 / 255.0) {
+                continue;
+            }
+
+            let pixel = &mut buffer[y * width + x];
+            let channel = |shift: u32, add: f32| ((((*pixel >> shift) & 0xFF) as f32 + add * 255.0).min(255.0)) as u32;
+            let alpha = channel(24, added.iter().copied().fold(0.0, f32::max));
+            *pixel = alpha << 24 | channel(16, added[0]) << 16 | channel(8, added[1]) << 8 | channel(0, added[2]);
+        }
+    }
+}
+
+// Box blur of a float RGB image, horizontally then vertically
+pub fn box_blur(image: &[[f32; 3]], width: usize, height: usize, radius: usize) -> Vec<[f32; 3]> {
+    let pass = |image: &[[f32; 3]], along_x: bool| {
+        let mut output = vec![[0.0f32; 3]; width * height];
+        let (lines, length) = if along_x { (height, width) } else { (width, height) };
+        let index = |line: usize, i: usize| if along_x { line * width + i } else { i * width + line };
+        for line in 0..lines {
+            for i in 0..length {
+                let (start, end) = (i.saturating_sub(radius), (i + radius).min(length - 1));
+                let mut sum = [0.0f32; 3];
+                for j in start..=end {
+                    for k in 0..3 {
+                        sum[k] += image[index(line, j)][k];
+                    }
+                }
+                let count = (2 * radius + 1) as f32;
+                output[index(line, i)] = sum.map(|c| c / count);
+            }
+        }
+        output
+    };
+    pass(&pass(image, true), false)
+}
+
+// A camera sensor the render is "exposed" on, for an astrophotography look
+#[derive(Clone, Copy)]
+pub struct Sensor {
+    // Relative exposure time: longer is brighter and less noisy, and saturates sooner
+    pub exposure: f32,
+    // Electrons of read noise per pixel, against a full well of SENSOR_FULL_WELL
+    pub read_noise: f32,
+    // Fraction of pixels with runaway dark current
+    pub hot_pixels: f32,
+    // Light lost toward the corners, 0 for none
+    pub vignetting: f32,
+    // Whether charge past a full well spills along the column, as on a CCD
+    pub bleed: bool,
+    // Seeds the hot pixels, which stay put from frame to frame, and the noise
+    pub seed: u64,
+}
+
+impl Default for Sensor {
+    fn default() -> Self {
+        Sensor { exposure: 1.0, read_noise: 8.0, hot_pixels: 0.0005, vignetting: 0.25, bleed: true, seed: 1 }
+    }
+}
+
+// Electrons a pixel holds when the render is at full brightness, at an exposure of 1
+pub const SENSOR_FULL_WELL: f32 = 1000.0;
+
+// Standard normal sample (Box-Muller)
+pub fn gaussian(rng: &mut Rng) -> f32 {
+    let u = rng.next_f32().max(1e-7);
+    let v = rng.next_f32();
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
+
+// Exposes a composited render on the sensor. `frame` varies the noise between animation frames.
+pub fn apply_sensor(buffer: &mut [u32], width: usize, height: usize, sensor: &Sensor, frame: u64) {
+    // Signal in electrons per channel, dimmed toward the corners
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+    let corner = (center_x * center_x + center_y * center_y).sqrt();
+    let mut electrons: Vec<[f32; 3]> = buffer
+        .iter()
+        .enumerate()
+        .map(|(i, &pixel)| {
+            let (dx, dy) = ((i % width) as f32 + 0.5 - center_x, (i / width) as f32 + 0.5 - center_y);
+            let falloff = 1.0 - sensor.vignetting * ((dx * dx + dy * dy) / (corner * corner));
+            [16, 8, 0].map(|shift| ((pixel >> shift) & 0xFF) as f32 / 255.0 * SENSOR_FULL_WELL * sensor.exposure * falloff)
+        })
+        .collect();
+
+    // Blooming: charge over the full well runs up and down the column, half each way, and fills
+    // the pixels it passes until it is used up
+    if sensor.bleed {
+        let excess: Vec<[f32; 3]> = electrons.iter().map(|charge| charge.map(|c| (c - SENSOR_FULL_WELL).max(0.0) / 2.0)).collect();
+        for charge in electrons.iter_mut() {
+            *charge = charge.map(|c| c.min(SENSOR_FULL_WELL));
+        }
+        let flow = |charge: &mut [f32; 3], carried: &mut [f32; 3], excess: &[f32; 3]| {
+            for k in 0..3 {
+                carried[k] += excess[k];
+                let taken = carried[k].min(SENSOR_FULL_WELL - charge[k]);
+                charge[k] += taken;
+                carried[k] -= taken;
+            }
+        };
+        for x in 0..width {
+            let mut carried = [0.0f32; 3];
+            for y in 0..height {
+                flow(&mut electrons[y * width + x], &mut carried, &excess[y * width + x]);
+            }
+            let mut carried = [0.0f32; 3];
+            for y in (0..height).rev() {
+                flow(&mut electrons[y * width + x], &mut carried, &excess[y * width + x]);
+            }
+        }
+    }
+
+    let mut hot = Rng::new(sensor.seed);
+    let mut noise = Rng::new(sensor.seed ^ frame.wrapping_mul(0x9E37_79B9_7F4A_7C15).wrapping_add(1));
+    for (pixel, signal) in buffer.iter_mut().zip(&electrons) {
+        let dark = if hot.next_f32() < sensor.hot_pixels { hot.range(0.3, 1.0) * SENSOR_FULL_WELL * sensor.exposure } else { 0.0 };
+        let alpha = (*pixel >> 24) & 0xFF;
+        let channel = |k: usize, noise: &mut Rng| {
+            // Shot noise grows with the square root of the collected charge
+            let charge = signal[k] + dark;
+            let read = charge + charge.sqrt() * gaussian(noise) + sensor.read_noise * gaussian(noise);
+            let value = (read.min(SENSOR_FULL_WELL) / SENSOR_FULL_WELL * 255.0).round().clamp(0.0, 255.0) as u32;
+            // Transparent areas stay transparent
+            value.min(alpha)
+        };
+        *pixel = alpha << 24 | channel(0, &mut noise) << 16 | channel(1, &mut noise) << 8 | channel(2, &mut noise);
+    }
+}
+
+// Pixel-art stylization: a low-resolution copy quantized to a fixed palette, scaled back up
+#[derive(Clone)]
+pub struct PixelArt {
+    // Width of the low-resolution image in pixels
+    pub size: usize,
+    pub palette: Vec<Color>,
+    pub dither: bool,
+}
+
+pub const PICO8_PALETTE: [&str; 16] = [
+    "#000000", "#1d2b53", "#7e2553", "#008751", "#ab5236", "#5f574f", "#c2c3c7", "#fff1e8",
+    "#ff004d", "#ffa300", "#ffec27", "#00e436", "#29adff", "#83769c", "#ff77a8", "#ffccaa",
+];
+
+pub const DB32_PALETTE: [&str; 32] = [
+    "#000000", "#222034", "#45283c", "#663931", "#8f563b", "#df7126", "#d9a066", "#eec39a",
+    "#fbf236", "#99e550", "#6abe30", "#37946e", "#4b692f", "#524b24", "#323c39", "#3f3f74",
+    "#306082", "#5b6ee1", "#639bff", "#5fcde4", "#cbdbfc", "#ffffff", "#9badb7", "#847e87",
+    "#696a6a", "#595652", "#76428a", "#ac3232", "#d95763", "#d77bba", "#8f974a", "#8a6f30",
+];
+
+// "pico8" (16 colors), "db32" (32 colors), or a comma-separated list of #rrggbb colors
+pub fn parse_palette(value: &str) -> Option<Vec<Color>> {
+    let colors: Option<Vec<Color>> = match value {
+        "pico8" => PICO8_PALETTE.iter().map(|hex| Color::from_hex(hex)).collect(),
+        "db32" => DB32_PALETTE.iter().map(|hex| Color::from_hex(hex)).collect(),
+        list => list.split(',').map(|hex| Color::from_hex(hex.trim())).collect(),
+    };
+    colors.filter(|colors| !colors.is_empty())
+}
+
+// 4x4 ordered dither thresholds
+pub const BAYER_4X4: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+pub fn nearest_palette_color(palette: &[Color], r: f32, g: f32, b: f32) -> Color {
+    // Weighted RGB distance; green differences are the most visible
+    let distance = |c: &Color| {
+        let dr = c.r as f32 - r;
+        let dg = c.g as f32 - g;
+        let db = c.b as f32 - b;
+        2.0 * dr * dr + 4.0 * dg * dg + 3.0 * db * db
+    };
+    palette
+        .iter()
+        .copied()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .unwrap_or(Color::new(0, 0, 0))
+}
+
+// The frames are still rendered at full size, so the low-resolution image is an area average of
+// the render, which keeps thin features from dropping out. Alpha becomes all-or-nothing.
+pub fn pixelate(buffer: &[u32], width: usize, height: usize, style: &PixelArt) -> Vec<u32> {
+    let low_width = style.size.clamp(1, width);
+    let low_height = (height * low_width).div_ceil(width).max(1);
+    let small = downsample(buffer, width, height, low_width, low_height);
+
+    // About half the gap between neighboring palette colors
+    let spread = 128.0 / (style.palette.len() as f32).cbrt();
+
+    let quantized: Vec<u32> = small
+        .iter()
+        .enumerate()
+        .map(|(i, &pixel)| {
+            let alpha = (pixel >> 24) & 0xFF;
+            if alpha < 128 {
+                return 0;
+            }
+            let unpremultiply = |shift: u32| ((pixel >> shift) & 0xFF) as f32 * 255.0 / alpha as f32;
+            let offset = if style.dither {
+                let (x, y) = (i % low_width, i / low_width);
+                ((BAYER_4X4[y % 4][x % 4] + 0.5) / 16.0 - 0.5) * spread
+            } else {
+                0.0
+            };
+            nearest_palette_color(
+                &style.palette,
+                unpremultiply(16) + offset,
+                unpremultiply(8) + offset,
+                unpremultiply(0) + offset,
+            )
+            .to_u32()
+        })
+        .collect();
+
+    let mut output = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let sy = y * low_height / height;
+        for x in 0..width {
+            output.push(quantized[sy * low_width + x * low_width / width]);
+        }
+    }
+    output
+}
+
+// The largest centered crop of a buffer with the aspect ratio of width x height
+pub fn crop_to_aspect(buffer: &[u32], src_width: usize, src_height: usize, width: usize, height: usize) -> (Vec<u32>, usize, usize) {
+    let (crop_width, crop_height) = if width * src_height > height * src_width {
+        (src_width, (src_width * height / width).max(1))
+    } else {
+        ((src_height * width / height).max(1), src_height)
+    };
+    let (left, top) = ((src_width - crop_width) / 2, (src_height - crop_height) / 2);
+    let cropped = (top..top + crop_height)
+        .flat_map(|y| buffer[y * src_width + left..][..crop_width].iter().copied())
+        .collect();
+    (cropped, crop_width, crop_height)
+}
+
+// Area-averaging downsample of a packed ARGB buffer (separable, all four channels)
+pub fn downsample(buffer: &[u32], src_width: usize, src_height: usize, dst_width: usize, dst_height: usize) -> Vec<u32> {
+    let channels = |pixel: u32| [(pixel >> 24) & 0xFF, (pixel >> 16) & 0xFF, (pixel >> 8) & 0xFF, pixel & 0xFF].map(|c| c as f32);
+
+    let x_weights = area_weights(src_width, dst_width);
+    let mut rows = vec![[0.0f32; 4]; dst_width * src_height];
+    for y in 0..src_height {
+        for (x, weights) in x_weights.iter().enumerate() {
+            let mut sum = [0.0f32; 4];
+            for &(sx, w) in weights {
+                let c = channels(buffer[y * src_width + sx]);
+                for k in 0..4 {
+                    sum[k] += c[k] * w;
+                }
+            }
+            rows[y * dst_width + x] = sum;
+        }
+    }
+
+    let y_weights = area_weights(src_height, dst_height);
+    let mut output = Vec::with_capacity(dst_width * dst_height);
+    for weights in &y_weights {
+        for x in 0..dst_width {
+            let mut sum = [0.0f32; 4];
+            for &(sy, w) in weights {
+                let c = rows[sy * dst_width + x];
+                for k in 0..4 {
+                    sum[k] += c[k] * w;
+                }
+            }
+            let [a, r, g, b] = sum.map(|c| (c.round() as u32).min(255));
+            output.push(a << 24 | r << 16 | g << 8 | b);
+        }
+    }
+    output
+}
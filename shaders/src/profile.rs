@@ -0,0 +1,71 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+
+// Profiling: exclusive time per pipeline stage, summed over all threads. Nested stages are
+// subtracted from their parent, so raster time excludes the shading it calls and so on. Stages
+// are timed around whole batches where they can be, since a clock read costs about as much as
+// a noise sample.
+#[derive(Clone, Copy)]
+pub enum Stage {
+    Shading,
+    Raster,
+    Blend,
+    Encode,
+}
+
+const STAGE_NAMES: [&str; 4] = ["shading", "raster", "blend", "encode"];
+
+static PROFILING: AtomicBool = AtomicBool::new(false);
+static STAGE_NANOS: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+static PROFILED_FRAMES: AtomicUsize = AtomicUsize::new(0);
+
+// Profiling is off until a driver turns it on, and then costs a clock read per stage
+pub fn set_enabled(enabled: bool) {
+    PROFILING.store(enabled, Ordering::Relaxed);
+}
+
+thread_local! {
+    // Time spent in nested stages of the stage currently running on this thread
+    static NESTED_NANOS: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn profile<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    if !PROFILING.load(Ordering::Relaxed) {
+        return f();
+    }
+
+    let outer_nested = NESTED_NANOS.with(|nested| nested.replace(0));
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed().as_nanos() as u64;
+    let nested = NESTED_NANOS.with(|nested| nested.replace(outer_nested + elapsed));
+    STAGE_NANOS[stage as usize].fetch_add(elapsed.saturating_sub(nested), Ordering::Relaxed);
+    result
+}
+
+pub fn count_profiled_frame() {
+    PROFILED_FRAMES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn print_profile(wall: std::time::Duration) {
+    let nanos: Vec<u64> = STAGE_NANOS.iter().map(|n| n.load(Ordering::Relaxed)).collect();
+    let total: u64 = nanos.iter().sum();
+    let frames = PROFILED_FRAMES.load(Ordering::Relaxed).max(1);
+
+    println!(
+        "\n=== PROFILE ({} frames, {:.1} ms wall, {:.1} ms in stages) ===",
+        frames,
+        wall.as_secs_f64() * 1000.0,
+        total as f64 / 1e6
+    );
+    for (name, &ns) in STAGE_NAMES.iter().zip(&nanos) {
+        println!(
+            "  {:<8} {:>10.1} ms  {:>8.2} ms/frame  {:>5.1}%",
+            name,
+            ns as f64 / 1e6,
+            ns as f64 / 1e6 / frames as f64,
+            if total > 0 { ns as f64 * 100.0 / total as f64 } else { 0.0 }
+        );
+    }
+}
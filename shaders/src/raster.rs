@@ -0,0 +1,1483 @@
+use std::f32::consts::PI;
+use std::ops::Range;
+
+use log::{debug, info, trace};
+
+use crate::color::{Color, Theme};
+use crate::kernel::easing::smoothstep;
+pub use crate::kernel::shaders::Fragment;
+use crate::geometry::{Camera, Mesh, Transform};
+use crate::math::{Mat4, Rng, Vec3};
+use crate::profile::{Stage, profile};
+use crate::settings::{Light, Lighting, RenderSettings, ToonStyle};
+
+// Size of a render, and the orthographic projection onto it: the world origin lands in the
+// middle and a unit sphere spans half the shorter side
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer { width, height }
+    }
+
+    // Saturates rather than wrapping, so a size too large to count still fails any pixel limit
+    pub fn pixels(&self) -> usize {
+        self.width.saturating_mul(self.height)
+    }
+
+    // None for a size too large to count at all
+    pub fn checked_pixels(&self) -> Option<usize> {
+        self.width.checked_mul(self.height)
+    }
+
+    // Pixels per world unit
+    pub fn scale(&self) -> f32 {
+        self.width.min(self.height) as f32 / 4.0
+    }
+
+    // Screen position of a world-space point
+    pub fn project(&self, p: &Vec3) -> (f32, f32) {
+        let scale = self.scale();
+        (self.width as f32 / 2.0 + p.x * scale, self.height as f32 / 2.0 - p.y * scale)
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Framebuffer::new(800, 800)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum DebugView {
+    // Mesh edges over a flat-lit surface
+    Wireframe,
+    // Face normal mapped from [-1, 1] to RGB
+    Normals,
+    // Nearer is brighter
+    Depth,
+    // Longitude/latitude of the surface point as red/green
+    Uv,
+    // The lighting term handed to the shaders
+    Intensity,
+}
+
+impl DebugView {
+    pub fn parse(value: &str) -> Option<DebugView> {
+        match value {
+            "wireframe" => Some(DebugView::Wireframe),
+            "normals" => Some(DebugView::Normals),
+            "depth" => Some(DebugView::Depth),
+            "uv" => Some(DebugView::Uv),
+            "intensity" => Some(DebugView::Intensity),
+            _ => None,
+        }
+    }
+
+    // `edge_distance` is the pixel distance to the nearest triangle edge
+    pub fn color(&self, fragment: &Fragment, edge_distance: f32) -> Color {
+        match self {
+            DebugView::Wireframe => {
+                if edge_distance < 0.75 {
+                    Color::new(255, 255, 255)
+                } else {
+                    let shade = fragment.intensity * 0.35;
+                    Color::from_float(shade * 0.6, shade * 0.8, shade)
+                }
+            }
+            DebugView::Normals => Color::from_float(
+                fragment.normal.x * 0.5 + 0.5,
+                fragment.normal.y * 0.5 + 0.5,
+                fragment.normal.z * 0.5 + 0.5,
+            ),
+            DebugView::Depth => {
+                // Stretched so the scene, which spans roughly z in [-2.5, 2.5], covers the range
+                let mid = normalized_depth(0.0);
+                let half_range = mid - normalized_depth(2.5);
+                let near = (0.5 - (fragment.depth - mid) / (2.0 * half_range)).clamp(0.0, 1.0);
+                Color::from_float(near, near, near)
+            }
+            DebugView::Uv => {
+                let p = fragment.object_position.normalize();
+                let u = p.z.atan2(p.x) / (2.0 * PI) + 0.5;
+                let v = p.y.clamp(-1.0, 1.0).acos() / PI;
+                Color::from_float(u, v, 0.0)
+            }
+            DebugView::Intensity => Color::from_float(fragment.intensity, fragment.intensity, fragment.intensity),
+        }
+    }
+}
+
+// What an interleaved frame leaves behind for the next one: final colors, depth, and which
+// pixels were actually shaded this frame (only those are trusted when reprojecting).
+pub struct FrameHistory {
+    pub size: Framebuffer,
+    pub color: Vec<u32>,
+    pub depth: Vec<f32>,
+    pub fresh: Vec<bool>,
+}
+
+// Sub-pixel positions cycled through by temporal anti-aliasing, and how much of each new frame
+// goes into the running average: about the last 1 / TEMPORAL_BLEND frames show through
+pub const TEMPORAL_SAMPLES: usize = 8;
+pub const TEMPORAL_BLEND: f32 = 0.125;
+
+// The running average temporal anti-aliasing keeps from frame to frame, premultiplied ARGB in
+// 0-255 kept as floats so the small steps of the average don't round away
+pub struct TemporalHistory {
+    pub size: Framebuffer,
+    pub color: Vec<[f32; 4]>,
+}
+
+impl TemporalHistory {
+    pub fn resolved(&self) -> Vec<u32> {
+        self.color.iter().map(|&channels| pack_channels(channels)).collect()
+    }
+
+    // Bilinear lookup, or None off the frame
+    fn sample(&self, x: f32, y: f32) -> Option<[f32; 4]> {
+        let Framebuffer { width, height } = self.size;
+        if !x.is_finite() || !y.is_finite() || x < 0.0 || y < 0.0 || x > (width - 1) as f32 || y > (height - 1) as f32 {
+            return None;
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let mut sum = [0.0; 4];
+        for (idx, weight) in [
+            (y0 * width + x0, (1.0 - fx) * (1.0 - fy)),
+            (y0 * width + x1, fx * (1.0 - fy)),
+            (y1 * width + x0, (1.0 - fx) * fy),
+            (y1 * width + x1, fx * fy),
+        ] {
+            for (total, channel) in sum.iter_mut().zip(self.color[idx]) {
+                *total += channel * weight;
+            }
+        }
+        Some(sum)
+    }
+}
+
+// Color and depth for one frame. With half-res shading, the visible fragment of each pixel is
+// kept (with the index of the shader that owns it) and only shaded in `resolve_half_res`.
+// A target can hold just a band of the frame's rows, for frames too large to keep whole, or a
+// rectangle of rows and columns, for re-rendering part of a frame: triangles are still projected
+// onto the whole frame and clipped to the band, so the band's pixels come out as they would in
+// the whole frame.
+pub struct RasterTarget {
+    pub size: Framebuffer,
+    pub rows: Range<usize>,
+    pub columns: Range<usize>,
+    // Every body drawn to the target is seen through this
+    pub camera: Camera,
+    // Sub-pixel shift of everything drawn, for temporal anti-aliasing
+    pub jitter: (f32, f32),
+    pub color: Vec<u32>,
+    pub depth: Vec<f32>,
+    pub deferred: Option<Vec<Option<(Fragment, usize)>>>,
+    pub debug: Option<DebugView>,
+    pub writes: Option<Vec<u32>>,
+    pub toon: Option<ToonStyle>,
+    // Normal of the visible surface per pixel, for toon outlines
+    pub normals: Option<Vec<Vec3>>,
+    // Object-space point of the visible opaque surface per pixel, for reprojecting it into
+    // another frame
+    pub surface: Option<Vec<Option<Vec3>>>,
+    pub stars: Option<StarField>,
+    pub cull: CullMode,
+    pub stats: RasterStats,
+}
+
+// What happened to the triangles sent to a target, for the debug log. Triangles split at the near
+// plane count once as clipped, and then once per piece.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RasterStats {
+    pub triangles: usize,
+    pub clipped: usize,
+    pub offscreen: usize,
+    pub culled: usize,
+    pub degenerate: usize,
+}
+
+impl RasterStats {
+    pub fn drawn(&self) -> usize {
+        self.triangles - self.offscreen - self.culled - self.degenerate
+    }
+}
+
+impl RasterTarget {
+    pub fn new(settings: &RenderSettings) -> Self {
+        let size = settings.framebuffer;
+        let rows = settings.rows.clone().unwrap_or(0..size.height);
+        let columns = settings.columns.clone().unwrap_or(0..size.width);
+        let pixels = columns.len() * rows.len();
+        RasterTarget {
+            size,
+            rows,
+            columns,
+            camera: settings.camera,
+            jitter: (0.0, 0.0),
+            color: vec![0u32; pixels],
+            depth: vec![f32::NEG_INFINITY; pixels],
+            // Debug views are cheap and written directly
+            deferred: (settings.half_res_shading && settings.debug.is_none()).then(|| vec![None; pixels]),
+            debug: settings.debug,
+            writes: settings.overdraw.then(|| vec![0; pixels]),
+            toon: settings.toon,
+            normals: settings.toon.map(|_| vec![Vec3::new(0.0, 0.0, 0.0); pixels]),
+            surface: None,
+            stars: settings.stars,
+            cull: settings.cull,
+            stats: RasterStats::default(),
+        }
+    }
+
+    pub fn log_stats(&self) {
+        let stats = &self.stats;
+        debug!(
+            "{} triangles: {} drawn, {} back-facing, {} off-screen, {} degenerate; {} clipped at the near plane",
+            stats.triangles,
+            stats.drawn(),
+            stats.culled,
+            stats.offscreen,
+            stats.degenerate,
+            stats.clipped
+        );
+    }
+
+    // The rows and columns held, as a frame of their own
+    pub fn band(&self) -> Framebuffer {
+        Framebuffer::new(self.columns.len(), self.rows.len())
+    }
+
+    pub fn count_write(&mut self, idx: usize) {
+        if let Some(writes) = &mut self.writes {
+            writes[idx] += 1;
+        }
+    }
+
+    // A glowing segment between two world-space points, hidden behind whatever is already drawn
+    // in front of it. Lines leave the depth buffer alone, so where they cross they add up.
+    pub fn add_line(&mut self, from: Vec3, to: Vec3, color: Color, strength: f32) {
+        let (from, to) = (self.camera.apply(&from), self.camera.apply(&to));
+        if crosses_near_plane(&[from, to], self.camera.near_z()) {
+            return;
+        }
+        let projection = self.camera.projection();
+        let (x1, y1) = self.screen(&projection, &from);
+        let (x2, y2) = self.screen(&projection, &to);
+
+        let steps = (x2 - x1).abs().max((y2 - y1).abs()).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let (x, y) = (x1 + (x2 - x1) * t, y1 + (y2 - y1) * t);
+            let (columns, rows) = (&self.columns, &self.rows);
+            if x < columns.start as f32 || y < rows.start as f32 || x >= columns.end as f32 || y >= rows.end as f32 {
+                continue;
+            }
+            let idx = (y as usize - rows.start) * columns.len() + x as usize - columns.start;
+            if from.z + (to.z - from.z) * t > self.depth[idx] {
+                self.count_write(idx);
+                self.color[idx] = blend_add(self.color[idx], color, strength);
+            }
+        }
+    }
+
+    // Where a sphere's outline falls on screen, as a center and radius in pixels. Seen in
+    // perspective it is only a circle while the sphere sits on the view axis; elsewhere it
+    // stretches into an ellipse and there is None.
+    pub fn silhouette(&self, center: &Vec3, radius: f32) -> Option<((f32, f32), f32)> {
+        let center = self.camera.apply(center);
+        let projection = self.camera.projection();
+        let edge = match self.camera.eye_z() {
+            None => center + Vec3::new(radius, 0.0, 0.0),
+            Some(eye_z) => {
+                let distance = eye_z - center.z;
+                if distance <= radius || center.x.abs().max(center.y.abs()) > 1e-4 * distance {
+                    return None;
+                }
+                // The point where a line from the eye grazes the sphere
+                let (sin, cos) = ((radius / distance).asin()).sin_cos();
+                Vec3::new(distance * cos * sin, 0.0, eye_z - distance * cos * cos)
+            }
+        };
+        let (cx, cy) = self.screen(&projection, &center);
+        let (ex, ey) = self.screen(&projection, &edge);
+        Some(((cx, cy), ((ex - cx).powi(2) + (ey - cy).powi(2)).sqrt()))
+    }
+
+    // Anti-aliases the outline of a sphere drawn on its own by how much of each pixel along it
+    // the true circle covers. Pixels the mesh reached are faded to that coverage; those it fell
+    // short of (its polygon sits inside the circle) borrow the color of the nearest drawn pixel
+    // toward the center.
+    pub fn smooth_silhouette(&mut self, center: &Vec3, radius: f32) {
+        let Some(((cx, cy), radius)) = self.silhouette(center, radius) else {
+            return;
+        };
+        let (rows, columns) = (self.rows.clone(), self.columns.clone());
+        let width = columns.len();
+        let index = |x: isize, y: isize| {
+            let inside = x >= columns.start as isize && x < columns.end as isize && y >= rows.start as isize && y < rows.end as isize;
+            inside.then(|| (y as usize - rows.start) * width + x as usize - columns.start)
+        };
+
+        let reach = radius + 1.0;
+        let (top, bottom) = ((cy - reach).floor().max(rows.start as f32), (cy + reach).ceil().min(rows.end as f32));
+        let (left, right) = ((cx - reach).floor().max(columns.start as f32), (cx + reach).ceil().min(columns.end as f32));
+        let original = self.color.clone();
+        for y in top as isize..bottom as isize {
+            for x in left as isize..right as isize {
+                let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+                let distance = (dx * dx + dy * dy).sqrt();
+                let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+                if coverage <= 0.0 || coverage >= 1.0 {
+                    continue;
+                }
+                let Some(idx) = index(x, y) else { continue };
+                let source = if original[idx] != 0 {
+                    Some(idx)
+                } else {
+                    // Up to three steps in, for meshes whose edges cut well inside the circle
+                    (1..=3).find_map(|step| {
+                        let step = step as f32 / distance.max(1.0);
+                        let (sx, sy) = ((x as f32 - dx * step).round(), (y as f32 - dy * step).round());
+                        index(sx as isize, sy as isize).filter(|&i| original[i] != 0)
+                    })
+                };
+                if let Some(source) = source {
+                    self.color[idx] = scale_premultiplied(original[source], coverage);
+                    self.depth[idx] = self.depth[source];
+                }
+            }
+        }
+    }
+
+    // The finished frame: the color buffer, or the overdraw heatmap when counting writes
+    pub fn finish(self) -> Vec<u32> {
+        self.log_stats();
+        let band = self.band();
+        let writes = match self.writes {
+            Some(writes) => writes,
+            None => {
+                let mut color = self.color;
+                if let (Some(style), Some(normals)) = (self.toon, &self.normals) {
+                    apply_toon_style(&mut color, band, &self.depth, normals, &style);
+                }
+                if let Some(stars) = &self.stars {
+                    draw_stars(&mut color, self.size, self.rows.clone(), self.columns.clone(), &self.depth, stars);
+                }
+                return color;
+            }
+        };
+
+        let covered = writes.iter().filter(|&&count| count > 0).count();
+        let total: u64 = writes.iter().map(|&count| count as u64).sum();
+        let max = writes.iter().copied().max().unwrap_or(0);
+        info!(
+            "Overdraw: {} writes over {} covered pixels ({:.2} per pixel, max {})",
+            total,
+            covered,
+            total as f64 / covered.max(1) as f64,
+            max
+        );
+
+        writes.iter().map(|&count| heat_color(count).to_u32()).collect()
+    }
+
+    pub fn with_deferred_shading(mut self) -> Self {
+        self.deferred = Some(vec![None; self.band().pixels()]);
+        self
+    }
+
+    pub fn with_surface_positions(mut self) -> Self {
+        self.surface = Some(vec![None; self.band().pixels()]);
+        self
+    }
+
+    pub fn jittered(self, jitter: (f32, f32)) -> Self {
+        RasterTarget { jitter, ..self }
+    }
+
+    // Screen position of a view-space point, with the target's jitter
+    fn screen(&self, projection: &Mat4, v: &Vec3) -> (f32, f32) {
+        let (x, y) = self.size.project(&projection.project_point(v));
+        (x + self.jitter.0, y + self.jitter.1)
+    }
+
+    // Shades the pixels where (x + y + frame) is even. The rest are looked up in the previous
+    // frame: `reproject` maps a surface point to where it was one frame ago, and the freshly
+    // shaded history pixels around that spot with matching depth are blended bilinearly.
+    // Pixels with no usable history (first frame, disocclusions, silhouettes) are shaded.
+    pub fn resolve_checkerboard(
+        &mut self,
+        shaders: &[&RgbaShader],
+        frame: usize,
+        history: Option<&FrameHistory>,
+        reproject: &dyn Fn(&Vec3) -> Vec3,
+    ) -> FrameHistory {
+        self.log_stats();
+        profile(Stage::Blend, || {
+            let deferred = self.deferred.take().unwrap_or_default();
+            let Framebuffer { width, height } = self.band();
+            let mut fresh = vec![false; self.band().pixels()];
+
+            for y in 0..height {
+                for x in 0..width {
+                    let idx = y * width + x;
+                    let fragment = match &deferred[idx] {
+                        Some((fragment, _)) => fragment,
+                        None => continue,
+                    };
+
+                    let reused = match history {
+                        Some(history) if (x + y + frame) % 2 == 1 => {
+                            sample_history(history, &reproject(&fragment.position))
+                        }
+                        _ => None,
+                    };
+
+                    match reused {
+                        Some(color) => self.color[idx] = color,
+                        None => fresh[idx] = true,
+                    }
+                }
+            }
+
+            profile(Stage::Shading, || {
+                for (idx, entry) in deferred.iter().enumerate() {
+                    if let (true, Some((fragment, id))) = (fresh[idx], entry) {
+                        self.color[idx] = shaders[*id](fragment).0.to_u32();
+                    }
+                }
+            });
+
+            FrameHistory {
+                size: self.size,
+                color: self.color.clone(),
+                depth: self.depth.clone(),
+                fresh,
+            }
+        })
+    }
+
+    // Shades the even-coordinate pixels, then fills every other pixel from the surrounding shaded
+    // samples on the same surface (same shader, close depth and normal). Pixels without such a
+    // neighbor, mostly along silhouettes, are shaded directly so edges stay sharp.
+    pub fn resolve_half_res(&mut self, shaders: &[&RgbaShader]) {
+        profile(Stage::Blend, || {
+            let deferred = match self.deferred.take() {
+                Some(deferred) => deferred,
+                None => return,
+            };
+
+            let Framebuffer { width, height } = self.band();
+            profile(Stage::Shading, || {
+                for y in (0..height).step_by(2) {
+                    for x in (0..width).step_by(2) {
+                        let idx = y * width + x;
+                        if let Some((fragment, id)) = &deferred[idx] {
+                            self.color[idx] = shaders[*id](fragment).0.to_u32();
+                        }
+                    }
+                }
+            });
+
+            // Pixels with no shaded neighbor on their surface, shaded together once the rest
+            // are filled in
+            let mut unfilled = Vec::new();
+            for y in 0..height {
+                for x in 0..width {
+                    if x % 2 == 0 && y % 2 == 0 {
+                        continue;
+                    }
+                    let idx = y * width + x;
+                    let (fragment, id) = match &deferred[idx] {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+
+                    let x0 = x & !1;
+                    let y0 = y & !1;
+                    let fx = (x - x0) as f32 * 0.5;
+                    let fy = (y - y0) as f32 * 0.5;
+
+                    let mut sum = [0.0f32; 3];
+                    let mut total = 0.0;
+                    for (sx, wx) in [(x0, 1.0 - fx), (x0 + 2, fx)] {
+                        for (sy, wy) in [(y0, 1.0 - fy), (y0 + 2, fy)] {
+                            let weight = wx * wy;
+                            if weight <= 0.0 || sx >= width || sy >= height {
+                                continue;
+                            }
+                            let sample_idx = sy * width + sx;
+                            if let Some((sample, sample_id)) = &deferred[sample_idx] {
+                                let same_surface = sample_id == id
+                                    && (sample.position.z - fragment.position.z).abs() < 0.05
+                                    && sample.normal.dot(&fragment.normal) > 0.9;
+                                if same_surface {
+                                    let c = self.color[sample_idx];
+                                    sum[0] += ((c >> 16) & 0xFF) as f32 * weight;
+                                    sum[1] += ((c >> 8) & 0xFF) as f32 * weight;
+                                    sum[2] += (c & 0xFF) as f32 * weight;
+                                    total += weight;
+                                }
+                            }
+                        }
+                    }
+
+                    if total > 0.0 {
+                        self.color[idx] = Color::new(
+                            (sum[0] / total).round() as u8,
+                            (sum[1] / total).round() as u8,
+                            (sum[2] / total).round() as u8,
+                        )
+                        .to_u32();
+                    } else {
+                        unfilled.push(idx);
+                    }
+                }
+            }
+
+            profile(Stage::Shading, || {
+                for idx in unfilled {
+                    if let Some((fragment, id)) = &deferred[idx] {
+                        self.color[idx] = shaders[*id](fragment).0.to_u32();
+                    }
+                }
+            });
+        })
+    }
+}
+
+// Background stars behind the bodies, seeded so they stay put from frame to frame
+#[derive(Clone, Copy)]
+pub struct StarField {
+    pub seed: u64,
+    // How far starlight bends around the bodies' limbs, 0 for not at all
+    pub refraction: f32,
+}
+
+// How far beyond a silhouette the atmosphere still bends and dims starlight, in pixels
+pub const LIMB_REACH: i32 = 16;
+
+// Draws the stars under the finished bodies. Just outside a silhouette, starlight passes through
+// the edge of the atmosphere: it is bent outward, away from the limb, and dimmed and reddened,
+// more so the closer it skims the surface. `color` and `depth` hold the given rows and columns of
+// the frame; the stars are scattered over all of it, so bands of a frame share one sky (though a
+// limb in the next band over doesn't bend the stars just across the edge).
+pub fn draw_stars(
+    color: &mut [u32],
+    size: Framebuffer,
+    rows: Range<usize>,
+    columns: Range<usize>,
+    depth: &[f32],
+    stars: &StarField,
+) {
+    let Framebuffer { width, height } = size;
+    let index = |x: usize, y: usize| (y - rows.start) * columns.len() + x - columns.start;
+    let covered = |x: i32, y: i32| {
+        x >= columns.start as i32
+            && y >= rows.start as i32
+            && (x as usize) < columns.end
+            && (y as usize) < rows.end
+            && depth[index(x as usize, y as usize)] > f32::NEG_INFINITY
+    };
+
+    let mut rng = Rng::new(stars.seed);
+    for _ in 0..size.pixels() / 400 {
+        let (mut x, mut y) = (rng.range(0.0, width as f32), rng.range(0.0, height as f32));
+        // Many faint stars, few bright ones
+        let mut brightness = 0.1 + 0.9 * rng.next_f32().powi(3);
+        let tint = rng.next_f32();
+        let mut rgb = [0.85 + 0.15 * tint, 0.85 + 0.1 * tint, 1.0 - 0.2 * tint];
+
+        // Nearest covered pixel within reach of the star
+        let (cx, cy) = (x as i32, y as i32);
+        if covered(cx, cy) {
+            continue;
+        }
+        let mut nearest: Option<(f32, f32, f32)> = None;
+        for dy in -LIMB_REACH..=LIMB_REACH {
+            for dx in -LIMB_REACH..=LIMB_REACH {
+                if !covered(cx + dx, cy + dy) {
+                    continue;
+                }
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if nearest.is_none_or(|(best, _, _)| distance < best) {
+                    nearest = Some((distance, dx as f32, dy as f32));
+                }
+            }
+        }
+        if let Some((distance, dx, dy)) = nearest.filter(|_| stars.refraction > 0.0) {
+            let closeness = (-distance / 4.0).exp() * stars.refraction.min(1.0);
+            let shift = 4.0 * stars.refraction * (-distance / 4.0).exp();
+            x -= dx / distance.max(1.0) * shift;
+            y -= dy / distance.max(1.0) * shift;
+            brightness *= 1.0 - 0.8 * closeness;
+            rgb = [rgb[0], rgb[1] * (1.0 - 0.4 * closeness), rgb[2] * (1.0 - 0.7 * closeness)];
+        }
+
+        // Spread over the four nearest pixels so shifted stars move smoothly, and only where
+        // the bodies leave the background showing
+        let (fx, fy) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (fx.floor(), fy.floor());
+        for (px, py, weight) in [
+            (x0, y0, (1.0 - (fx - x0)) * (1.0 - (fy - y0))),
+            (x0 + 1.0, y0, (fx - x0) * (1.0 - (fy - y0))),
+            (x0, y0 + 1.0, (1.0 - (fx - x0)) * (fy - y0)),
+            (x0 + 1.0, y0 + 1.0, (fx - x0) * (fy - y0)),
+        ] {
+            if px < columns.start as f32 || py < rows.start as f32 || px >= columns.end as f32 || py >= rows.end as f32 {
+                continue;
+            }
+            let idx = index(px as usize, py as usize);
+            let coverage = ((color[idx] >> 24) & 0xFF) as f32 / 255.0;
+            let strength = brightness * weight * (1.0 - coverage);
+            let star = Color::from_float(rgb[0], rgb[1], rgb[2]);
+            color[idx] = blend_add(color[idx], star, strength);
+        }
+    }
+}
+
+// Flattens the palette and draws dark outlines wherever the visible surface changes abruptly:
+// at silhouettes against the background, at depth jumps (a moon in front of its planet) and at
+// sharp creases in the normal.
+pub fn apply_toon_style(color: &mut [u32], size: Framebuffer, depth: &[f32], normals: &[Vec3], style: &ToonStyle) {
+    let Framebuffer { width, height } = size;
+    let outline = Color::new(12, 10, 20).to_u32();
+    let flattened: Vec<u32> = color.iter().map(|&pixel| style.flatten(pixel)).collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            if depth[idx] == f32::NEG_INFINITY {
+                continue;
+            }
+
+            let mut edge = false;
+            for (dx, dy) in [(-1i32, 0i32), (1, 0), (0, -1), (0, 1), (-1, -1), (1, 1), (-1, 1), (1, -1)] {
+                let nx = x as i32 + dx;
+                let ny = y as i32 + dy;
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+                let neighbor = ny as usize * width + nx as usize;
+                if depth[neighbor] == f32::NEG_INFINITY
+                    || (depth[neighbor] - depth[idx]).abs() > 0.1
+                    || normals[neighbor].dot(&normals[idx]) < 0.5
+                {
+                    edge = true;
+                    break;
+                }
+            }
+
+            color[idx] = if edge { outline } else { flattened[idx] };
+        }
+    }
+}
+
+// Heatmap ramp for write counts: black, blue, cyan, green, yellow, red, then white from 8 up
+pub fn heat_color(count: u32) -> Color {
+    const STOPS: [(f32, [f32; 3]); 7] = [
+        (0.0, [0.0, 0.0, 0.0]),
+        (1.0, [0.0, 0.0, 0.6]),
+        (2.0, [0.0, 0.7, 0.9]),
+        (3.0, [0.0, 0.8, 0.0]),
+        (4.0, [1.0, 1.0, 0.0]),
+        (6.0, [1.0, 0.0, 0.0]),
+        (8.0, [1.0, 1.0, 1.0]),
+    ];
+
+    let count = count as f32;
+    for pair in STOPS.windows(2) {
+        let (start, from) = pair[0];
+        let (end, to) = pair[1];
+        if count <= end {
+            let t = (count - start) / (end - start);
+            return Color::from_float(
+                from[0] + (to[0] - from[0]) * t,
+                from[1] + (to[1] - from[1]) * t,
+                from[2] + (to[2] - from[2]) * t,
+            );
+        }
+    }
+    Color::new(255, 255, 255)
+}
+
+// Bilinear lookup of a previous-frame position, using only pixels that were shaded in that frame
+// and still see the same point (depth within a small tolerance)
+pub fn sample_history(history: &FrameHistory, position: &Vec3) -> Option<u32> {
+    let Framebuffer { width, height } = history.size;
+    let (sx, sy) = history.size.project(position);
+    if !sx.is_finite() || !sy.is_finite() || sx < 0.0 || sy < 0.0 {
+        return None;
+    }
+
+    let x0 = sx.floor() as usize;
+    let y0 = sy.floor() as usize;
+    let fx = sx - x0 as f32;
+    let fy = sy - y0 as f32;
+
+    let mut sum = [0.0f32; 3];
+    let mut total = 0.0;
+    for (x, wx) in [(x0, 1.0 - fx), (x0 + 1, fx)] {
+        for (y, wy) in [(y0, 1.0 - fy), (y0 + 1, fy)] {
+            let weight = wx * wy;
+            if weight <= 0.0 || x >= width || y >= height {
+                continue;
+            }
+            let idx = y * width + x;
+            if !history.fresh[idx] || (history.depth[idx] - position.z).abs() > 0.05 {
+                continue;
+            }
+            let c = history.color[idx];
+            sum[0] += ((c >> 16) & 0xFF) as f32 * weight;
+            sum[1] += ((c >> 8) & 0xFF) as f32 * weight;
+            sum[2] += (c & 0xFF) as f32 * weight;
+            total += weight;
+        }
+    }
+
+    // Require most of the footprint to be valid, otherwise edges smear
+    if total < 0.5 {
+        return None;
+    }
+    Some(
+        Color::new(
+            (sum[0] / total).round() as u8,
+            (sum[1] / total).round() as u8,
+            (sum[2] / total).round() as u8,
+        )
+        .to_u32(),
+    )
+}
+
+// Where temporal anti-aliasing samples each pixel on the `sample`th frame: the (2, 3) Halton
+// sequence, which covers the pixel evenly however many frames are averaged, centered on it
+pub fn jitter_offset(sample: usize) -> (f32, f32) {
+    let halton = |mut index: usize, base: usize| {
+        let (mut fraction, mut result) = (1.0, 0.0);
+        while index > 0 {
+            fraction /= base as f32;
+            result += fraction * (index % base) as f32;
+            index /= base;
+        }
+        result
+    };
+    let index = sample % TEMPORAL_SAMPLES + 1;
+    (halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+}
+
+fn unpack_channels(pixel: u32) -> [f32; 4] {
+    [24, 16, 8, 0].map(|shift| ((pixel >> shift) & 0xFF) as f32)
+}
+
+fn pack_channels(channels: [f32; 4]) -> u32 {
+    channels.iter().fold(0, |packed, &channel| packed << 8 | channel.round().clamp(0.0, 255.0) as u32)
+}
+
+// Folds a frame drawn with `jitter` into the running average. Each pixel finds where its surface
+// point was a frame ago through `previous_screen` (empty pixels look at the same spot), and the
+// history there is clamped to the colors around the pixel in this frame before blending, so
+// what has moved away or come into view doesn't leave a trail.
+pub fn accumulate_temporal(
+    frame: &[u32],
+    surface: &[Option<Vec3>],
+    size: Framebuffer,
+    jitter: (f32, f32),
+    history: Option<&TemporalHistory>,
+    previous_screen: &dyn Fn(&Vec3) -> (f32, f32),
+) -> TemporalHistory {
+    let Framebuffer { width, height } = size;
+    let current: Vec<[f32; 4]> = frame.iter().map(|&pixel| unpack_channels(pixel)).collect();
+    let history = match history {
+        Some(history) if history.size == size => history,
+        _ => return TemporalHistory { size, color: current },
+    };
+
+    let mut color = Vec::with_capacity(current.len());
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let (hx, hy) = match surface[idx] {
+                Some(point) => {
+                    let (px, py) = previous_screen(&point);
+                    (px + jitter.0, py + jitter.1)
+                }
+                None => (x as f32, y as f32),
+            };
+            let Some(previous) = history.sample(hx, hy) else {
+                color.push(current[idx]);
+                continue;
+            };
+
+            let (mut low, mut high) = ([f32::MAX; 4], [f32::MIN; 4]);
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    for (channel, value) in current[ny * width + nx].iter().enumerate() {
+                        low[channel] = low[channel].min(*value);
+                        high[channel] = high[channel].max(*value);
+                    }
+                }
+            }
+            let mut blended = [0.0; 4];
+            for channel in 0..4 {
+                let previous = previous[channel].clamp(low[channel], high[channel]);
+                blended[channel] = previous + (current[idx][channel] - previous) * TEMPORAL_BLEND;
+            }
+            color.push(blended);
+        }
+    }
+    TemporalHistory { size, color }
+}
+
+// Screen-space edge function: positive when `p` is on the inside of a clockwise-on-screen edge
+// a -> b. Evaluated from the same endpoint whichever way the edge is walked, so a shared edge
+// gives exactly opposite values for the two triangles that use it.
+pub fn edge_function(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+    if (b.0, b.1) < (a.0, a.1) {
+        return -edge_function(b, a, p);
+    }
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+// With y pointing down: a top edge is horizontal with the inside below it, a left edge goes up
+pub fn is_top_left(a: (f32, f32), b: (f32, f32)) -> bool {
+    (a.1 == b.1 && b.0 > a.0) || b.1 < a.1
+}
+
+// The rasterizer works with RGBA shaders; the planet shaders are opaque
+pub type RgbaShader<'a> = dyn Fn(&Fragment) -> (Color, f32) + 'a;
+
+pub fn opaque(shader: impl Fn(&Fragment) -> Color) -> impl Fn(&Fragment) -> (Color, f32) {
+    move |fragment| (shader(fragment), 1.0)
+}
+
+// Passes a shader's colors through the settings' theme
+pub fn themed(shader: impl Fn(&Fragment) -> Color, theme: Theme) -> impl Fn(&Fragment) -> Color {
+    move |fragment| match theme {
+        Theme::Realistic => shader(fragment),
+        theme => theme.apply(shader(fragment)),
+    }
+}
+
+// Colors a shader's output by the light; self-lit bodies like the sun are left unwrapped
+pub fn lit(shader: impl Fn(&Fragment) -> (Color, f32), light: &Light) -> impl Fn(&Fragment) -> (Color, f32) {
+    let light = *light;
+    move |fragment| {
+        let (color, alpha) = shader(fragment);
+        (light.tint(color), alpha)
+    }
+}
+
+// How a surface's fragments combine with the target
+#[derive(Clone, Copy)]
+pub enum BlendMode {
+    // Depth-tested and written; the shader's alpha is ignored
+    Opaque,
+    // Blended over the target by the shader's alpha
+    Alpha,
+}
+
+// Which single-sided triangles the rasterizer skips, judged by their winding on the screen
+// before any pixel is touched. Closed bodies hide their back faces, so skipping those is free;
+// `None` draws both sides and leaves hiding them to the depth test, and `Front` shows a body's
+// inside.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum CullMode {
+    None,
+    #[default]
+    Back,
+    Front,
+}
+
+impl CullMode {
+    pub fn parse(value: &str) -> Option<CullMode> {
+        match value {
+            "none" => Some(CullMode::None),
+            "back" => Some(CullMode::Back),
+            "front" => Some(CullMode::Front),
+            _ => None,
+        }
+    }
+}
+
+// Per-body surface properties the rasterizer needs
+#[derive(Clone, Copy)]
+pub struct Material {
+    // Thin surfaces like rings are seen from both sides; closed bodies never show their back faces
+    pub double_sided: bool,
+    pub blend: BlendMode,
+    pub lighting: Lighting,
+    // A sphere (world-space center and radius) that can stand between this surface and the
+    // light, like a planet eclipsing its moon
+    pub occluder: Option<(Vec3, f32)>,
+    // Passed on to the shader as `Fragment::heat`
+    pub heat: f32,
+}
+
+impl Material {
+    pub fn solid(lighting: Lighting) -> Material {
+        Material { double_sided: false, blend: BlendMode::Opaque, lighting, occluder: None, heat: 0.0 }
+    }
+
+    pub fn ring(lighting: Lighting) -> Material {
+        Material { double_sided: true, blend: BlendMode::Alpha, lighting, occluder: None, heat: 0.0 }
+    }
+
+    pub fn shadowed_by(self, center: Vec3, radius: f32) -> Material {
+        Material { occluder: Some((center, radius)), ..self }
+    }
+
+    pub fn heated(self, heat: f32) -> Material {
+        Material { heat, ..self }
+    }
+
+    // 1 where the occluder fully hides the light from `point`, 0 where it is in plain view, with a
+    // narrow penumbra in between
+    pub fn shadow(&self, point: &Vec3, light_dir: &Vec3) -> f32 {
+        let (center, radius) = match self.occluder {
+            Some(occluder) => occluder,
+            None => return 0.0,
+        };
+        let along = (center - *point).dot(light_dir);
+        if along <= 0.0 {
+            return 0.0;
+        }
+        let miss = (center - (*point + *light_dir * along)).length();
+        1.0 - smoothstep((miss - radius * 0.95) / (radius * 0.1))
+    }
+
+    // Whether a face is drawn, given which way it faces. Double-sided surfaces always are.
+    pub fn draws(&self, front_facing: bool, cull: CullMode) -> bool {
+        match cull {
+            _ if self.double_sided => true,
+            CullMode::None => true,
+            CullMode::Back => front_facing,
+            CullMode::Front => !front_facing,
+        }
+    }
+}
+
+// The viewer sits on the +z side looking down -z. For the parallel projection, geometry closer
+// than this is behind the camera; perspective cameras have their own planes (`Camera::near_z`).
+pub const NEAR_PLANE_Z: f32 = 10.0;
+
+// Only used to normalize fragment depth; nothing is clipped against it
+pub const FAR_PLANE_Z: f32 = -10.0;
+
+pub fn normalized_depth(z: f32) -> f32 {
+    ((NEAR_PLANE_Z - z) / (NEAR_PLANE_Z - FAR_PLANE_Z)).clamp(0.0, 1.0)
+}
+
+// Pieces from `clip_to_near_plane` come back through object space and can land a rounding error
+// past the plane, so they are let through rather than clipped again
+pub fn crosses_near_plane(vertices: &[Vec3], near_z: f32) -> bool {
+    vertices.iter().any(|v| v.z > near_z + 1e-4)
+}
+
+// Sutherland-Hodgman against the near plane. Keeps the winding, and pins new vertices exactly
+// onto the plane so the pieces never need clipping again. Returns fewer than three vertices
+// when nothing is left in front.
+pub fn clip_to_near_plane(polygon: &[Vec3], near_z: f32) -> Vec<Vec3> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, a) in polygon.iter().enumerate() {
+        let b = &polygon[(i + 1) % polygon.len()];
+        let a_inside = a.z <= near_z;
+        let b_inside = b.z <= near_z;
+
+        if a_inside {
+            clipped.push(*a);
+        }
+        if a_inside != b_inside {
+            // Worked out from the edge's ends in a fixed order, so the triangles on both sides
+            // of the edge cut it at the very same point
+            let (a, b) = if (a.x, a.y, a.z) <= (b.x, b.y, b.z) { (a, b) } else { (b, a) };
+            let t = (near_z - a.z) / (b.z - a.z);
+            let mut crossing = *a + (*b - *a) * t;
+            crossing.z = near_z;
+            clipped.push(crossing);
+        }
+    }
+    clipped
+}
+
+// Pixel bounding box of a projected triangle, clamped to the screen. None when the triangle is
+// entirely off-screen or has non-finite coordinates, which would otherwise wrap or saturate in
+// the usize casts.
+pub fn screen_bounds(
+    size: Framebuffer,
+    p1: (f32, f32),
+    p2: (f32, f32),
+    p3: (f32, f32),
+) -> Option<(usize, usize, usize, usize)> {
+    if [p1, p2, p3].iter().any(|p| !p.0.is_finite() || !p.1.is_finite()) {
+        return None;
+    }
+
+    let min_x = p1.0.min(p2.0).min(p3.0);
+    let max_x = p1.0.max(p2.0).max(p3.0);
+    let min_y = p1.1.min(p2.1).min(p3.1);
+    let max_y = p1.1.max(p2.1).max(p3.1);
+    let (right, bottom) = (size.width as f32 - 1.0, size.height as f32 - 1.0);
+    if max_x < 0.0 || max_y < 0.0 || min_x > right || min_y > bottom {
+        return None;
+    }
+
+    Some((min_x.max(0.0) as usize, max_x.min(right) as usize, min_y.max(0.0) as usize, max_y.min(bottom) as usize))
+}
+
+// Screen-space triangle setup for the top-left fill rule. A pixel is covered when it is strictly
+// inside all three edges, or exactly on a top or left edge, so pixels on an edge shared by two
+// triangles are drawn exactly once.
+pub struct TriangleEdges {
+    pub points: [(f32, f32); 3],
+    pub area: f32,
+    pub flipped: bool,
+    pub top_left: [bool; 3],
+    // Length of the edge opposite each vertex, in the order given to `new`
+    pub opposite_lengths: [f32; 3],
+}
+
+impl TriangleEdges {
+    pub fn new(p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) -> Option<Self> {
+        let area = edge_function(p1, p2, p3);
+        if area == 0.0 || !area.is_finite() {
+            return None;
+        }
+
+        // Reorder the other winding so the inside is always where every edge function is positive
+        let flipped = area < 0.0;
+        let points = if flipped { [p1, p3, p2] } else { [p1, p2, p3] };
+        let top_left = [
+            is_top_left(points[1], points[2]),
+            is_top_left(points[2], points[0]),
+            is_top_left(points[0], points[1]),
+        ];
+        let length = |a: (f32, f32), b: (f32, f32)| ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+        let opposite_lengths = [length(p2, p3), length(p3, p1), length(p1, p2)];
+        Some(TriangleEdges { points, area: area.abs(), flipped, top_left, opposite_lengths })
+    }
+
+    // Pixel distance from a covered point (as returned by `barycentric`) to the nearest edge
+    pub fn edge_distance(&self, u: f32, v: f32) -> f32 {
+        [1.0 - u - v, u, v]
+            .iter()
+            .zip(self.opposite_lengths)
+            .map(|(weight, length)| weight * self.area / length.max(1e-6))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    // Weights of the second and third vertex (in the order given to `new`) if the pixel is covered
+    pub fn barycentric(&self, px: f32, py: f32) -> Option<(f32, f32)> {
+        let [a, b, c] = self.points;
+        let weights = [
+            edge_function(b, c, (px, py)),
+            edge_function(c, a, (px, py)),
+            edge_function(a, b, (px, py)),
+        ];
+
+        for (weight, top_left) in weights.iter().zip(self.top_left) {
+            // Written so that a NaN weight counts as outside
+            let covered = *weight > 0.0 || (*weight == 0.0 && top_left);
+            if !covered {
+                return None;
+            }
+        }
+
+        if self.flipped {
+            Some((weights[2] / self.area, weights[1] / self.area))
+        } else {
+            Some((weights[1] / self.area, weights[2] / self.area))
+        }
+    }
+}
+
+// Draws a mesh's triangles in order, placed and lit as `render_triangle` does
+#[allow(clippy::too_many_arguments)]
+pub fn draw<F>(
+    target: &mut RasterTarget,
+    mesh: &Mesh,
+    transform: &Transform,
+    light: &Light,
+    shader: &F,
+    shader_id: usize,
+    material: &Material,
+    time: f32,
+) where
+    F: Fn(&Fragment) -> (Color, f32) + ?Sized,
+{
+    for &[a, b, c] in &mesh.indices {
+        let (vertices, normals) = (&mesh.vertices, [mesh.normals[a], mesh.normals[b], mesh.normals[c]]);
+        render_triangle(target, vertices[a], vertices[b], vertices[c], normals, transform, light, shader, shader_id, material, time);
+    }
+}
+
+// The normal at a point of a triangle, blended from the normals at its corners
+fn normal_at(corners: [Vec3; 3], normals: [Vec3; 3], point: &Vec3) -> Vec3 {
+    let (edge1, edge2, offset) = (corners[1] - corners[0], corners[2] - corners[0], *point - corners[0]);
+    let (d11, d12, d22) = (edge1.dot(&edge1), edge1.dot(&edge2), edge2.dot(&edge2));
+    let (d1, d2) = (offset.dot(&edge1), offset.dot(&edge2));
+    let area = d11 * d22 - d12 * d12;
+    if area.abs() < 1e-12 {
+        return normals[0];
+    }
+    let (u, v) = ((d22 * d1 - d12 * d2) / area, (d11 * d2 - d12 * d1) / area);
+    (normals[0] * (1.0 - u - v) + normals[1] * u + normals[2] * v).normalize()
+}
+
+#[allow(clippy::too_many_arguments)]
+// Draws one triangle given in object space; `transform` places it in the world, and the
+// target's camera takes it on to the screen. Lighting follows the corners' `normals`,
+// interpolated across the triangle, so curved surfaces built from few triangles shade smoothly.
+pub fn render_triangle<F>(
+    target: &mut RasterTarget,
+    v1: Vec3,
+    v2: Vec3,
+    v3: Vec3,
+    normals: [Vec3; 3],
+    transform: &Transform,
+    light: &Light,
+    shader: &F,
+    shader_id: usize,
+    material: &Material,
+    time: f32,
+) where
+    F: Fn(&Fragment) -> (Color, f32) + ?Sized,
+{
+    let camera = target.camera;
+    let model_view = camera.view().mul(&transform.matrix());
+    let object = [v1, v2, v3];
+    let view = object.map(|v| model_view.transform_point(&v));
+
+    // Triangles crossing the near plane are clipped and drawn as a fan of the pieces in front.
+    // The pieces keep the view-space corners they were cut with rather than going back through
+    // object space, so their edges line up exactly with the neighboring triangles' and the fill
+    // rule still covers every pixel along them once.
+    let near_z = camera.near_z();
+    if crosses_near_plane(&view, near_z) {
+        let view_to_object = transform.inverse().mul(&camera.inverse_view());
+        let polygon = clip_to_near_plane(&view, near_z);
+        target.stats.clipped += 1;
+        trace!(
+            "clipped a triangle at z = {:.2}, {:.2}, {:.2} into {} pieces",
+            view[0].z,
+            view[1].z,
+            view[2].z,
+            polygon.len().saturating_sub(2)
+        );
+        for i in 1..polygon.len().saturating_sub(1) {
+            let piece = [polygon[0], polygon[i], polygon[i + 1]];
+            let piece_object = piece.map(|v| view_to_object.transform_point(&v));
+            let piece_normals = piece_object.map(|point| normal_at(object, normals, &point));
+            rasterize_triangle(target, piece, piece_object, piece_normals, transform, light, shader, shader_id, material, time);
+        }
+        return;
+    }
+    rasterize_triangle(target, view, object, normals, transform, light, shader, shader_id, material, time);
+}
+
+// `render_triangle` for a triangle in front of the near plane, given in view space along with
+// where its corners are on the body
+#[allow(clippy::too_many_arguments)]
+fn rasterize_triangle<F>(
+    target: &mut RasterTarget,
+    view: [Vec3; 3],
+    object: [Vec3; 3],
+    normals: [Vec3; 3],
+    transform: &Transform,
+    light: &Light,
+    shader: &F,
+    shader_id: usize,
+    material: &Material,
+    time: f32,
+) where
+    F: Fn(&Fragment) -> (Color, f32) + ?Sized,
+{
+    let camera = target.camera;
+    let model_view = camera.view().mul(&transform.matrix());
+    let view_to_object = transform.inverse().mul(&camera.inverse_view());
+    let [v1, v2, v3] = view;
+    let [object1, object2, object3] = object;
+
+    target.stats.triangles += 1;
+    let size = target.size;
+    let projection = camera.projection();
+    let p1 = target.screen(&projection, &v1);
+    let p2 = target.screen(&projection, &v2);
+    let p3 = target.screen(&projection, &v3);
+
+    let (min_x, max_x, min_y, max_y) = match screen_bounds(size, p1, p2, p3) {
+        Some((min_x, max_x, min_y, max_y))
+            if min_y < target.rows.end
+                && max_y >= target.rows.start
+                && min_x < target.columns.end
+                && max_x >= target.columns.start =>
+        {
+            let (rows, columns) = (&target.rows, &target.columns);
+            (min_x.max(columns.start), max_x.min(columns.end - 1), min_y.max(rows.start), max_y.min(rows.end - 1))
+        }
+        _ => {
+            target.stats.offscreen += 1;
+            return;
+        }
+    };
+
+    let edges = match TriangleEdges::new(p1, p2, p3) {
+        Some(edges) => edges,
+        None => {
+            target.stats.degenerate += 1;
+            return;
+        }
+    };
+    // Counter-clockwise in the view is clockwise on the screen, whose y runs down
+    let front_facing = edges.flipped;
+    if !material.draws(front_facing, target.cull) {
+        target.stats.culled += 1;
+        return;
+    }
+
+    // Faces seen from behind are lit on the side the viewer sees
+    let edge1 = v2 - v1;
+    let edge2 = v3 - v1;
+    let face_normal = edge1.cross(&edge2).normalize();
+    let face_normal = if front_facing { face_normal } else { -face_normal };
+
+    // Each body is lit from the direction of the light as seen from its center
+    let world_light_dir = light.direction_from(&transform.offset);
+    let light_dir = camera.rotate(&world_light_dir);
+    let object_light_dir = transform.invert_direction(&world_light_dir);
+    let object_view_dir = |position: &Vec3| view_to_object.transform_direction(&camera.toward_eye(position));
+    let parallel_view_dir = object_view_dir(&v1);
+    // Under perspective, weights found on the screen are evened out by each corner's distance
+    // from the eye so surfaces don't swim
+    let inverse_distances = camera.eye_z().map(|eye_z| [1.0 / (eye_z - v1.z), 1.0 / (eye_z - v2.z), 1.0 / (eye_z - v3.z)]);
+    // Corner normals in view space, on the side of the face being drawn (the back of a
+    // double-sided surface is lit from behind)
+    let corner_normals = normals.map(|normal| {
+        let normal = model_view.transform_direction(&normal).normalize();
+        if normal.dot(&face_normal) < 0.0 { -normal } else { normal }
+    });
+
+    profile(Stage::Raster, || {
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if let Some((screen_u, screen_v)) = edges.barycentric(x as f32, y as f32) {
+                    let (u, v) = match inverse_distances {
+                        Some([w1, w2, w3]) => {
+                            let (w1, w2, w3) = ((1.0 - screen_u - screen_v) * w1, screen_u * w2, screen_v * w3);
+                            (w2 / (w1 + w2 + w3), w3 / (w1 + w2 + w3))
+                        }
+                        None => (screen_u, screen_v),
+                    };
+                    let position = v1 + edge1 * u + edge2 * v;
+                    let idx = (y - target.rows.start) * target.columns.len() + x - target.columns.start;
+                    let [n1, n2, n3] = corner_normals;
+                    let normal = match (n1 * (1.0 - u - v) + n2 * u + n3 * v).normalize() {
+                        // Corner normals that cancel out leave the face's own
+                        normal if normal.length() == 0.0 => face_normal,
+                        normal => normal,
+                    };
+                    let mut intensity = material.lighting.intensity(&normal, &light_dir, light.intensity);
+                    if let Some(style) = &target.toon {
+                        intensity = style.band(intensity);
+                    }
+
+                    // Eclipsed fragments keep only the ambient term. Occluders are placed in the
+                    // world, not the view.
+                    if material.occluder.is_some() {
+                        let world = camera.invert(&position);
+                        let shadow = material.shadow(&world, &light.direction_from(&world));
+                        let ambient = material.lighting.ambient;
+                        intensity += (ambient.min(intensity) - intensity) * shadow;
+                    }
+
+                    let fragment = Fragment {
+                        position,
+                        object_position: object1 + (object2 - object1) * u + (object3 - object1) * v,
+                        normal,
+                        intensity: intensity * material.lighting.fog(position.z),
+                        time,
+                        screen: (x as f32, y as f32),
+                        depth: camera.normalized_depth(position.z),
+                        heat: material.heat,
+                        light_dir: object_light_dir,
+                        view_dir: if inverse_distances.is_some() { object_view_dir(&position) } else { parallel_view_dir },
+                        specular: material.lighting.specular,
+                        roughness: material.lighting.roughness,
+                    };
+
+                    // Debug views draw every surface as opaque so blended ones are inspectable too
+                    if let Some(view) = target.debug {
+                        if position.z > target.depth[idx] {
+                            target.depth[idx] = position.z;
+                            target.count_write(idx);
+                            target.color[idx] = view.color(&fragment, edges.edge_distance(screen_u, screen_v)).to_u32();
+                        }
+                        continue;
+                    }
+
+                    match material.blend {
+                        BlendMode::Opaque => {
+                            if position.z > target.depth[idx] {
+                                target.depth[idx] = position.z;
+                                target.count_write(idx);
+                                if let Some(normals) = &mut target.normals {
+                                    normals[idx] = normal;
+                                }
+                                if let Some(surface) = &mut target.surface {
+                                    surface[idx] = Some(fragment.object_position);
+                                }
+                                match &mut target.deferred {
+                                    Some(deferred) => deferred[idx] = Some((fragment, shader_id)),
+                                    None => {
+                                        let (color, _) = profile(Stage::Shading, || shader(&fragment));
+                                        target.color[idx] = color.to_u32();
+                                    }
+                                }
+                            }
+                        }
+                        // Composited in draw order over whatever is already resolved, so blended
+                        // surfaces go after `resolve_half_res`. They are hidden behind opaque ones
+                        // but leave the depth buffer alone, so blended layers all show through
+                        // each other.
+                        BlendMode::Alpha => {
+                            if position.z <= target.depth[idx] {
+                                continue;
+                            }
+                            let (color, alpha) = profile(Stage::Shading, || shader(&fragment));
+                            if alpha > 0.01 {
+                                target.count_write(idx);
+                                profile(Stage::Blend, || {
+                                    target.color[idx] = blend_over(target.color[idx], color, alpha);
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Alpha-composites a color over a premultiplied pixel. Colors stay premultiplied by coverage,
+// so alpha accumulates the same way.
+pub fn blend_over(existing: u32, color: Color, alpha: f32) -> u32 {
+    let existing_r = ((existing >> 16) & 0xFF) as f32 / 255.0;
+    let existing_g = ((existing >> 8) & 0xFF) as f32 / 255.0;
+    let existing_b = (existing & 0xFF) as f32 / 255.0;
+    let existing_a = ((existing >> 24) & 0xFF) as f32 / 255.0;
+
+    let r = color.r as f32 / 255.0;
+    let g = color.g as f32 / 255.0;
+    let b = color.b as f32 / 255.0;
+
+    let final_r = (r * alpha + existing_r * (1.0 - alpha)).clamp(0.0, 1.0);
+    let final_g = (g * alpha + existing_g * (1.0 - alpha)).clamp(0.0, 1.0);
+    let final_b = (b * alpha + existing_b * (1.0 - alpha)).clamp(0.0, 1.0);
+    let final_a = (alpha + existing_a * (1.0 - alpha)).clamp(0.0, 1.0);
+
+    ((final_a * 255.0) as u32) << 24
+        | ((final_r * 255.0) as u32) << 16
+        | ((final_g * 255.0) as u32) << 8
+        | ((final_b * 255.0) as u32)
+}
+
+// Adds light on top of the target, like a glow drawn with additive blending
+pub fn blend_add(existing: u32, color: Color, strength: f32) -> u32 {
+    let add = |shift: u32, channel: u8| {
+        let value = ((existing >> shift) & 0xFF) as f32 + channel as f32 * strength;
+        (value.min(255.0) as u32) << shift
+    };
+    let alpha = (((existing >> 24) & 0xFF) as f32 + 255.0 * strength).min(255.0) as u32;
+    alpha << 24 | add(16, color.r) | add(8, color.g) | add(0, color.b)
+}
+
+// A premultiplied pixel covering only `coverage` of its area
+pub fn scale_premultiplied(pixel: u32, coverage: f32) -> u32 {
+    let scale = |shift: u32| ((((pixel >> shift) & 0xFF) as f32 * coverage).round() as u32) << shift;
+    scale(24) | scale(16) | scale(8) | scale(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::generate_sphere;
+
+    // Draws `mesh` blended into a 64x64 target counting writes, so a pixel covered twice is written
+    // twice rather than failing the depth test the second time
+    fn count_writes(mesh: &Mesh, transform: &Transform, camera: Camera, material: Material) -> RasterTarget {
+        let settings =
+            RenderSettings { framebuffer: Framebuffer::new(64, 64), camera, overdraw: true, ..RenderSettings::default() };
+        let mut target = RasterTarget::new(&settings);
+        let material = Material { blend: BlendMode::Alpha, ..material };
+        let shader = |_: &Fragment| (Color::new(255, 255, 255), 0.5);
+        draw(&mut target, mesh, transform, &Light::default(), &shader, 0, &material, 0.0);
+        target
+    }
+
+    #[test]
+    fn triangles_sharing_a_diagonal_cover_each_pixel_once() {
+        // A square from pixel 16 to 48 each way, cut along a diagonal through pixel corners
+        let [a, b, c, d] = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| Vec3::new(x, y, 0.0));
+        let mesh = Mesh::from_triangles(&[[a, b, c], [a, c, d]]);
+        let target = count_writes(&mesh, &Transform::spin(0.0), Camera::default(), Material::solid(Lighting::default()));
+        let writes = target.writes.unwrap();
+        for y in 0..64 {
+            for x in 0..64 {
+                let inside = (16..48).contains(&x) && (16..48).contains(&y);
+                assert_eq!(writes[y * 64 + x], inside as u32, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn a_sphere_covers_each_pixel_of_its_disk_once() {
+        let sphere = generate_sphere(1.0, 24);
+        let target = count_writes(&sphere, &Transform::spin(0.3), Camera::default(), Material::solid(Lighting::default()));
+        let writes = target.writes.unwrap();
+        for y in 0..64 {
+            for x in 0..64 {
+                let count = writes[y * 64 + x];
+                assert!(count <= 1, "pixel ({}, {}) written {} times", x, y, count);
+                // The outline is 16 pixels from the center, less where its facets cut inside
+                let (dx, dy) = (x as f32 - 32.0, y as f32 - 32.0);
+                if (dx * dx + dy * dy).sqrt() < 15.0 {
+                    assert_eq!(count, 1, "pixel ({}, {}) missed", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_cut_at_the_near_plane_meet_without_gaps_or_overlaps() {
+        let camera = Camera { fov: Some(60f32.to_radians()), near: 2.0, ..Camera::default() };
+        // A strip of floor running from far ahead to behind the eye, cut along a diagonal that
+        // crosses the near plane
+        let [a, b, c, d] = [(-0.6, -5.0), (0.6, -5.0), (0.6, 12.0), (-0.6, 12.0)].map(|(x, z)| Vec3::new(x, -0.5, z));
+        let mesh = Mesh::from_triangles(&[[a, b, c], [a, c, d]]);
+        let target = count_writes(&mesh, &Transform::spin(0.0), camera, Material::ring(Lighting::default()));
+        assert_eq!(target.stats.clipped, 2);
+        let writes = target.writes.unwrap();
+        assert!(writes.iter().all(|&count| count <= 1));
+
+        // What is left of the strip is a trapezoid on screen, so the rows it covers are unbroken
+        // runs, one after another
+        let runs: Vec<usize> = writes
+            .chunks(64)
+            .enumerate()
+            .filter_map(|(y, row)| {
+                let covered: Vec<usize> = (0..64).filter(|&x| row[x] == 1).collect();
+                let (first, last) = (*covered.first()?, *covered.last()?);
+                assert_eq!(covered.len(), last - first + 1, "gap in row {}", y);
+                Some(y)
+            })
+            .collect();
+        assert!(runs.len() > 10);
+        assert_eq!(runs.len(), runs[runs.len() - 1] - runs[0] + 1);
+    }
+}
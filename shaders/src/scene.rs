@@ -0,0 +1,820 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::commands::{Backend, CommandList};
+use crate::geometry::{Mesh, Transform};
+use crate::math::{Quat, Vec3};
+use crate::kernel::orbit::Orbit;
+use crate::mesh_cache;
+use crate::raster::{
+    Fragment, FrameHistory, Material, RasterTarget, TemporalHistory, accumulate_temporal,
+    draw, jitter_offset, lit, opaque, themed,
+};
+use crate::settings::{Light, LightSource, RenderSettings, SPHERE_SEGMENTS};
+use crate::shaders::{
+    PlanetShader, PlanetShine, RingGap, RingStyle, configured_shader, debris_disk_shader, europa_shader, io_shader, moon_shader,
+    ring_shader, sun_shader, with_aurora,
+};
+
+// A small moon orbiting inside the rings, keeping a gap clear around its orbit
+#[derive(Clone, Copy)]
+pub struct ShepherdMoon {
+    pub orbit_radius: f32,
+    pub radius: f32,
+    pub orbit_angle: f32,
+}
+
+impl ShepherdMoon {
+    // "saturn", "none", or "orbit_radius:radius[:orbit_angle]" moons, comma separated, with the
+    // angle in degrees
+    pub fn parse_list(value: &str) -> Option<Vec<ShepherdMoon>> {
+        match value {
+            "saturn" => return Some(ShepherdMoon::saturn()),
+            "none" => return Some(Vec::new()),
+            _ => {}
+        }
+        value
+            .split(',')
+            .filter(|moon| !moon.is_empty())
+            .map(|moon| {
+                let parts: Vec<&str> = moon.split(':').collect();
+                if parts.len() < 2 || parts.len() > 3 {
+                    return None;
+                }
+                let number = |index: usize| parts[index].parse::<f32>().ok();
+                let orbit_angle = if parts.len() == 3 { number(2)?.to_radians() } else { 0.0 };
+                let (orbit_radius, radius) = (number(0)?, number(1)?);
+                (orbit_radius > 0.0 && radius > 0.0).then_some(ShepherdMoon { orbit_radius, radius, orbit_angle })
+            })
+            .collect()
+    }
+
+    // Pan in the Encke gap and Daphnis in the Keeler gap, blown up enough to be seen
+    pub fn saturn() -> Vec<ShepherdMoon> {
+        vec![
+            ShepherdMoon { orbit_radius: 1.96, radius: 0.012, orbit_angle: 2.2 },
+            ShepherdMoon { orbit_radius: 1.99, radius: 0.005, orbit_angle: 4.0 },
+        ]
+    }
+
+    // The cleared lane is a few of the moon's radii wide, so bigger moons open wider gaps
+    pub fn gap(&self) -> RingGap {
+        RingGap::new("shepherd", self.orbit_radius, self.radius * 4.0, 0.5)
+    }
+
+    pub fn moon(&self) -> Moon {
+        Moon {
+            name: "shepherd",
+            shader: moon_shader,
+            radius: self.radius,
+            segments: 8,
+            distance: self.orbit_radius,
+            height: 0.0,
+            orbit_angle: self.orbit_angle,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            // The rings turn with the planet, and the moons keep pace in their lanes
+            orbit_speed: 1.0,
+            period: None,
+            density: 1.0,
+        }
+    }
+}
+
+// Schematic magnetosphere for figures: dipole field lines, r = L cos²(latitude) in a few
+// meridian planes, and the two radiation belts as tori around the magnetic equator. Drawn in the
+// planet's frame, so they tilt with it.
+pub fn draw_magnetosphere(target: &mut RasterTarget, transform: &Transform, planet_radius: f32) {
+    let field_color = Color::from_float(0.45, 0.65, 1.0);
+    for shell in [1.6f32, 2.4, 3.6, 5.5] {
+        // Field lines leave and re-enter the surface where L cos² = 1
+        let footpoint = (1.0 / shell).sqrt().acos();
+        for meridian in 0..6 {
+            let longitude = meridian as f32 * PI / 3.0;
+            let point = |latitude: f32| {
+                let r = shell * latitude.cos().powi(2) * planet_radius;
+                let p = Vec3::new(latitude.cos() * longitude.cos(), latitude.sin(), latitude.cos() * longitude.sin());
+                transform.apply(&(p * r))
+            };
+            let samples = 64;
+            for i in 0..samples {
+                let a = -footpoint + 2.0 * footpoint * i as f32 / samples as f32;
+                let b = -footpoint + 2.0 * footpoint * (i + 1) as f32 / samples as f32;
+                target.add_line(point(a), point(b), field_color, 0.35);
+            }
+        }
+    }
+
+    // Inner belt of protons close in, outer belt of electrons further out
+    let belts = [(1.5, 0.15, Color::from_float(1.0, 0.55, 0.3)), (2.6, 0.35, Color::from_float(0.4, 1.0, 0.7))];
+    for (radius, thickness, color) in belts {
+        for ring in 0..12 {
+            let around = ring as f32 * PI / 6.0;
+            let (ring_radius, height) = (radius + thickness * around.cos(), thickness * 0.6 * around.sin());
+            let point = |longitude: f32| {
+                let p = Vec3::new(ring_radius * longitude.cos(), height, ring_radius * longitude.sin());
+                transform.apply(&(p * planet_radius))
+            };
+            let samples = 96;
+            for i in 0..samples {
+                let a = 2.0 * PI * i as f32 / samples as f32;
+                let b = 2.0 * PI * (i + 1) as f32 / samples as f32;
+                target.add_line(point(a), point(b), color, 0.2);
+            }
+        }
+    }
+}
+
+pub fn render_sphere<F>(
+    mesh: &Mesh,
+    shader: F,
+    body: &str,
+    time: f32,
+    rotation: f32,
+    settings: &RenderSettings,
+) -> Vec<u32>
+where
+    F: Fn(&Fragment) -> Color,
+{
+    let mut target = RasterTarget::new(settings);
+    let material = settings.solid_material_for(body);
+    let light = settings.light_for(body);
+    let shader = with_aurora(lit(opaque(themed(shader, settings.theme)), &light), settings.aurora_for(body));
+
+    let transform = settings.body_transform(rotation);
+
+    let mut commands = CommandList::new();
+    commands.draw(mesh, &transform, &light, &shader, 0, &material);
+    target.execute(&commands, time);
+    if settings.smooth_limb {
+        target.smooth_silhouette(&transform.offset, mesh.radius());
+    }
+
+    if settings.magnetosphere {
+        draw_magnetosphere(&mut target, &transform, mesh.radius());
+    }
+    target.finish()
+}
+
+// A showcase spin of one planet, by shader name: `frames` frames with its rotation going round
+// `revolutions` times while the camera, the light and shader time stay put. Whole revolutions
+// loop, since the frame after the last would be the first again. Frames are drawn one at a time
+// and in any order, so they can go straight to the frame pipeline instead of all being held.
+pub struct Turntable<'a> {
+    shader: Box<PlanetShader<'a>>,
+    shader_name: String,
+    mesh: Arc<Mesh>,
+    step: f32,
+    settings: &'a RenderSettings,
+}
+
+impl<'a> Turntable<'a> {
+    pub fn new(shader_name: &str, revolutions: f32, frames: usize, settings: &'a RenderSettings) -> Result<Self, String> {
+        let shader =
+            configured_shader(shader_name, settings).ok_or_else(|| format!("Unknown planet shader: {}", shader_name))?;
+        Ok(Turntable {
+            shader,
+            shader_name: shader_name.to_string(),
+            mesh: mesh_cache::sphere(1.0, settings.segments(SPHERE_SEGMENTS)),
+            step: 2.0 * PI * revolutions / frames.max(1) as f32,
+            settings,
+        })
+    }
+
+    pub fn frame(&self, frame: usize) -> Vec<u32> {
+        render_sphere(&self.mesh, &self.shader, &self.shader_name, 0.0, self.step * frame as f32, self.settings)
+    }
+}
+
+// One frame of a spinning sphere with temporal anti-aliasing: drawn a fraction of a pixel off
+// where the last frame was, as `sample` picks, and averaged with the frames before it.
+// `rotation_step` is how far the body turned since the frame that produced `history`.
+#[allow(clippy::too_many_arguments)]
+pub fn render_sphere_temporal<F>(
+    mesh: &Mesh,
+    shader: F,
+    body: &str,
+    time: f32,
+    rotation: f32,
+    rotation_step: f32,
+    sample: usize,
+    history: Option<&TemporalHistory>,
+    settings: &RenderSettings,
+) -> TemporalHistory
+where
+    F: Fn(&Fragment) -> Color,
+{
+    let jitter = jitter_offset(sample);
+    let mut target = RasterTarget::new(settings).with_surface_positions().jittered(jitter);
+    let material = settings.solid_material_for(body);
+    let light = settings.light_for(body);
+    let shader = with_aurora(lit(opaque(themed(shader, settings.theme)), &light), settings.aurora_for(body));
+
+    let transform = settings.body_transform(rotation);
+
+    let mut commands = CommandList::new();
+    commands.draw(mesh, &transform, &light, &shader, 0, &material);
+    target.execute(&commands, time);
+
+    if settings.magnetosphere {
+        draw_magnetosphere(&mut target, &transform, mesh.radius());
+    }
+    let size = target.size;
+    let surface = target.surface.take().unwrap_or_default();
+    let frame = target.finish();
+
+    // Object space straight to last frame's screen, without its jitter
+    let camera = settings.camera;
+    let to_previous =
+        camera.projection().mul(&camera.view()).mul(&settings.body_transform(rotation - rotation_step).matrix());
+    accumulate_temporal(&frame, &surface, size, jitter, history, &|point| size.project(&to_previous.project_point(point)))
+}
+
+// One frame of a spinning sphere with checkerboard shading. `rotation_step` is how far the body
+// turned since the frame that produced `history`.
+#[allow(clippy::too_many_arguments)]
+pub fn render_sphere_interleaved<F>(
+    mesh: &Mesh,
+    shader: F,
+    material: Material,
+    light: &Light,
+    time: f32,
+    rotation: f32,
+    rotation_step: f32,
+    frame: usize,
+    history: Option<&FrameHistory>,
+    settings: &RenderSettings,
+) -> FrameHistory
+where
+    F: Fn(&Fragment) -> Color,
+{
+    let mut target = RasterTarget::new(settings).with_deferred_shading();
+    let shader = lit(opaque(shader), light);
+
+    let transform = Transform::spin(rotation);
+
+    draw(&mut target, mesh, &transform, light, &shader, 0, &material, time);
+
+    target.resolve_checkerboard(&[&shader], frame, history, &|p| p.rotate_y(-rotation_step))
+}
+
+// Mean unlit color of a shader over the whole sphere, from evenly spread sample points
+pub fn average_albedo(shader: &impl Fn(&Fragment) -> Color, time: f32) -> Color {
+    let samples = 256;
+    let golden_angle = PI * (3.0 - 5.0f32.sqrt());
+    let mut sum = [0.0f32; 3];
+    for i in 0..samples {
+        let y = 1.0 - 2.0 * (i as f32 + 0.5) / samples as f32;
+        let ring = (1.0 - y * y).sqrt();
+        let angle = golden_angle * i as f32;
+        let p = Vec3::new(ring * angle.cos(), y, ring * angle.sin());
+        let color = shader(&Fragment {
+            position: p,
+            object_position: p,
+            normal: p,
+            intensity: 1.0,
+            time,
+            screen: (0.0, 0.0),
+            depth: 0.0,
+            heat: 0.0,
+            light_dir: p,
+            view_dir: p,
+            specular: 0.0,
+            roughness: 1.0,
+        });
+        sum[0] += color.r as f32;
+        sum[1] += color.g as f32;
+        sum[2] += color.b as f32;
+    }
+    let [r, g, b] = sum.map(|channel| (channel / samples as f32).round() as u8);
+    Color::new(r, g, b)
+}
+
+// The rings at a point of the ring plane, themed but not yet lit, for rings spanning the ring
+// style's extent
+pub fn ring_surface(settings: &RenderSettings, planet_shine: PlanetShine) -> impl Fn(&Fragment) -> (Color, f32) + '_ {
+    // Shepherd moons clear their lanes on top of the named gaps
+    let mut ring_gaps = settings.ring_gaps.clone();
+    ring_gaps.extend(settings.shepherd_moons.iter().map(ShepherdMoon::gap));
+    let (inner_radius, outer_radius) = settings.ring_style.extent();
+    move |fragment| {
+        let (color, alpha) = match settings.ring_style {
+            RingStyle::Rings => ring_shader(fragment, &planet_shine, &ring_gaps, &settings.ringlets),
+            RingStyle::DebrisDisk => debris_disk_shader(fragment, inner_radius, outer_radius),
+        };
+        (settings.theme.apply(color), alpha)
+    }
+}
+
+// Looking through the rings at a slant crosses more of them, so they get denser toward edge-on
+pub fn slanted(surface: impl Fn(&Fragment) -> (Color, f32)) -> impl Fn(&Fragment) -> (Color, f32) {
+    move |fragment| {
+        let (color, alpha) = surface(fragment);
+        let slant = fragment.normal.z.abs().max(0.05);
+        (color, 1.0 - (1.0 - alpha).powf(1.0 / slant))
+    }
+}
+
+// Sunlight that reaches the planet through its rings: where the way to the light crosses the ring
+// plane, the direct light loses what the rings cover there and takes on their color, denser the
+// lower the light grazes them. The planet and rings share an object frame, rings at y = 0.
+pub fn ring_filtered(
+    shader: impl Fn(&Fragment) -> Color,
+    surface: impl Fn(&Fragment) -> (Color, f32),
+    (inner_radius, outer_radius): (f32, f32),
+    ambient: f32,
+) -> impl Fn(&Fragment) -> Color {
+    move |fragment| {
+        let (p, light_dir) = (fragment.object_position, fragment.light_dir.normalize());
+        if p.y * light_dir.y >= 0.0 {
+            return shader(fragment);
+        }
+        let crossing = p + light_dir * (-p.y / light_dir.y);
+        let radius = (crossing.x * crossing.x + crossing.z * crossing.z).sqrt();
+        if radius < inner_radius || radius > outer_radius {
+            return shader(fragment);
+        }
+        let (ring_color, alpha) = surface(&Fragment { object_position: Vec3::new(crossing.x, 0.0, crossing.z), ..*fragment });
+        if alpha <= 0.0 {
+            return shader(fragment);
+        }
+        let alpha = 1.0 - (1.0 - alpha).powf(1.0 / light_dir.y.abs().max(0.05));
+
+        // Only the direct light goes through the rings; the ambient part is shaded on its own
+        let color = shader(fragment);
+        let shade = shader(&Fragment { intensity: fragment.intensity.min(ambient), ..*fragment });
+        let through = |shade: u8, color: u8, ring: u8| {
+            let filter = (1.0 - alpha) * (1.0 + (ring as f32 / 255.0 - 1.0) * alpha);
+            (shade as f32 + color.saturating_sub(shade) as f32 * filter) as u8
+        };
+        Color::new(
+            through(shade.r, color.r, ring_color.r),
+            through(shade.g, color.g, ring_color.g),
+            through(shade.b, color.b, ring_color.b),
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_planet_with_rings(
+    planet_mesh: &Mesh,
+    ring_mesh: &Mesh,
+    planet_shader: impl Fn(&Fragment) -> Color,
+    body: &str,
+    time: f32,
+    rotation: f32,
+    settings: &RenderSettings,
+) -> Vec<u32> {
+    let mut target = RasterTarget::new(settings);
+    let planet_material = settings.solid_material_for(body);
+    let ring_material = Material::ring(settings.lighting_for(settings.ring_style.body()));
+    let light = settings.light_for(body);
+    let planet_shine = PlanetShine {
+        color: average_albedo(&planet_shader, time),
+        strength: settings.planet_shine,
+    };
+    let shepherd_shader = lit(opaque(themed(moon_shader, settings.theme)), &light);
+
+    // `ring_mesh` spans the ring style's extent
+    let (inner_radius, outer_radius) = settings.ring_style.extent();
+    let surface = ring_surface(settings, planet_shine);
+
+    let planet_shader = themed(planet_shader, settings.theme);
+    let planet_shader = ring_filtered(planet_shader, &surface, (inner_radius, outer_radius), planet_material.lighting.ambient);
+    let planet_shader = with_aurora(lit(opaque(planet_shader), &light), settings.aurora_for(body));
+
+    // The rings' edges are what is left of them edge-on: shaded like the ring just inside the edge,
+    // and about as dense as the whole ring seen end to end
+    let edge_shader = lit(
+        |fragment: &Fragment| {
+            let p = fragment.object_position;
+            let radius = (p.x * p.x + p.z * p.z).sqrt().max(1e-6);
+            let inside = radius.clamp(inner_radius + 0.01, outer_radius - 0.01) / radius;
+            let fragment = Fragment { object_position: Vec3::new(p.x * inside, 0.0, p.z * inside), ..*fragment };
+            let (color, alpha) = surface(&fragment);
+            (color, (alpha * 3.0).min(0.95))
+        },
+        &light,
+    );
+    let ring_shader = lit(slanted(&surface), &light);
+
+    let transform = settings.body_transform(rotation);
+
+    let planet_radius = planet_mesh.radius();
+    let shepherds: Vec<_> = settings
+        .shepherd_moons
+        .iter()
+        .map(|shepherd| {
+            let moon = shepherd.moon();
+            let moon_material = Material::solid(settings.lighting_for(moon.name))
+                .shadowed_by(transform.offset, planet_radius);
+            let moon_mesh = mesh_cache::sphere(moon.radius, settings.segments(moon.segments));
+            // Orbiting in the ring plane, so the tilt carries the orbit along with the rings
+            let orbit = moon.transform(rotation, 0.0);
+            let offset = transform.orientation.rotate(&orbit.offset.rotate_x(settings.axial_tilt));
+            let moon_transform =
+                Transform { offset, ..orbit }.tilted(settings.axial_tilt).oriented(transform.orientation);
+            (moon_mesh, moon_transform, moon_material)
+        })
+        .collect();
+
+    // The edges go around in as many segments as the ring: two triangles each
+    let ring_segments = ring_mesh.indices.len() / 2;
+    let edges = match settings.ring_thickness > 0.0 {
+        true => [inner_radius, outer_radius].map(|radius| mesh_cache::ring_edge(radius, settings.ring_thickness, ring_segments)).to_vec(),
+        false => Vec::new(),
+    };
+
+    // The rings blend over the planet and moons, which the command list draws and shades first
+    let mut commands = CommandList::new();
+    commands.draw(planet_mesh, &transform, &light, &planet_shader, 0, &planet_material);
+    for (moon_mesh, moon_transform, moon_material) in &shepherds {
+        commands.draw(moon_mesh, moon_transform, &light, &shepherd_shader, 1, moon_material);
+    }
+    commands.draw(ring_mesh, &transform, &light, &ring_shader, 1, &ring_material);
+    for edge in &edges {
+        commands.draw(edge, &transform, &light, &edge_shader, 1, &ring_material);
+    }
+    target.execute(&commands, time);
+
+    if settings.magnetosphere {
+        draw_magnetosphere(&mut target, &transform, planet_radius);
+    }
+    target.finish()
+}
+
+// A moon: its own shader and mesh size, and a circular orbit around the planet's center
+#[derive(Clone, Copy)]
+pub struct Moon {
+    // Key for per-body lighting overrides
+    pub name: &'static str,
+    pub shader: fn(&Fragment) -> Color,
+    pub radius: f32,
+    pub segments: usize,
+    pub distance: f32,
+    // Height of the orbit above the planet's equator
+    pub height: f32,
+    // Position along the orbit at zero rotation, measured from the closest approach
+    pub orbit_angle: f32,
+    // 0 for a circular orbit; `distance` is then the semi-major axis
+    pub eccentricity: f32,
+    // Tilt of the orbit's plane about the x axis, in radians
+    pub inclination: f32,
+    // Orbit and spin rate as a fraction of the planet's spin
+    pub orbit_speed: f32,
+    // Seconds of shader time per orbit. With one the moon follows its Keplerian `orbit` through
+    // time instead of keeping pace with the planet's spin, and `orbit_speed` is unused.
+    pub period: Option<f32>,
+    // Mean density relative to the planet's; denser moons survive closer in
+    pub density: f32,
+}
+
+impl Moon {
+    // The original grey cratered moon
+    pub fn luna(orbit_angle: f32) -> Moon {
+        Moon {
+            name: "moon",
+            shader: moon_shader,
+            radius: 0.3,
+            segments: 30,
+            distance: 2.5,
+            height: 0.3,
+            orbit_angle,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            orbit_speed: 0.3,
+            period: None,
+            density: 1.0,
+        }
+    }
+
+    // A small volcanic moon on a tight orbit, for gas giants
+    pub fn io(orbit_angle: f32) -> Moon {
+        Moon {
+            name: "io",
+            shader: io_shader,
+            radius: 0.22,
+            segments: 30,
+            distance: 1.8,
+            height: 0.1,
+            orbit_angle,
+            eccentricity: 0.05,
+            inclination: 0.0,
+            orbit_speed: 0.7,
+            period: None,
+            density: 2.7,
+        }
+    }
+
+    // An icy moon with a thin shell over an ocean, flexed on a slightly eccentric orbit
+    pub fn europa(orbit_angle: f32) -> Moon {
+        Moon {
+            name: "europa",
+            shader: europa_shader,
+            radius: 0.2,
+            segments: 30,
+            distance: 2.8,
+            height: 0.15,
+            orbit_angle,
+            eccentricity: 0.009,
+            inclination: 0.0,
+            orbit_speed: 0.35,
+            period: None,
+            density: 2.3,
+        }
+    }
+
+    // A moon preset by name, for picking one on the command line
+    pub fn by_name(name: &str, orbit_angle: f32) -> Option<Moon> {
+        match name {
+            "moon" | "luna" => Some(Moon::luna(orbit_angle)),
+            "io" => Some(Moon::io(orbit_angle)),
+            "europa" => Some(Moon::europa(orbit_angle)),
+            _ => None,
+        }
+    }
+
+    // Fluid Roche limit: any closer and the planet's tides pull the moon apart
+    pub fn roche_limit(&self, planet_radius: f32) -> f32 {
+        2.44 * planet_radius * (1.0 / self.density.max(0.01)).cbrt()
+    }
+
+    // For a moon inside the Roche limit, how far its debris has spread along the orbit: 0 just
+    // inside the limit, 1 (a complete ring) from 70% of it inwards
+    pub fn disruption(&self, planet_radius: f32) -> Option<f32> {
+        let limit = self.roche_limit(planet_radius);
+        (self.distance < limit).then(|| ((limit - self.distance) / (0.3 * limit)).clamp(0.0, 1.0))
+    }
+
+    // Tides flex a moon on an eccentric orbit, heating it far more strongly the closer it is.
+    // Normalized so e = 0.05 at two planet radii gives 1.
+    pub fn tidal_heating(&self, planet_radius: f32) -> f32 {
+        let closeness = 2.0 * planet_radius / self.distance.max(0.01);
+        ((self.eccentricity / 0.05).powi(2) * closeness.powi(5)).min(2.5)
+    }
+
+    // Distance from the planet's center at `anomaly`, the angle from the closest approach
+    pub fn orbit_radius(&self, anomaly: f32) -> f32 {
+        let e = self.eccentricity.clamp(0.0, 0.95);
+        if e == 0.0 {
+            return self.distance;
+        }
+        self.distance * (1.0 - e * e) / (1.0 + e * anomaly.cos())
+    }
+
+    // Turns the equatorial plane into the plane of the orbit
+    pub fn orbit_plane(&self) -> Quat {
+        Quat::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), self.inclination)
+    }
+
+    // The moon's orbit through time, for moons with a period; it starts at `orbit_angle`
+    pub fn orbit(&self) -> Option<Orbit> {
+        let period = self.period?;
+        let eccentricity = self.eccentricity.clamp(0.0, 0.95);
+        Some(Orbit {
+            semi_major_axis: self.distance,
+            eccentricity,
+            period,
+            inclination: self.inclination,
+            // Angles on the orbit run against the direction of travel
+            phase: Orbit::phase_for(-self.orbit_angle, eccentricity),
+        })
+    }
+
+    // How far round the moon has come from `orbit_angle`, which is also how far it has turned on
+    // its axis since it always shows the planet the same face
+    pub fn orbit_turn(&self, rotation: f32, time: f32) -> f32 {
+        match self.orbit() {
+            Some(orbit) => self.orbit_angle + orbit.true_anomaly(time),
+            None => rotation * self.orbit_speed,
+        }
+    }
+
+    pub fn transform(&self, rotation: f32, time: f32) -> Transform {
+        let plane = self.orbit_plane();
+        if let Some(orbit) = self.orbit() {
+            let offset = plane.rotate(&(orbit.in_plane_position(time) + Vec3::new(0.0, self.height, 0.0)));
+            return Transform::new(self.orbit_turn(rotation, time), offset).oriented(plane);
+        }
+
+        let angle = rotation * self.orbit_speed;
+        // rotate_y turns the offset back by `angle`, and the ellipse stays put while it does
+        let radius = self.orbit_radius(self.orbit_angle - angle);
+        let offset = Vec3::new(
+            radius * self.orbit_angle.cos(),
+            self.height,
+            radius * self.orbit_angle.sin(),
+        );
+        // The offset swings around with the moon's share of the planet's spin, then the whole
+        // orbit (and the moon's spin axis with it) leans over by the inclination
+        Transform::new(angle, plane.rotate(&offset.rotate_y(angle))).oriented(plane)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_planet_with_moon(
+    planet_mesh: &Mesh,
+    planet_shader: impl Fn(&Fragment) -> Color,
+    body: &str,
+    time: f32,
+    rotation: f32,
+    moons: &[Moon],
+    settings: &RenderSettings,
+) -> Vec<u32> {
+    let mut target = RasterTarget::new(settings);
+    let planet_material = settings.solid_material_for(body);
+    let light = settings.light_for(body);
+    let planet_shader = themed(planet_shader, settings.theme);
+    let planet_shader = with_aurora(lit(opaque(planet_shader), &light), settings.aurora_for(body));
+    let moon_shaders: Vec<_> =
+        moons.iter().map(|moon| lit(opaque(themed(moon.shader, settings.theme)), &light)).collect();
+
+    let planet_transform = settings.body_transform(rotation);
+    let planet_radius = planet_mesh.radius();
+
+    let moon_meshes: Vec<_> = moons
+        .iter()
+        .map(|moon| match moon.disruption(planet_radius) {
+            // A moon inside the Roche limit is drawn as its debris instead
+            Some(spread) => {
+                let orbit = Transform::spin(moon.orbit_turn(rotation, time)).oriented(moon.orbit_plane());
+                (mesh_cache::debris(moon, spread), orbit)
+            }
+            // Moon meshes are built at their own radius so surface patterns keep their size in world units
+            None => (mesh_cache::sphere(moon.radius, settings.segments(moon.segments)), moon.transform(rotation, time)),
+        })
+        .collect();
+
+    let mut commands = CommandList::new();
+    commands.draw(planet_mesh, &planet_transform, &light, &planet_shader, 0, &planet_material);
+    for (index, ((moon, moon_shader), (moon_mesh, moon_transform))) in
+        moons.iter().zip(&moon_shaders).zip(&moon_meshes).enumerate()
+    {
+        // Seen from the sun the planet can hide the moon, so its phase includes eclipses
+        let moon_material = Material::solid(settings.lighting_for(moon.name))
+            .shadowed_by(planet_transform.offset, planet_radius)
+            .heated(moon.tidal_heating(planet_radius));
+        commands.draw(moon_mesh, moon_transform, &light, moon_shader, index + 1, &moon_material);
+    }
+    target.execute(&commands, time);
+    if settings.magnetosphere {
+        draw_magnetosphere(&mut target, &planet_transform, planet_radius);
+    }
+    target.finish()
+}
+
+// One body of a `Scene`. `body` names it for the per-body lighting, light and aurora, the same
+// way as for the single-body renders.
+pub struct SceneBody<'a> {
+    pub body: String,
+    pub mesh: Arc<Mesh>,
+    pub transform: Transform,
+    pub shader: Box<PlanetShader<'a>>,
+    // The settings' material for `body` when None
+    pub material: Option<Material>,
+}
+
+// Rings around a body of a `Scene`, from `inner` to `outer` in the equatorial plane of
+// `transform`. They are shaded as the ring style's own extent stretched over that span.
+pub struct SceneRings {
+    pub body: String,
+    pub mesh: Arc<Mesh>,
+    pub transform: Transform,
+    pub inner: f32,
+    pub outer: f32,
+    pub planet_shine: PlanetShine,
+}
+
+// Any number of bodies drawn into one frame with a shared depth buffer, so they hide each other
+// wherever they are: a whole planetary system at once, rather than one planet and what circles it
+#[derive(Default)]
+pub struct Scene<'a> {
+    pub bodies: Vec<SceneBody<'a>>,
+    pub rings: Vec<SceneRings>,
+}
+
+impl<'a> Scene<'a> {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    pub fn add(
+        &mut self,
+        body: &str,
+        mesh: Arc<Mesh>,
+        transform: Transform,
+        shader: impl Fn(&Fragment) -> Color + Sync + 'a,
+    ) -> &mut Self {
+        self.bodies.push(SceneBody { body: body.to_string(), mesh, transform, shader: Box::new(shader), material: None });
+        self
+    }
+
+    // Rings from `inner` to `outer` around a body placed by `transform`, `segments` around
+    pub fn add_rings(
+        &mut self,
+        body: &str,
+        transform: Transform,
+        inner: f32,
+        outer: f32,
+        segments: usize,
+        planet_shine: PlanetShine,
+    ) -> &mut Self {
+        let mesh = mesh_cache::ring(inner, outer, segments);
+        self.rings.push(SceneRings { body: body.to_string(), mesh, transform, inner, outer, planet_shine });
+        self
+    }
+
+    pub fn render(&self, time: f32, settings: &RenderSettings) -> Vec<u32> {
+        let mut target = RasterTarget::new(settings);
+        let lights: Vec<Light> = self.bodies.iter().map(|body| settings.light_for(&body.body)).collect();
+        let shaders: Vec<_> = self
+            .bodies
+            .iter()
+            .zip(&lights)
+            .map(|(body, light)| {
+                let shader = lit(opaque(themed(&body.shader, settings.theme)), light);
+                with_aurora(shader, settings.aurora_for(&body.body))
+            })
+            .collect();
+
+        // Each body is its own shader id, so half-res shading never fills in across two of them
+        let mut commands = CommandList::new();
+        for (index, ((body, shader), light)) in self.bodies.iter().zip(&shaders).zip(&lights).enumerate() {
+            let material = body.material.unwrap_or_else(|| settings.solid_material_for(&body.body));
+            commands.draw(&body.mesh, &body.transform, light, shader, index, &material);
+        }
+
+        let ring_material = Material::ring(settings.lighting_for(settings.ring_style.body()));
+        let (style_inner, style_outer) = settings.ring_style.extent();
+        let ring_lights: Vec<Light> = self.rings.iter().map(|rings| settings.light_for(&rings.body)).collect();
+        let ring_shaders: Vec<_> = self
+            .rings
+            .iter()
+            .zip(&ring_lights)
+            .map(|(rings, light)| {
+                let surface = ring_surface(settings, rings.planet_shine);
+                let (inner, outer) = (rings.inner, rings.outer);
+                let stretched = move |fragment: &Fragment| {
+                    let p = fragment.object_position;
+                    let radius = (p.x * p.x + p.z * p.z).sqrt().max(1e-6);
+                    let style_radius = style_inner + (radius - inner) / (outer - inner) * (style_outer - style_inner);
+                    let scale = style_radius / radius;
+                    surface(&Fragment { object_position: Vec3::new(p.x * scale, p.y, p.z * scale), ..*fragment })
+                };
+                lit(slanted(stretched), light)
+            })
+            .collect();
+        for (index, ((rings, shader), light)) in self.rings.iter().zip(&ring_shaders).zip(&ring_lights).enumerate() {
+            commands.draw(&rings.mesh, &rings.transform, light, shader, self.bodies.len() + index, &ring_material);
+        }
+        target.execute(&commands, time);
+        target.finish()
+    }
+}
+
+// Transit: a small planet crossing in front of the sun, seen from behind the planet's night side
+#[allow(clippy::too_many_arguments)]
+pub fn render_transit_frame(
+    sun_mesh: &Mesh,
+    planet_mesh: &Mesh,
+    planet_shader: impl Fn(&Fragment) -> Color,
+    body: &str,
+    time: f32,
+    planet_x: f32,
+    settings: &RenderSettings,
+) -> Vec<u32> {
+    let mut target = RasterTarget::new(settings);
+    let sun_material = Material::solid(settings.lighting_for("sun"));
+    let planet_material = Material::solid(settings.lighting_for(body));
+    let light = settings.light;
+    // The planet is lit by the sun itself, which sits behind it, so mostly its unlit hemisphere
+    // faces the viewer
+    let planet_light = Light { source: LightSource::Position(Vec3::new(0.0, 0.0, 0.0)), ..light };
+    let sun_shader = opaque(themed(sun_shader, settings.theme));
+    let planet_shader = lit(opaque(themed(planet_shader, settings.theme)), &planet_light);
+
+    let planet_offset = Vec3::new(planet_x, 0.15, 1.5);
+
+    let sun_transform = Transform::spin(0.0);
+    let planet_transform = Transform::new(0.0, planet_offset);
+
+    let mut commands = CommandList::new();
+    commands.draw(sun_mesh, &sun_transform, &light, &sun_shader, 0, &sun_material);
+    commands.draw(planet_mesh, &planet_transform, &planet_light, &planet_shader, 1, &planet_material);
+    target.execute(&commands, time);
+    target.finish()
+}
+
+// Total brightness of a frame (sum of pixel luminance)
+pub fn total_flux(buffer: &[u32]) -> f64 {
+    buffer
+        .iter()
+        .map(|&pixel| {
+            let r = ((pixel >> 16) & 0xFF) as f64 / 255.0;
+            let g = ((pixel >> 8) & 0xFF) as f64 / 255.0;
+            let b = (pixel & 0xFF) as f64 / 255.0;
+            0.2126 * r + 0.7152 * g + 0.0722 * b
+        })
+        .sum()
+}
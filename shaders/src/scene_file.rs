@@ -0,0 +1,486 @@
+use std::collections::{BTreeMap, HashSet};
+use std::f32::consts::PI;
+
+use toml::{Table, Value};
+
+use crate::kernel::orbit::Orbit;
+use crate::geometry::Camera;
+use crate::math::Vec3;
+use crate::schema::{SCHEMA_VERSION, migrate, shader_params};
+use crate::settings::{Tessellation, body_key};
+use crate::shaders::SHADER_NAMES;
+
+// A system described in a TOML file:
+//
+//     schema_version = 1
+//     quality = "high"
+//
+//     [camera]
+//     position = [0.0, 6.0, 14.0]
+//     target = [0.0, 0.0, 0.0]
+//     fov = 45.0
+//
+//     [[body]]
+//     name = "Sol"
+//     shader = "sun"
+//     radius = 1.0
+//
+//     [[body]]
+//     name = "Terra"
+//     shader = "rocky"
+//     radius = 0.4
+//     orbits = "Sol"
+//     distance = 6.0
+//     eccentricity = 0.02
+//     period = 20.0
+//     inclination = 5.0
+//     phase = 90.0
+//     params = { cloud_altitude = 0.05 }
+//     rings = { inner = 0.6, outer = 0.9 }
+//
+// Bodies without `orbits` sit at the origin; the others follow a Keplerian orbit around the body
+// they name, with `distance` as its semi-major axis, once every `period` seconds of shader time.
+// `inclination` and `phase` (where along the orbit it starts, 0 at the closest approach) are in
+// degrees. The camera takes the same fields as
+// the camera switches, with `fov` in degrees; left out, it is up to whoever renders the scene.
+// `quality` (draft, normal or high) is the mesh tessellation, as --quality sets it.
+pub struct SceneFile {
+    pub schema_version: u32,
+    pub camera: Option<Camera>,
+    pub quality: Option<Tessellation>,
+    pub bodies: Vec<BodyDescription>,
+}
+
+pub struct BodyDescription {
+    pub name: String,
+    // As written; `body_key` turns short names into the shader's own
+    pub shader: String,
+    pub radius: f32,
+    pub orbits: Option<String>,
+    pub distance: f32,
+    pub eccentricity: f32,
+    pub period: f32,
+    // Degrees, as written
+    pub inclination: f32,
+    pub phase: f32,
+    pub params: BTreeMap<String, f32>,
+    // Inner and outer ring radii
+    pub rings: Option<(f32, f32)>,
+}
+
+impl BodyDescription {
+    // The body's path around what it orbits
+    pub fn orbit(&self) -> Orbit {
+        Orbit {
+            semi_major_axis: self.distance,
+            eccentricity: self.eccentricity,
+            period: self.period,
+            inclination: self.inclination.to_radians(),
+            phase: self.phase.to_radians(),
+        }
+    }
+}
+
+// Pulls typed fields out of one TOML table, remembering every problem instead of stopping at the
+// first
+struct Fields<'a> {
+    table: &'a Table,
+    context: String,
+    problems: &'a mut Vec<String>,
+}
+
+impl<'a> Fields<'a> {
+    fn number(&mut self, key: &str) -> Option<f32> {
+        match self.table.get(key)? {
+            Value::Float(value) => Some(*value as f32),
+            Value::Integer(value) => Some(*value as f32),
+            other => {
+                self.problems.push(format!("{}: {} should be a number, not {}", self.context, key, other.type_str()));
+                None
+            }
+        }
+    }
+
+    fn string(&mut self, key: &str) -> Option<String> {
+        match self.table.get(key)? {
+            Value::String(value) => Some(value.clone()),
+            other => {
+                self.problems.push(format!("{}: {} should be a string, not {}", self.context, key, other.type_str()));
+                None
+            }
+        }
+    }
+
+    fn vector(&mut self, key: &str) -> Option<Vec3> {
+        match self.table.get(key)? {
+            Value::Array(items) => {
+                let numbers: Vec<f32> = items
+                    .iter()
+                    .filter_map(|item| match item {
+                        Value::Float(value) => Some(*value as f32),
+                        Value::Integer(value) => Some(*value as f32),
+                        _ => None,
+                    })
+                    .collect();
+                if items.len() == 3
+                    && let [x, y, z] = numbers[..]
+                {
+                    return Some(Vec3::new(x, y, z));
+                }
+                self.problems.push(format!("{}: {} should be three numbers", self.context, key));
+                None
+            }
+            other => {
+                self.problems.push(format!("{}: {} should be an array of three numbers, not {}", self.context, key, other.type_str()));
+                None
+            }
+        }
+    }
+
+    fn table(&mut self, key: &str) -> Option<&'a Table> {
+        let table = self.table;
+        match table.get(key)? {
+            Value::Table(value) => Some(value),
+            other => {
+                self.problems.push(format!("{}: {} should be a table, not {}", self.context, key, other.type_str()));
+                None
+            }
+        }
+    }
+
+    fn unknown_keys(&mut self, known: &[&str]) {
+        for key in self.table.keys().filter(|key| !known.contains(&key.as_str())) {
+            self.problems.push(format!("{}: unknown key {}", self.context, key));
+        }
+    }
+}
+
+impl SceneFile {
+    // Reads and validates a scene file, with every problem found if it isn't usable
+    pub fn load(path: &str) -> Result<SceneFile, Vec<String>> {
+        let text = std::fs::read_to_string(path).map_err(|error| vec![format!("Could not read {}: {}", path, error)])?;
+        let (scene, mut problems) = SceneFile::parse(&text).map_err(|error| vec![error])?;
+        problems.extend(scene.validate());
+        if problems.is_empty() { Ok(scene) } else { Err(problems) }
+    }
+
+    // Reads the structure of the file. Values are only checked for their types here, and those
+    // problems come back next to the scene so `validate` can still report the rest; only invalid
+    // TOML is an error.
+    pub fn parse(text: &str) -> Result<(SceneFile, Vec<String>), String> {
+        let table: Table = text.parse().map_err(|error: toml::de::Error| error.to_string())?;
+        let mut problems = Vec::new();
+        let mut fields = Fields { table: &table, context: "scene".to_string(), problems: &mut problems };
+        fields.unknown_keys(&["schema_version", "quality", "camera", "body"]);
+        let schema_version = match fields.number("schema_version") {
+            Some(version) if version >= 1.0 && version.fract() == 0.0 => version as u32,
+            Some(version) => {
+                fields.problems.push(format!("scene: schema_version {} is not a version number", version));
+                SCHEMA_VERSION
+            }
+            None => SCHEMA_VERSION,
+        };
+
+        let quality = fields.string("quality").and_then(|quality| {
+            let parsed = Tessellation::parse(&quality);
+            if parsed.is_none() {
+                fields.problems.push(format!("scene: quality should be draft, normal or high, not {:?}", quality));
+            }
+            parsed
+        });
+
+        let camera = fields.table("camera").map(|table| {
+            let mut camera_fields = Fields { table, context: "camera".to_string(), problems: &mut *fields.problems };
+            camera_fields.unknown_keys(&["position", "target", "up", "fov", "near", "far", "zoom"]);
+            let default = Camera::default();
+            Camera {
+                position: camera_fields.vector("position").unwrap_or(default.position),
+                target: camera_fields.vector("target").unwrap_or(default.target),
+                up: camera_fields.vector("up").unwrap_or(default.up),
+                fov: camera_fields.number("fov").map(f32::to_radians),
+                near: camera_fields.number("near").unwrap_or(default.near),
+                far: camera_fields.number("far").unwrap_or(default.far),
+                zoom: camera_fields.number("zoom").unwrap_or(default.zoom),
+            }
+        });
+
+        let mut bodies = Vec::new();
+        let entries = match table.get("body") {
+            Some(Value::Array(entries)) => entries.as_slice(),
+            Some(other) => {
+                problems.push(format!("scene: body should be an array of tables, not {}", other.type_str()));
+                &[]
+            }
+            None => &[],
+        };
+        for (index, entry) in entries.iter().enumerate() {
+            let Value::Table(entry) = entry else {
+                problems.push(format!("body {}: should be a table, not {}", index + 1, entry.type_str()));
+                continue;
+            };
+            let context = match entry.get("name") {
+                Some(Value::String(name)) => format!("body {:?}", name),
+                _ => format!("body {}", index + 1),
+            };
+            let mut fields = Fields { table: entry, context, problems: &mut problems };
+            fields.unknown_keys(&[
+                "name",
+                "shader",
+                "radius",
+                "orbits",
+                "distance",
+                "eccentricity",
+                "period",
+                "inclination",
+                "phase",
+                "params",
+                "rings",
+            ]);
+            let name = fields.string("name");
+            let shader = fields.string("shader");
+            if name.is_none() {
+                fields.problems.push(format!("{}: missing name", fields.context));
+            }
+            if shader.is_none() {
+                fields.problems.push(format!("{}: missing shader", fields.context));
+            }
+
+            let mut params = BTreeMap::new();
+            if let Some(table) = fields.table("params") {
+                let mut param_fields = Fields {
+                    table,
+                    context: format!("{} params", fields.context),
+                    problems: &mut *fields.problems,
+                };
+                for key in table.keys() {
+                    if let Some(value) = param_fields.number(key) {
+                        params.insert(key.clone(), value);
+                    }
+                }
+            }
+
+            let mut rings = None;
+            if let Some(table) = fields.table("rings") {
+                let mut ring_fields = Fields {
+                    table,
+                    context: format!("{} rings", fields.context),
+                    problems: &mut *fields.problems,
+                };
+                ring_fields.unknown_keys(&["inner", "outer"]);
+                match (ring_fields.number("inner"), ring_fields.number("outer")) {
+                    (Some(inner), Some(outer)) => rings = Some((inner, outer)),
+                    _ => ring_fields.problems.push(format!("{}: needs inner and outer radii", ring_fields.context)),
+                }
+            }
+
+            bodies.push(BodyDescription {
+                name: name.unwrap_or_default(),
+                shader: shader.unwrap_or_default(),
+                radius: fields.number("radius").unwrap_or(1.0),
+                orbits: fields.string("orbits"),
+                distance: fields.number("distance").unwrap_or(0.0),
+                eccentricity: fields.number("eccentricity").unwrap_or(0.0),
+                period: fields.number("period").unwrap_or(1.0),
+                inclination: fields.number("inclination").unwrap_or(0.0),
+                phase: fields.number("phase").unwrap_or(0.0),
+                params,
+                rings,
+            });
+        }
+
+        Ok((SceneFile { schema_version, camera, quality, bodies }, problems))
+    }
+
+    // A valid scene file's text rewritten for the current shader schema, with a note per change.
+    // Only the bodies whose parameters changed get them written out, and comments aren't kept.
+    pub fn upgrade(text: &str) -> Result<(String, Vec<String>), Vec<String>> {
+        let (scene, mut problems) = SceneFile::parse(text).map_err(|error| vec![error])?;
+        problems.extend(scene.validate());
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        let mut table: Table = text.parse().map_err(|error: toml::de::Error| vec![error.to_string()])?;
+        let mut notes = Vec::new();
+        if let Some(Value::Array(body_tables)) = table.get_mut("body") {
+            for (body_table, body) in body_tables.iter_mut().zip(&scene.bodies) {
+                let mut params = body.params.clone();
+                let changes = migrate(body_key(&body.shader), scene.schema_version, &mut params)
+                    .map_err(|error| vec![format!("body {:?}: {}", body.name, error)])?;
+                if changes.is_empty() {
+                    continue;
+                }
+                // Through f32's shortest form, so 0.05 is written back as 0.05
+                let params: Table = params
+                    .into_iter()
+                    .map(|(name, value)| (name, Value::Float(value.to_string().parse().unwrap_or(value as f64))))
+                    .collect();
+                if let Value::Table(body_table) = body_table {
+                    body_table.insert("params".to_string(), Value::Table(params));
+                }
+                notes.extend(changes.into_iter().map(|change| format!("body {:?}: {}", body.name, change)));
+            }
+        }
+        match table.get("schema_version") {
+            None => notes.push(format!("schema_version set to {}", SCHEMA_VERSION)),
+            Some(_) if scene.schema_version != SCHEMA_VERSION => {
+                notes.push(format!("schema_version {} is now {}", scene.schema_version, SCHEMA_VERSION))
+            }
+            Some(_) => {}
+        }
+        table.insert("schema_version".to_string(), Value::Integer(SCHEMA_VERSION as i64));
+        Ok((table.to_string(), notes))
+    }
+
+    // Everything wrong with the scene, so a bad file can be fixed in one go instead of failing
+    // partway into a render
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.bodies.is_empty() {
+            problems.push("scene: no bodies".to_string());
+        }
+
+        // Parameters can only be checked against a schema this build knows
+        let known_schema = self.schema_version <= SCHEMA_VERSION;
+        if !known_schema {
+            problems.push(format!(
+                "scene: written for shader schema {}, but this build only knows up to {}",
+                self.schema_version, SCHEMA_VERSION
+            ));
+        }
+
+        if let Some(camera) = &self.camera {
+            let back = camera.position - camera.target;
+            if back.length() < 1e-6 || camera.up.cross(&back).length() < 1e-6 {
+                problems.push("camera: needs to be apart from its target, with up not along its view".to_string());
+            }
+            if let Some(fov) = camera.fov
+                && !(fov > 0.0 && fov < PI)
+            {
+                problems.push(format!("camera: fov {} should be between 0 and 180 degrees", fov.to_degrees()));
+            }
+            if camera.near <= 0.0 || camera.far <= camera.near {
+                problems.push(format!("camera: near {} and far {} should be positive, near first", camera.near, camera.far));
+            }
+            if camera.zoom <= 0.0 {
+                problems.push(format!("camera: zoom {} should be positive", camera.zoom));
+            }
+        }
+
+        let mut names = HashSet::new();
+        for body in self.bodies.iter().filter(|body| !body.name.is_empty()) {
+            if !names.insert(body.name.as_str()) {
+                problems.push(format!("body {:?}: name used more than once", body.name));
+            }
+        }
+
+        for body in &self.bodies {
+            let context = format!("body {:?}", body.name);
+            let shader = body_key(&body.shader);
+            if SHADER_NAMES.contains(&shader) && known_schema {
+                let mut params = body.params.clone();
+                match migrate(shader, self.schema_version, &mut params) {
+                    Ok(_) => {
+                        for (name, value) in &params {
+                            match shader_params(shader).find(|param| param.name == name) {
+                                Some(param) if *value < param.min || *value > param.max => problems.push(format!(
+                                    "{}: {} = {} is outside {}..{}",
+                                    context, name, value, param.min, param.max
+                                )),
+                                Some(_) => {}
+                                None => problems.push(format!("{}: {} doesn't take a parameter {}", context, shader, name)),
+                            }
+                        }
+                    }
+                    Err(error) => problems.push(format!("{}: {}", context, error)),
+                }
+            } else if !SHADER_NAMES.contains(&shader) && !body.shader.is_empty() {
+                problems.push(format!("{}: unknown shader {:?}", context, body.shader));
+            }
+
+            if body.radius <= 0.0 {
+                problems.push(format!("{}: radius {} should be positive", context, body.radius));
+            }
+            if let Some((inner, outer)) = body.rings {
+                if inner <= body.radius {
+                    problems.push(format!("{}: rings start at {}, inside the body (radius {})", context, inner, body.radius));
+                }
+                if outer <= inner {
+                    problems.push(format!("{}: ring outer radius {} should be larger than the inner {}", context, outer, inner));
+                }
+            }
+
+            let Some(parent_name) = &body.orbits else {
+                continue;
+            };
+            let Some(parent) = self.bodies.iter().find(|other| &other.name == parent_name) else {
+                problems.push(format!("{}: orbits {:?}, which isn't in the scene", context, parent_name));
+                continue;
+            };
+            if self.orbit_chain_loops(body) {
+                problems.push(format!("{}: its chain of orbits loops back on itself", context));
+                continue;
+            }
+            if !(0.0..1.0).contains(&body.eccentricity) {
+                problems.push(format!("{}: eccentricity {} should be at least 0 and below 1", context, body.eccentricity));
+            }
+            if body.period <= 0.0 {
+                problems.push(format!("{}: orbital period {} should be positive", context, body.period));
+            }
+            // Closest approach, measured from the parent's center
+            let periapsis = body.distance * (1.0 - body.eccentricity.clamp(0.0, 1.0));
+            let parent_extent = parent.rings.map_or(parent.radius, |(_, outer)| outer.max(parent.radius));
+            if periapsis <= parent_extent + body.radius {
+                problems.push(format!(
+                    "{}: comes within {} of {:?}'s center, inside their combined radius {}{}",
+                    context,
+                    periapsis,
+                    parent.name,
+                    parent_extent + body.radius,
+                    if parent.rings.is_some() { " counting its rings" } else { "" },
+                ));
+            }
+        }
+
+        problems
+    }
+
+    // Where each body is at `time`, in the order of `bodies`: its place on its own orbit added to
+    // that of everything it orbits
+    pub fn positions(&self, time: f32) -> Vec<Vec3> {
+        self.bodies
+            .iter()
+            .map(|body| {
+                let mut position = Vec3::new(0.0, 0.0, 0.0);
+                let mut current = body;
+                // At most once around the scene, should the chain loop anyway
+                for _ in 0..self.bodies.len() {
+                    let Some(parent) =
+                        current.orbits.as_ref().and_then(|name| self.bodies.iter().find(|other| &other.name == name))
+                    else {
+                        break;
+                    };
+                    position += current.orbit().position(time);
+                    current = parent;
+                }
+                position
+            })
+            .collect()
+    }
+
+    // Whether following `orbits` from a body comes back to it
+    fn orbit_chain_loops(&self, body: &BodyDescription) -> bool {
+        let mut current = body;
+        for _ in 0..self.bodies.len() {
+            let Some(parent) = current.orbits.as_ref().and_then(|name| self.bodies.iter().find(|other| &other.name == name))
+            else {
+                return false;
+            };
+            if std::ptr::eq(parent, body) {
+                return true;
+            }
+            current = parent;
+        }
+        false
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+// Versioned parameters of the configurable shaders, so a description written against an older
+// version keeps rendering the way it did. Bump `SCHEMA_VERSION` whenever a parameter is added,
+// renamed or has its default changed, and record it in the tables below.
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub struct ShaderParam {
+    pub name: &'static str,
+    pub default: f32,
+    pub min: f32,
+    pub max: f32,
+    // First schema version that has the parameter
+    pub since: u32,
+    // What descriptions from before `since` get instead of `default`, when the parameter changed
+    // how the shader looks by default
+    pub legacy: Option<f32>,
+}
+
+// Parameters every planet takes, whatever its shader
+const BODY_PARAMS: [ShaderParam; 2] = [
+    ShaderParam { name: "internal_heat", default: 0.0, min: 0.0, max: 10.0, since: 1, legacy: None },
+    ShaderParam { name: "variation", default: 0.0, min: 0.0, max: 1.0, since: 1, legacy: None },
+];
+
+const ROCKY_PLANET_PARAMS: [ShaderParam; 1] =
+    [ShaderParam { name: "cloud_altitude", default: 0.0, min: 0.0, max: 0.99, since: 1, legacy: None }];
+
+const HOT_JUPITER_PARAMS: [ShaderParam; 1] = [ShaderParam {
+    name: "equilibrium_temperature",
+    default: 1500.0,
+    min: 100.0,
+    max: 5000.0,
+    since: 1,
+    legacy: None,
+}];
+
+// Parameters renamed along the way, as (version of the rename, old name, new name)
+const RENAMES: [(u32, &str, &str); 0] = [];
+
+// Every parameter a shader takes, by canonical shader name
+pub fn shader_params(shader: &str) -> impl Iterator<Item = &'static ShaderParam> {
+    let specific: &'static [ShaderParam] = match shader {
+        "rocky_planet" => &ROCKY_PLANET_PARAMS,
+        "hot_jupiter" => &HOT_JUPITER_PARAMS,
+        _ => &[],
+    };
+    BODY_PARAMS.iter().chain(specific)
+}
+
+// Brings one body's parameters from the schema version they were written against up to the
+// current one: renames are applied in order, and parameters the old version didn't have are filled
+// in. Returns a note per change, or an error for versions this build doesn't know.
+pub fn migrate(shader: &str, version: u32, params: &mut BTreeMap<String, f32>) -> Result<Vec<String>, String> {
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "written for shader schema {}, but this build only knows up to {}",
+            version, SCHEMA_VERSION
+        ));
+    }
+
+    let mut notes = Vec::new();
+    for &(renamed_in, old, new) in &RENAMES {
+        if version < renamed_in
+            && let Some(value) = params.remove(old)
+        {
+            params.insert(new.to_string(), value);
+            notes.push(format!("renamed {} to {}", old, new));
+        }
+    }
+    for param in shader_params(shader) {
+        if params.contains_key(param.name) {
+            continue;
+        }
+        let value = match param.legacy {
+            Some(legacy) if version < param.since => legacy,
+            _ => param.default,
+        };
+        params.insert(param.name.to_string(), value);
+        if version < param.since {
+            notes.push(format!("added {} = {}", param.name, value));
+        }
+    }
+    Ok(notes)
+}
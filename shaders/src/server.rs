@@ -0,0 +1,296 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+
+use crate::app::{BANDED_PIXELS, finish_render, planet_shader_name, render_still};
+use crate::cli::{Options, ServeArgs, parse_size};
+use crate::io::encode_png;
+use crate::json::Json;
+use crate::post::Background;
+use crate::raster::Framebuffer;
+
+// Request bodies are a few hundred bytes of JSON; anything past this is turned away unread
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+// The request line and headers together
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+// Connections served at once; more are turned away until one finishes
+const MAX_CONNECTIONS: usize = 8;
+// How long a client may take to send its request, and to take the reply
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The switches a request may add, with whether each takes a value. They only change how the planet
+// looks: nothing that reads or writes files, sizes the frame (that is the "size" key's job) or
+// sets how much the server spends on a render.
+const REQUEST_SWITCHES: [(&str, bool); 56] = [
+    ("--theme", true),
+    ("--background", true),
+    ("--caption", false),
+    ("--variation", true),
+    ("--light-dir", true),
+    ("--light-pos", true),
+    ("--light-color", true),
+    ("--light-intensity", true),
+    ("--planet-shine", true),
+    ("--ambient", true),
+    ("--wrap", true),
+    ("--fog", true),
+    ("--lighting", true),
+    ("--camera", true),
+    ("--look-at", true),
+    ("--camera-up", true),
+    ("--fov", true),
+    ("--near", true),
+    ("--far", true),
+    ("--cull", true),
+    ("--quality", true),
+    ("--smooth-limb", false),
+    ("--half-res", false),
+    ("--tilt", true),
+    ("--spin-axis", true),
+    ("--cloud-altitude", true),
+    ("--internal-heat", true),
+    ("--equilibrium-temperature", true),
+    ("--bands", true),
+    ("--storms", true),
+    ("--magnetosphere", false),
+    ("--aurora", false),
+    ("--aurora-pole", true),
+    ("--aurora-oval", true),
+    ("--aurora-activity", true),
+    ("--ring-style", true),
+    ("--ring-gaps", true),
+    ("--ringlets", true),
+    ("--ring-thickness", true),
+    ("--shepherd-moons", true),
+    ("--stars", true),
+    ("--limb-refraction", true),
+    ("--toon", false),
+    ("--toon-bands", true),
+    ("--debug", true),
+    ("--palette", true),
+    ("--dither", true),
+    ("--telescope", true),
+    ("--psf-halo", true),
+    ("--psf-threshold", true),
+    ("--sensor", false),
+    ("--exposure", true),
+    ("--sensor-read-noise", true),
+    ("--sensor-hot-pixels", true),
+    ("--sensor-vignetting", true),
+    ("--sensor-seed", true),
+];
+
+// `shaders serve`: renders planets for whoever asks over HTTP on the local machine, so a web page
+// or notebook doesn't start a process (and build the meshes again) for every image. A request is
+// a POST to /render with a JSON body like
+//
+//     {"planet": "volcanic", "size": "512x512", "time": 1.5, "rotation": 0.3,
+//      "switches": ["--theme", "pastel", "--background", "transparent"]}
+//
+// where everything but the planet is optional, and the reply is the PNG. The request's switches
+// go after the ones the server was started with, and are limited to `REQUEST_SWITCHES`. Each
+// connection is served on its own thread, up to `MAX_CONNECTIONS` at once. Browsers only get to
+// read the replies from pages on the origin given with --allow-origin, if any.
+pub fn serve(args: &ServeArgs) -> Result<(), String> {
+    let address = format!("127.0.0.1:{}", args.port);
+    let listener = TcpListener::bind(&address).map_err(|error| format!("Could not listen on {}: {}", address, error))?;
+    info!("Serving renders on http://{}/render", address);
+
+    let switches = Arc::new(args.switches.clone());
+    let origin = Arc::new(args.allow_origin.clone());
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                warn!("Could not accept a connection: {}", error);
+                continue;
+            }
+        };
+        if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            open.fetch_sub(1, Ordering::SeqCst);
+            let busy = Response::text("503 Service Unavailable", "Too many renders at once; try again shortly".to_string());
+            if let Err(error) = stream.set_write_timeout(Some(IO_TIMEOUT)).and_then(|_| respond(stream, busy, origin.as_deref())) {
+                debug!("Connection dropped: {}", error);
+            }
+            continue;
+        }
+        let (switches, origin, open) = (Arc::clone(&switches), Arc::clone(&origin), Arc::clone(&open));
+        thread::spawn(move || {
+            if let Err(error) = handle_connection(stream, &switches, origin.as_deref()) {
+                debug!("Connection dropped: {}", error);
+            }
+            open.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: &'static str, message: String) -> Self {
+        Response { status, content_type: "text/plain; charset=utf-8", body: (message + "\n").into_bytes() }
+    }
+}
+
+// One request and its response; the connection is closed after it
+fn handle_connection(stream: TcpStream, switches: &[String], origin: Option<&str>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut header = (&mut reader).take(MAX_HEADER_BYTES);
+    let mut request_line = String::new();
+    header.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    let mut complete = false;
+    loop {
+        let mut line = String::new();
+        if header.read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.trim_end().is_empty() {
+            complete = true;
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let headers_too_large = !complete && header.limit() == 0;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let response = match (method, path) {
+        _ if headers_too_large => Response::text(
+            "431 Request Header Fields Too Large",
+            format!("The request line and headers are limited to {} bytes", MAX_HEADER_BYTES),
+        ),
+        // Browsers check before posting JSON from another origin
+        ("OPTIONS", "/render") if origin.is_some() => Response { status: "204 No Content", content_type: "text/plain", body: Vec::new() },
+        ("POST", "/render") if content_length > MAX_BODY_BYTES => {
+            Response::text("413 Payload Too Large", format!("Render requests are limited to {} bytes", MAX_BODY_BYTES))
+        }
+        ("POST", "/render") => {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            let start = Instant::now();
+            match render_request(&body, switches) {
+                Ok(frame) => {
+                    info!("Rendered {} in {:.1} ms", frame.label, start.elapsed().as_secs_f64() * 1000.0);
+                    let png = encode_png(&frame.buffer, frame.width, frame.height, frame.transparent);
+                    Response { status: "200 OK", content_type: "image/png", body: png }
+                }
+                Err(error) => {
+                    warn!("Bad render request: {}", error);
+                    Response::text("400 Bad Request", error)
+                }
+            }
+        }
+        (_, "/render") => Response::text("405 Method Not Allowed", "Render requests are POSTed".to_string()),
+        _ => Response::text("404 Not Found", format!("Nothing at {}; render requests go to /render", path)),
+    };
+    respond(stream, response, origin)
+}
+
+fn respond(mut stream: TcpStream, response: Response, origin: Option<&str>) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    if let Some(origin) = origin {
+        write!(
+            stream,
+            "Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Methods: POST, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Content-Type\r\n",
+            origin
+        )?;
+    }
+    write!(stream, "Connection: close\r\n\r\n")?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+// A finished frame for a render request, with what it shows
+pub struct RenderedRequest {
+    pub label: String,
+    pub buffer: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+    pub transparent: bool,
+}
+
+// Renders the planet a request body asks for. Switches that don't parse turn the request away.
+pub fn render_request(body: &[u8], switches: &[String]) -> Result<RenderedRequest, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "The request isn't UTF-8".to_string())?;
+    let request = Json::parse(text).map_err(|error| format!("The request isn't JSON: {}", error))?;
+    if !matches!(request, Json::Object(_)) {
+        return Err("The request should be a JSON object".to_string());
+    }
+
+    let planet = request.get("planet").and_then(Json::as_str).ok_or("The request needs a \"planet\"")?;
+    let shader = planet_shader_name(planet)?;
+    let number = |key: &str| match request.get(key) {
+        None => Ok(0.0),
+        Some(value) => value.as_f64().map(|value| value as f32).ok_or(format!("\"{}\" should be a number", key)),
+    };
+    let (time, rotation) = (number("time")?, number("rotation")?);
+
+    let mut switches = switches.to_vec();
+    let frame_limit = |frame: Framebuffer, size: &str| match frame.checked_pixels() {
+        Some(pixels) if pixels <= BANDED_PIXELS => Ok(()),
+        _ => Err(format!("{} is too large to render here; use `shaders render`", size)),
+    };
+    if let Some(size) = request.get("size") {
+        let size = size.as_str().ok_or("\"size\" should be a string like \"800x600\"")?;
+        let frame = parse_size(size).map_err(|error| format!("\"size\": {}", error))?;
+        // Checked before the switches are parsed, since they build mattes the size of the frame
+        frame_limit(frame, size)?;
+        switches.extend(["--size".to_string(), size.to_string()]);
+    }
+    match request.get("switches") {
+        None => {}
+        Some(Json::Array(items)) => {
+            let mut items = items.iter();
+            while let Some(item) = items.next() {
+                let name = item.as_str().ok_or("\"switches\" should be an array of strings")?;
+                let Some(&(_, takes_value)) = REQUEST_SWITCHES.iter().find(|(allowed, _)| *allowed == name) else {
+                    return Err(format!("{} can't be set in a render request", name));
+                };
+                switches.push(name.to_string());
+                if takes_value {
+                    let value = items.next().and_then(Json::as_str).ok_or(format!("{} expects a value", name))?;
+                    switches.push(value.to_string());
+                }
+            }
+        }
+        Some(_) => return Err("\"switches\" should be an array of strings".to_string()),
+    }
+
+    let options = Options::parse(switches)?;
+    let size = options.render.framebuffer;
+    frame_limit(size, &format!("{}x{}", size.width, size.height))?;
+    let (buffer, metadata) = render_still(&options, shader, time, rotation).ok_or_else(|| format!("Unknown planet shader: {}", planet))?;
+    let (buffer, height) = finish_render(&options, &buffer, &metadata.facts);
+    Ok(RenderedRequest {
+        label: format!("{} at {}x{}", metadata.body, size.width, size.height),
+        buffer,
+        width: size.width,
+        height,
+        transparent: matches!(options.background, Background::Transparent),
+    })
+}
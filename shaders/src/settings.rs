@@ -0,0 +1,368 @@
+use std::ops::Range;
+
+use crate::color::{Color, Theme};
+use crate::geometry::{Camera, Transform};
+use crate::math::{Quat, Vec3};
+use crate::raster::{CullMode, DebugView, Framebuffer, LIMB_REACH, Material, StarField};
+use crate::scene::ShepherdMoon;
+use crate::shaders::{Aurora, RingGap, RingStyle, Ringlet, Storm, ZonalProfile};
+
+// Per-frame render settings shared by every body in the frame
+#[derive(Clone)]
+pub struct RenderSettings {
+    // Size of the frame in pixels
+    pub framebuffer: Framebuffer,
+    // What every scene is seen through: set by --camera, --look-at, --fov and friends, and
+    // swung around by the viewer's mouse controls
+    pub camera: Camera,
+    // The frame's rows to render, for rendering a large frame a band at a time; None for all
+    pub rows: Option<Range<usize>>,
+    // The frame's columns to render, for re-rendering a rectangle of it; None for all
+    pub columns: Option<Range<usize>>,
+    // Which faces of closed bodies the rasterizer skips
+    pub cull: CullMode,
+    // How finely spheres and rings are cut into triangles
+    pub tessellation: Tessellation,
+    // Shade one pixel per 2x2 block and fill the rest from neighbors on the same surface
+    pub half_res_shading: bool,
+    // In animations, shade half the pixels each frame and reproject the rest from the last frame
+    pub checkerboard: bool,
+    // In animations, sample each pixel a little off center from frame to frame and average the
+    // frames, for anti-aliasing without rendering each frame larger
+    pub temporal_aa: bool,
+    // Smooth the limb of a lone sphere by how much of each edge pixel its outline covers
+    pub smooth_limb: bool,
+    // Replace the shaders with a visualization of the mesh or fragment attributes
+    pub debug: Option<DebugView>,
+    // Output a heatmap of how many times each pixel was written instead of the image
+    pub overdraw: bool,
+    // Where the light comes from, its color and strength
+    pub light: Light,
+    // Scene lighting, and replacements for individual bodies keyed by `body_key`
+    pub lighting: Lighting,
+    pub lighting_overrides: Vec<(String, Lighting)>,
+    // Non-photorealistic style applied on top of whatever shaders are used
+    pub toon: Option<ToonStyle>,
+    // Strength of the planet's reflected light on the inner edge of its rings
+    pub planet_shine: f32,
+    // Replaces the default storms of the gas and ice giants
+    pub storms: Option<Vec<Storm>>,
+    // Replaces the default band pattern of the gas and ice giants
+    pub bands: Option<ZonalProfile>,
+    // Named gaps cut into planetary rings
+    pub ring_gaps: Vec<RingGap>,
+    // Narrow, possibly eccentric or clumpy ringlets on top of the main rings
+    pub ringlets: Vec<Ringlet>,
+    // Small moons inside the rings, each clearing a gap of its own
+    pub shepherd_moons: Vec<ShepherdMoon>,
+    // Whether ringed planets get rings or a glowing debris disk
+    pub ring_style: RingStyle,
+    // Overlays the planet's field lines and radiation belts, for diagrams
+    pub magnetosphere: bool,
+    // Auroral ovals on the planets
+    pub aurora: Option<Aurora>,
+    pub stars: Option<StarField>,
+    // Heat from inside planets, like a rogue planet's; glows through cracks and volcanoes
+    pub internal_heat: f32,
+    // Equilibrium temperature of hot Jupiters, in kelvin
+    pub equilibrium_temperature: f32,
+    // Height of rocky planets' cloud layer above the ground, in planet radii; 0 paints them on
+    pub cloud_altitude: f32,
+    // Color remap applied to every body
+    pub theme: Theme,
+    // How far each body of a random system strays from its shader's look, from 0 to 1
+    pub variation: f32,
+    // Vertical thickness of the rings, which is all that shows of them edge-on
+    pub ring_thickness: f32,
+    // Lean of the bodies' spin axes in radians, carrying their bands and rings with them
+    pub axial_tilt: f32,
+    // Direction the spin axis points to before the tilt; None keeps it along y
+    pub spin_axis: Option<Vec3>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            framebuffer: Framebuffer::default(),
+            camera: Camera::default(),
+            rows: None,
+            columns: None,
+            cull: CullMode::Back,
+            tessellation: Tessellation::Normal,
+            half_res_shading: false,
+            checkerboard: false,
+            temporal_aa: false,
+            smooth_limb: false,
+            debug: None,
+            overdraw: false,
+            light: Light::default(),
+            lighting: Lighting::default(),
+            lighting_overrides: Vec::new(),
+            toon: None,
+            planet_shine: 0.25,
+            storms: None,
+            bands: None,
+            ring_gaps: RingGap::saturn(),
+            ringlets: Ringlet::saturn(),
+            shepherd_moons: Vec::new(),
+            ring_style: RingStyle::Rings,
+            magnetosphere: false,
+            aurora: None,
+            stars: None,
+            ring_thickness: 0.01,
+            internal_heat: 0.0,
+            equilibrium_temperature: 1500.0,
+            cloud_altitude: 0.0,
+            variation: 0.0,
+            theme: Theme::Realistic,
+            axial_tilt: 0.0,
+            spin_axis: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ToonStyle {
+    // Number of flat lighting steps
+    pub bands: u32,
+    // Levels per color channel after flattening
+    pub levels: u32,
+}
+
+impl Default for ToonStyle {
+    fn default() -> Self {
+        ToonStyle { bands: 4, levels: 6 }
+    }
+}
+
+impl ToonStyle {
+    // Rounds up so the darkest band still shows the surface colors
+    pub fn band(&self, intensity: f32) -> f32 {
+        let bands = self.bands.max(1) as f32;
+        ((intensity * bands).ceil() / bands).clamp(1.0 / bands, 1.0)
+    }
+
+    // Posterizes each channel in a square-root space so dark colors keep some steps
+    pub fn flatten(&self, pixel: u32) -> u32 {
+        let steps = (self.levels.max(2) - 1) as f32;
+        let channel = |shift: u32| {
+            let value = (((pixel >> shift) & 0xFF) as f32 / 255.0).sqrt();
+            ((value * steps).round() / steps).powi(2).mul_add(255.0, 0.5) as u32
+        };
+        (pixel & 0xFF000000) | channel(16) << 16 | channel(8) << 8 | channel(0)
+    }
+}
+
+// Segments around a sphere (and pole to pole) and around a ring at normal quality
+pub const SPHERE_SEGMENTS: usize = 50;
+pub const RING_SEGMENTS: usize = 100;
+
+// Mesh quality, scaling every segment count alike so bodies, moons and rings stay in proportion
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum Tessellation {
+    Draft,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Tessellation {
+    pub fn parse(value: &str) -> Option<Tessellation> {
+        match value {
+            "draft" => Some(Tessellation::Draft),
+            "normal" => Some(Tessellation::Normal),
+            "high" => Some(Tessellation::High),
+            _ => None,
+        }
+    }
+
+    // A mesh of `segments` at normal quality, at this one. Even the smallest moons keep enough
+    // to stay round in draft.
+    pub fn segments(self, segments: usize) -> usize {
+        match self {
+            Tessellation::Draft => (segments / 2).max(6.min(segments)),
+            Tessellation::Normal => segments,
+            Tessellation::High => segments * 2,
+        }
+    }
+}
+
+impl RenderSettings {
+    // `segments` at normal quality scaled to the chosen tessellation
+    pub fn segments(&self, segments: usize) -> usize {
+        self.tessellation.segments(segments)
+    }
+
+    // Where a body spun by `rotation` ends up: tilted, then turned so its axis points along `spin_axis`
+    pub fn body_transform(&self, rotation: f32) -> Transform {
+        let orientation = self.spin_axis.map_or(Quat::identity(), |axis| Quat::between(&Vec3::new(0.0, 1.0, 0.0), &axis));
+        Transform::spin(rotation).tilted(self.axial_tilt).oriented(orientation)
+    }
+
+    // How far past the edge of a band or region the pixels inside it look: stars bent around a
+    // limb, toon outlines, half-res fill-in and the smoothed limb all take neighboring pixels into
+    // account
+    pub fn neighbor_reach(&self) -> usize {
+        let stars = if self.stars.is_some() { 2 * LIMB_REACH as usize } else { 0 };
+        let neighbors = if self.toon.is_some() || self.half_res_shading { 2 } else { 0 };
+        let limb = if self.smooth_limb { 3 } else { 0 };
+        stars.max(neighbors).max(limb)
+    }
+
+    // `range` of rows or columns (of `end`) with the rows around it that its pixels depend on, so
+    // rendering just those gives the same pixels in `range` as the whole frame. Half-res shading
+    // shades the even pixels, so the padded range starts on an even one as the frame does.
+    pub fn padded(&self, range: &Range<usize>, end: usize) -> Range<usize> {
+        let reach = self.neighbor_reach();
+        let start = range.start.saturating_sub(reach);
+        let start = if self.half_res_shading { start - start % 2 } else { start };
+        start..(range.end + reach).min(end)
+    }
+
+    pub fn lighting_for(&self, body: &str) -> Lighting {
+        let body = body_key(body);
+        self.lighting_overrides
+            .iter()
+            .find(|(name, _)| name == body)
+            .map_or(self.lighting, |(_, lighting)| *lighting)
+    }
+
+    // The light as a body sees it. The sun shines by itself, so it keeps its own colors, and a
+    // rogue planet has no star near enough to light it beyond the ambient starlight. A light at a
+    // point is usually the sun itself, which would only see it from the inside; it is shaded as
+    // if lit from the viewer, darkening toward the limb.
+    pub fn light_for(&self, body: &str) -> Light {
+        match body_key(body) {
+            "sun" => {
+                let source = match self.light.source {
+                    LightSource::Position(_) => LightSource::Direction(Vec3::new(0.0, 0.0, 1.0)),
+                    source => source,
+                };
+                Light { color: Color::new(255, 255, 255), source, ..self.light }
+            }
+            "rogue_planet" => Light { intensity: 0.0, ..self.light },
+            _ => self.light,
+        }
+    }
+
+    // Stars have no aurora
+    pub fn aurora_for(&self, body: &str) -> Option<Aurora> {
+        self.aurora.filter(|_| body_key(body) != "sun")
+    }
+
+    // Surface of a planet or star with its lighting and the internal heat from the settings
+    pub fn solid_material_for(&self, body: &str) -> Material {
+        Material::solid(self.lighting_for(body)).heated(self.internal_heat)
+    }
+}
+
+// A light infinitely far away along a direction, or at a point in the world so that bodies in
+// different places see it from different angles
+#[derive(Clone, Copy)]
+pub enum LightSource {
+    Direction(Vec3),
+    Position(Vec3),
+}
+
+#[derive(Clone, Copy)]
+pub struct Light {
+    pub source: LightSource,
+    pub color: Color,
+    // Scales the lit intensity of every fragment
+    pub intensity: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Light {
+            source: LightSource::Direction(Vec3::new(0.5, 0.5, 1.0)),
+            color: Color::new(255, 255, 255),
+            intensity: 1.0,
+        }
+    }
+}
+
+impl Light {
+    // Unit vector from `point` towards the light
+    pub fn direction_from(&self, point: &Vec3) -> Vec3 {
+        match self.source {
+            LightSource::Direction(direction) => direction.normalize(),
+            LightSource::Position(position) => (position - *point).normalize(),
+        }
+    }
+
+    pub fn tint(&self, color: Color) -> Color {
+        let channel = |value: u8, light: u8| (value as u32 * light as u32 / 255) as u8;
+        Color::new(channel(color.r, self.color.r), channel(color.g, self.color.g), channel(color.b, self.color.b))
+    }
+}
+
+// How surfaces respond to the light direction
+#[derive(Clone, Copy)]
+pub struct Lighting {
+    // Brightness of faces turned fully away from the light
+    pub ambient: f32,
+    // How far light wraps past the terminator; 0 is plain Lambert, 1 lights the whole sphere
+    pub wrap: f32,
+    // Exponential dimming per unit of distance behind `fog_start` (a z value), so far bodies in
+    // wide shots fall off into the background; 0 disables it
+    pub fog_density: f32,
+    pub fog_start: f32,
+    // Strength of specular highlights, for the shaders that have them, and how rough the surface
+    // is: 0 is a mirror-sharp glint, 1 spreads it over the whole lit side
+    pub specular: f32,
+    pub roughness: f32,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting { ambient: 0.2, wrap: 0.0, fog_density: 0.0, fog_start: 1.0, specular: 1.0, roughness: 0.3 }
+    }
+}
+
+impl Lighting {
+    // Ambient light comes from everywhere else in the sky, so it stays when the light is dimmed
+    pub fn intensity(&self, normal: &Vec3, light_dir: &Vec3, light_intensity: f32) -> f32 {
+        let diffuse = ((normal.dot(light_dir) + self.wrap) / (1.0 + self.wrap)).max(0.0);
+        diffuse * (1.0 - self.ambient) * light_intensity + self.ambient
+    }
+
+    // Multiplier for the intensity of a fragment at depth `z`
+    pub fn fog(&self, z: f32) -> f32 {
+        if self.fog_density <= 0.0 {
+            return 1.0;
+        }
+        (-self.fog_density * (self.fog_start - z).max(0.0)).exp()
+    }
+
+    // "ambient=0.05,wrap=0.3,fog=0.5,specular=1,roughness=0.3"; keys left out keep their value
+    // from `base`
+    pub fn parse(value: &str, base: Lighting) -> Option<Lighting> {
+        let mut lighting = base;
+        for part in value.split(',') {
+            let (key, number) = part.split_once('=')?;
+            let number: f32 = number.parse().ok()?;
+            match key {
+                "ambient" => lighting.ambient = number.clamp(0.0, 1.0),
+                "wrap" => lighting.wrap = number.clamp(0.0, 1.0),
+                "fog" => lighting.fog_density = number.max(0.0),
+                "specular" => lighting.specular = number.max(0.0),
+                "roughness" => lighting.roughness = number.clamp(0.01, 1.0),
+                _ => return None,
+            }
+        }
+        Some(lighting)
+    }
+}
+
+// Canonical name of a body for per-body settings, accepting the same aliases as `shader_by_name`
+pub fn body_key(name: &str) -> &str {
+    match name {
+        "rocky" => "rocky_planet",
+        "desert" => "desert_planet",
+        "volcanic" => "volcanic_planet",
+        "rogue" => "rogue_planet",
+        "carbon" => "carbon_planet",
+        other => other,
+    }
+}
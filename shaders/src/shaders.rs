@@ -0,0 +1,131 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::math::{Rng, Vec3};
+use crate::settings::{RenderSettings, body_key};
+
+// The shaders themselves live in `kernel`; these are the parts that need the render settings
+pub use crate::kernel::shaders::*;
+
+// A planet shader that can be shared between render threads
+pub type PlanetShader<'a> = dyn Fn(&Fragment) -> Color + Sync + 'a;
+
+// A shader by name with the parameters from the settings applied, for the shaders that have any
+pub fn configured_shader<'a>(name: &str, settings: &'a RenderSettings) -> Option<Box<PlanetShader<'a>>> {
+    let customized = settings.storms.is_some() || settings.bands.is_some();
+    match name {
+        "gas_giant" if customized => {
+            let storms = settings.storms.as_deref().unwrap_or(&GAS_GIANT_STORMS);
+            let bands = settings.bands.as_ref().unwrap_or(&GAS_GIANT_BANDS);
+            Some(Box::new(move |fragment: &Fragment| gas_giant_shader_with(fragment, storms, bands)))
+        }
+        "ice_giant" if customized => {
+            let storms = settings.storms.as_deref().unwrap_or(&ICE_GIANT_STORMS);
+            let bands = settings.bands.as_ref().unwrap_or(&ICE_GIANT_BANDS);
+            Some(Box::new(move |fragment: &Fragment| ice_giant_shader_with(fragment, storms, bands)))
+        }
+        "rocky_planet" if settings.cloud_altitude > 0.0 => {
+            let altitude = settings.cloud_altitude;
+            Some(Box::new(move |fragment: &Fragment| rocky_planet_shader_with(fragment, altitude)))
+        }
+        "hot_jupiter" => {
+            let temperature = settings.equilibrium_temperature;
+            Some(Box::new(move |fragment: &Fragment| hot_jupiter_shader_with(fragment, temperature)))
+        }
+        _ => shader_by_name(name).map(|shader| Box::new(shader) as Box<PlanetShader>),
+    }
+}
+
+// Deterministic jitter for one body, so several bodies with the same shader don't come out as
+// copies. `amount` runs from 0 (the shader as written) to 1 (the widest sensible spread); `seed`
+// picks where in that range each knob lands.
+#[derive(Clone, Copy)]
+pub struct Variation {
+    pub amount: f32,
+    pub seed: u64,
+}
+
+// The knobs one seed draws, each already scaled by the amount
+struct VariationDraws {
+    // Turn of the pattern about the spin axis, and about an equatorial axis for bodies whose
+    // patterns don't follow latitude
+    spin: f32,
+    tip: f32,
+    time_offset: f32,
+    hue_degrees: f32,
+    saturation: f32,
+    brightness: f32,
+    // Multiplier for the physical parameters in the settings
+    scale: f32,
+}
+
+impl Variation {
+    fn draws(&self) -> VariationDraws {
+        let mut rng = Rng::new(self.seed ^ 0x5641_5249_4154_494F);
+        let mut signed = || rng.range(-1.0, 1.0) * self.amount;
+        VariationDraws {
+            spin: signed() * PI,
+            tip: signed() * PI,
+            time_offset: signed() * 50.0,
+            hue_degrees: signed() * 35.0,
+            saturation: 1.0 + signed() * 0.3,
+            brightness: 1.0 + signed() * 0.15,
+            scale: 1.0 + signed() * 0.3,
+        }
+    }
+
+    // The settings with this body's physical parameters jittered: how hot a hot Jupiter runs,
+    // how much heat escapes from inside, and how high the clouds sit
+    pub fn settings(&self, settings: &RenderSettings) -> RenderSettings {
+        let scale = self.draws().scale;
+        RenderSettings {
+            equilibrium_temperature: settings.equilibrium_temperature * scale,
+            internal_heat: settings.internal_heat * scale,
+            cloud_altitude: (settings.cloud_altitude * scale).min(0.99),
+            ..settings.clone()
+        }
+    }
+}
+
+// Wraps a body's shader with its variation. Banded bodies only have their pattern turned about
+// the spin axis so the bands stay level; everything else gets turned freely, which lands a
+// different stretch of noise in view. The light and view directions turn with the point so
+// lighting-dependent shading stays put.
+pub fn varied<'a>(
+    shader: impl Fn(&Fragment) -> Color + Sync + 'a,
+    body: &str,
+    variation: Variation,
+) -> impl Fn(&Fragment) -> Color + Sync + 'a {
+    let draws = variation.draws();
+    let banded = matches!(body_key(body), "gas_giant" | "ice_giant" | "hot_jupiter" | "sun");
+    let tip = if banded { 0.0 } else { draws.tip };
+    let off = variation.amount <= 0.0;
+    move |fragment| {
+        if off {
+            return shader(fragment);
+        }
+        let turn = |v: &Vec3| v.rotate_x(tip).rotate_y(draws.spin);
+        let fragment = Fragment {
+            object_position: turn(&fragment.object_position),
+            light_dir: turn(&fragment.light_dir),
+            view_dir: turn(&fragment.view_dir),
+            time: fragment.time + draws.time_offset,
+            ..*fragment
+        };
+        let color = shader(&fragment);
+
+        // Hue turned about the gray axis, then saturation and brightness about the gray level
+        let (r, g, b) = (color.r as f32 / 255.0, color.g as f32 / 255.0, color.b as f32 / 255.0);
+        let (cos, sin) = (draws.hue_degrees.to_radians().cos(), draws.hue_degrees.to_radians().sin());
+        let k = (1.0 - cos) / 3.0;
+        let s = sin / 3.0f32.sqrt();
+        let turned = [
+            r * (cos + k) + g * (k - s) + b * (k + s),
+            r * (k + s) + g * (cos + k) + b * (k - s),
+            r * (k - s) + g * (k + s) + b * (cos + k),
+        ];
+        let gray = (turned[0] + turned[1] + turned[2]) / 3.0;
+        let adjust = |c: f32| (gray + (c - gray) * draws.saturation) * draws.brightness;
+        Color::from_float(adjust(turned[0]), adjust(turned[1]), adjust(turned[2]))
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use crate::geometry::Camera;
+use crate::kernel::easing::{EASING_NAMES, Easing, lerp, slerp};
+use crate::json::Json;
+use crate::math::Vec3;
+use crate::settings::{LightSource, RenderSettings};
+
+// Values that can be blended from one key to the next: `t` runs from 0 at `self` to 1 at `to`
+pub trait Interpolate: Copy {
+    fn interpolate(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, to: &f32, t: f32) -> f32 {
+        lerp(*self, *to, t)
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(&self, to: &Vec3, t: f32) -> Vec3 {
+        *self + (*to - *self) * t
+    }
+}
+
+// A vector keyed for where it points, like the light's direction: it swings round between keys
+// rather than cutting across
+#[derive(Clone, Copy, Debug)]
+pub struct Direction(pub Vec3);
+
+impl Interpolate for Direction {
+    fn interpolate(&self, to: &Direction, t: f32) -> Direction {
+        Direction(slerp(&self.0, &to.0, t))
+    }
+}
+
+// One animated value, keyed at points in shader time. Before the first key it holds the first
+// value and after the last the last; in between it runs from key to key along each key's easing.
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    // In time order, no two at the same time; the easing is the one on the way to the next key
+    pub keys: Vec<(f32, T, Easing)>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Track { keys: Vec::new() }
+    }
+}
+
+impl<T: Interpolate> Track<T> {
+    // Keys `value` at `time`, replacing any key already there, and runs straight on to the next
+    pub fn key(&mut self, time: f32, value: T) -> &mut Self {
+        self.key_eased(time, value, Easing::Linear)
+    }
+
+    // Keys `value` at `time` and eases from it to the next key
+    pub fn key_eased(&mut self, time: f32, value: T, easing: Easing) -> &mut Self {
+        match self.keys.binary_search_by(|(key_time, _, _)| key_time.total_cmp(&time)) {
+            Ok(index) => self.keys[index] = (time, value, easing),
+            Err(index) => self.keys.insert(index, (time, value, easing)),
+        }
+        self
+    }
+
+    // The value at `time`, or None for a track with no keys
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let after = self.keys.partition_point(|(key_time, _, _)| *key_time <= time);
+        match (after.checked_sub(1).map(|index| &self.keys[index]), self.keys.get(after)) {
+            (Some((from_time, from, easing)), Some((to_time, to, _))) => {
+                Some(from.interpolate(to, easing.apply((time - from_time) / (to_time - from_time))))
+            }
+            (Some((_, value, _)), None) | (None, Some((_, value, _))) => Some(*value),
+            (None, None) => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+// The shader parameters a timeline can key, as the scene files name them
+pub const TIMELINE_PARAMS: [&str; 3] = ["internal_heat", "cloud_altitude", "equilibrium_temperature"];
+
+// Keyframed changes to the render settings over an animation: where the camera is, where the light
+// comes from and the shader parameters. Sampled at each frame's shader time; what it doesn't key
+// stays as the switches set it.
+#[derive(Clone, Default, Debug)]
+pub struct Timeline {
+    pub camera_position: Track<Vec3>,
+    pub light_direction: Track<Direction>,
+    // By parameter name, one of `TIMELINE_PARAMS`
+    pub params: BTreeMap<String, Track<f32>>,
+}
+
+impl Timeline {
+    // A timeline from JSON, every track a list of [time, value] keys, or [time, value, easing] to
+    // ease out of that key by one of `EASING_NAMES` rather than going straight:
+    //
+    //     {
+    //         "camera_position": [[0, [0, 0, 10], "ease_in_out"], [8, [6, 2, 8]]],
+    //         "light_direction": [[0, [1, 0.5, 1]], [8, [-1, 0.5, 1]]],
+    //         "params": { "cloud_altitude": [[0, 0], [4, 0.08]] }
+    //     }
+    pub fn parse(text: &str) -> Result<Timeline, String> {
+        let json = Json::parse(text)?;
+        let Json::Object(members) = &json else {
+            return Err("a timeline should be an object of tracks".to_string());
+        };
+        let mut timeline = Timeline::default();
+        for (name, value) in members {
+            match name.as_str() {
+                "camera_position" => timeline.camera_position = track(name, value, vector)?,
+                "light_direction" => {
+                    timeline.light_direction = track(name, value, |json| vector(json).map(Direction))?
+                }
+                "params" => {
+                    let Json::Object(params) = value else {
+                        return Err("params should be an object of tracks".to_string());
+                    };
+                    for (param, value) in params {
+                        if !TIMELINE_PARAMS.contains(&param.as_str()) {
+                            return Err(format!("{} can't be keyed; try one of {}", param, TIMELINE_PARAMS.join(", ")));
+                        }
+                        timeline.params.insert(param.clone(), track(param, value, |json| json.as_f64().map(|v| v as f32))?);
+                    }
+                }
+                other => return Err(format!("unknown track {}", other)),
+            }
+        }
+        Ok(timeline)
+    }
+
+    // Whether `camera` can be moved along the keyed positions: the view needs a direction to face
+    // and a way up at every position it passes through, keys and the way between them alike, so
+    // none of them can be on the line through the target along `up`
+    pub fn check_camera(&self, camera: &Camera) -> Result<(), String> {
+        let up = camera.up.normalize();
+        // Distance from that line, as a vector at right angles to it
+        let off_axis = |p: Vec3| up.cross(&(p - camera.target));
+        for (time, position, _) in &self.camera_position.keys {
+            if (*position - camera.target).length() < 1e-6 {
+                return Err(format!("camera_position: the key at {} is on the camera's target", time));
+            }
+            if off_axis(*position).length() < 1e-6 {
+                return Err(format!("camera_position: the key at {} looks straight along --camera-up", time));
+            }
+        }
+        for pair in self.camera_position.keys.windows(2) {
+            let [(from_time, from, easing), (to_time, to, _)] = pair else { continue };
+            // Easings that overshoot carry the camera on past the next key along the same line
+            let reach = (0..=100).map(|step| easing.apply(step as f32 / 100.0));
+            let (low, high) = reach.fold((0.0f32, 1.0f32), |(low, high), s| (low.min(s), high.max(s)));
+            // Closest approach of the way between the keys to the line
+            let (start, along) = (off_axis(*from), up.cross(&(*to - *from)));
+            let s = match along.dot(&along) {
+                0.0 => low,
+                length => (-start.dot(&along) / length).clamp(low, high),
+            };
+            if (start + along * s).length() < 1e-6 {
+                return Err(format!(
+                    "camera_position: between the keys at {} and {} the camera passes through its target or along --camera-up",
+                    from_time, to_time
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Timeline, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        Timeline::parse(&text)
+    }
+
+    // `settings` as the timeline has them at `time`
+    pub fn apply(&self, time: f32, settings: &RenderSettings) -> RenderSettings {
+        let mut settings = settings.clone();
+        if let Some(position) = self.camera_position.sample(time) {
+            settings.camera.position = position;
+        }
+        if let Some(Direction(direction)) = self.light_direction.sample(time) {
+            settings.light.source = LightSource::Direction(direction);
+        }
+        for (param, track) in &self.params {
+            let Some(value) = track.sample(time) else { continue };
+            match param.as_str() {
+                "internal_heat" => settings.internal_heat = value,
+                "cloud_altitude" => settings.cloud_altitude = value,
+                "equilibrium_temperature" => settings.equilibrium_temperature = value,
+                _ => {}
+            }
+        }
+        settings
+    }
+}
+
+fn vector(json: &Json) -> Option<Vec3> {
+    match json {
+        Json::Array(items) => match items.iter().map(Json::as_f64).collect::<Option<Vec<_>>>()?[..] {
+            // Numbers past what f32 holds would leave the camera or light nowhere
+            [x, y, z] if [x, y, z].iter().all(|v| (*v as f32).is_finite()) => Some(Vec3::new(x as f32, y as f32, z as f32)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// A track from its list of [time, value] and [time, value, easing] keys, in any order
+fn track<T: Interpolate>(name: &str, json: &Json, value: impl Fn(&Json) -> Option<T>) -> Result<Track<T>, String> {
+    let Json::Array(keys) = json else {
+        return Err(format!("{} should be a list of [time, value] keys", name));
+    };
+    let mut track = Track::default();
+    for key in keys {
+        let Json::Array(parts) = key else {
+            return Err(format!("{}: every key should be [time, value]", name));
+        };
+        let easing = match parts.get(2) {
+            None => Some(Easing::Linear),
+            Some(Json::String(easing)) => Easing::parse(easing),
+            Some(_) => None,
+        };
+        let Some(easing) = easing else {
+            return Err(format!("{}: a key's easing should be one of {}", name, EASING_NAMES.join(", ")));
+        };
+        let parsed = match parts.len() {
+            2 | 3 => parts[0].as_f64().zip(value(&parts[1])),
+            _ => None,
+        };
+        match parsed {
+            Some((time, value)) if time.is_finite() => track.key_eased(time as f32, value, easing),
+            _ => return Err(format!("{}: every key should be [time, value]", name)),
+        };
+    }
+    Ok(track)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_run_between_keys_and_hold_past_them() {
+        let mut track = Track::default();
+        track.key(2.0, 10.0).key(0.0, 0.0);
+        assert_eq!(track.sample(-1.0), Some(0.0));
+        assert_eq!(track.sample(0.5), Some(2.5));
+        assert_eq!(track.sample(1.0), Some(5.0));
+        assert_eq!(track.sample(3.0), Some(10.0));
+        assert_eq!(Track::<f32>::default().sample(1.0), None);
+    }
+
+    #[test]
+    fn keys_ease_on_the_way_to_the_next() {
+        let mut track = Track::default();
+        track.key_eased(0.0, 0.0, Easing::EaseIn).key(4.0, 8.0);
+        assert_eq!(track.sample(2.0), Some(1.0));
+        assert_eq!(track.sample(4.0), Some(8.0));
+    }
+
+    #[test]
+    fn timelines_key_params_by_name() {
+        let timeline = Timeline::parse(r#"{ "params": { "internal_heat": [[0, 1], [10, 3, "linear"]] } }"#).unwrap();
+        assert_eq!(timeline.params["internal_heat"].sample(5.0), Some(2.0));
+        assert!(Timeline::parse(r#"{ "params": { "albedo": [[0, 1]] } }"#).is_err());
+        assert!(Timeline::parse(r#"{ "zoom": [] }"#).is_err());
+    }
+
+    #[test]
+    fn camera_tracks_stay_off_the_target_and_the_up_axis() {
+        let check = |text: &str| Timeline::parse(text).unwrap().check_camera(&Camera::default());
+        assert!(check(r#"{ "camera_position": [[0, [0, 0, 10]], [4, [6, 2, 8]]] }"#).is_ok());
+        assert!(check(r#"{ "camera_position": [[0, [0, 0, 0]]] }"#).unwrap_err().contains("on the camera's target"));
+        assert!(check(r#"{ "camera_position": [[0, [0, 5, 0]]] }"#).unwrap_err().contains("along --camera-up"));
+        // Straight through the target from one side to the other
+        let through = check(r#"{ "camera_position": [[0, [0, 0, 10]], [4, [0, 0, -10]]] }"#);
+        assert!(through.unwrap_err().contains("between the keys at 0 and 4"));
+        // Stopping short of the target is fine, unless the easing overshoots past it
+        assert!(check(r#"{ "camera_position": [[0, [0, 0, 10]], [4, [0, 0, 2]]] }"#).is_ok());
+        assert!(check(r#"{ "camera_position": [[0, [0, 0, 10], "elastic"], [4, [0, 0, 2]]] }"#).is_err());
+        assert!(Timeline::parse(r#"{ "camera_position": [[0, [0, 0, 1e99]]] }"#).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use std::f32::consts::FRAC_PI_2;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+
+use crate::app::{display_name, finish_render, planet_shader_name};
+use crate::cli::{Options, ViewArgs};
+use crate::geometry::Camera;
+use crate::mesh_cache;
+use crate::raster::Framebuffer;
+use crate::scene::render_sphere;
+use crate::settings::{RenderSettings, SPHERE_SEGMENTS};
+use crate::shaders::configured_shader;
+
+// Radians the camera turns per pixel dragged, and how much one step of the scroll wheel zooms
+const DRAG_RADIANS_PER_PIXEL: f32 = 0.01;
+const ZOOM_PER_SCROLL_STEP: f32 = 1.1;
+
+// How much smaller the quick passes after a change are, coarsest first, how many rows each
+// full-resolution band has, and how long each window frame may spend refining before it's shown
+const PREVIEW_DIVISORS: [usize; 2] = [4, 2];
+const REFINE_BAND_ROWS: usize = 32;
+const REFINE_BUDGET: Duration = Duration::from_millis(15);
+
+// `shaders view`: one planet in a window, rendered again every frame with the shader time
+// following the clock. Dragging with the left button orbits the camera around its target and the
+// scroll wheel zooms; R puts the camera back where the switches had it. Space pauses, Escape closes the window.
+// Frames too slow to draw at once are built up progressively: a change shows at low resolution
+// straight away and sharpens a band at a time over the next window frames.
+pub fn view(options: &Options, args: &ViewArgs) -> Result<(), String> {
+    let shader_name = planet_shader_name(&args.planet)?;
+    let shader = configured_shader(shader_name, &options.render)
+        .ok_or_else(|| format!("Unknown planet shader: {}", args.planet))?;
+    let size = options.render.framebuffer;
+    let title = display_name(shader_name);
+    let mut window = Window::new(&title, size.width, size.height, WindowOptions::default())
+        .map_err(|error| format!("Could not open a window: {}", error))?;
+    window.set_target_fps(60);
+
+    let sphere_mesh = mesh_cache::sphere(1.0, options.render.segments(SPHERE_SEGMENTS));
+    let mut settings = options.render.clone();
+    let (mut orbit, mut drag_from) = (Orbit::default(), None);
+    let mut time = 0.0;
+    let mut paused = false;
+    let mut last_frame = Instant::now();
+    let mut progressive = Progressive::new(size);
+    // Frames since the title's frame rate was last updated
+    let (mut frames, mut counted_since) = (0, Instant::now());
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let elapsed = last_frame.elapsed().as_secs_f32();
+        last_frame = Instant::now();
+        let mut changed = !paused;
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            paused = !paused;
+        }
+        if !paused {
+            time += elapsed * args.speed;
+        }
+        if window.is_key_pressed(Key::R, KeyRepeat::No) {
+            orbit = Orbit::default();
+            changed = true;
+        }
+        changed |= orbit_camera(&window, &mut orbit, &mut drag_from);
+        let camera = options.render.camera.orbited(orbit.yaw, orbit.pitch);
+        settings.camera = Camera { zoom: camera.zoom * orbit.zoom, ..camera };
+
+        if changed {
+            progressive.restart();
+        }
+        progressive.refine(&settings, |settings| {
+            render_sphere(&sphere_mesh, &shader, shader_name, time, time * args.spin, settings)
+        });
+        // Captions would change the frame's height, so the window shows the frame without one
+        let (buffer, _) = finish_render(options, &progressive.buffer, &[]);
+        window
+            .update_with_buffer(&buffer, size.width, size.height)
+            .map_err(|error| format!("Could not draw the frame: {}", error))?;
+
+        frames += 1;
+        if counted_since.elapsed() >= Duration::from_secs(1) {
+            let fps = frames as f64 / counted_since.elapsed().as_secs_f64();
+            let state = if paused { ", paused" } else { "" };
+            window.set_title(&format!("{} - t = {:.1} s, {:.0} fps{}", title, time, fps, state));
+            (frames, counted_since) = (0, Instant::now());
+        }
+    }
+    Ok(())
+}
+
+// How far the mouse has moved the camera from where the switches put it
+struct Orbit {
+    yaw: f32,
+    pitch: f32,
+    zoom: f32,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Orbit { yaw: 0.0, pitch: 0.0, zoom: 1.0 }
+    }
+}
+
+// Turns the camera by the distance dragged since the last frame, stopping at the poles, and zooms
+// with the scroll wheel. Returns whether the camera moved.
+fn orbit_camera(window: &Window, orbit: &mut Orbit, drag_from: &mut Option<(f32, f32)>) -> bool {
+    let mut moved = false;
+    let mouse = window.get_mouse_pos(MouseMode::Discard);
+    *drag_from = match (window.get_mouse_down(MouseButton::Left), mouse, *drag_from) {
+        (true, Some((x, y)), Some((from_x, from_y))) => {
+            orbit.yaw += (x - from_x) * DRAG_RADIANS_PER_PIXEL;
+            orbit.pitch = (orbit.pitch + (y - from_y) * DRAG_RADIANS_PER_PIXEL).clamp(-FRAC_PI_2, FRAC_PI_2);
+            moved = (x, y) != (from_x, from_y);
+            Some((x, y))
+        }
+        (true, position, _) => position,
+        (false, ..) => None,
+    };
+    if let Some((_, steps)) = window.get_scroll_wheel() {
+        orbit.zoom = (orbit.zoom * ZOOM_PER_SCROLL_STEP.powf(steps)).clamp(0.25, 8.0);
+        moved = true;
+    }
+    moved
+}
+
+enum Refinement {
+    // The whole frame at this fraction of the window's size, scaled up
+    Coarse(usize),
+    // These rows at full resolution
+    Band(Range<usize>),
+}
+
+// The frame being built up since the view last changed: a quick coarse pass or two, then the
+// full-resolution bands, nearest the middle of the window (where the planet is) first
+struct Progressive {
+    size: Framebuffer,
+    steps: Vec<Refinement>,
+    done: usize,
+    buffer: Vec<u32>,
+    // Rendering time spent on the frame so far, and on the last one completed
+    spent: Duration,
+    last_complete: Option<Duration>,
+}
+
+impl Progressive {
+    fn new(size: Framebuffer) -> Self {
+        let mut progressive = Progressive {
+            size,
+            steps: Vec::new(),
+            done: 0,
+            buffer: vec![0; size.pixels()],
+            spent: Duration::ZERO,
+            last_complete: None,
+        };
+        progressive.restart();
+        progressive
+    }
+
+    // Starts the frame over. When the last one took no longer than a window frame, there's
+    // nothing to gain from the coarse passes, so it goes straight to full resolution.
+    fn restart(&mut self) {
+        let height = self.size.height;
+        let mut bands: Vec<Range<usize>> =
+            (0..height).step_by(REFINE_BAND_ROWS).map(|start| start..(start + REFINE_BAND_ROWS).min(height)).collect();
+        let offset = |band: &Range<usize>| (band.start + band.end).abs_diff(height);
+        bands.sort_by_key(offset);
+
+        let quick = self.last_complete.is_some_and(|spent| spent <= REFINE_BUDGET);
+        let coarse = PREVIEW_DIVISORS.iter().filter(|_| !quick).map(|&divisor| Refinement::Coarse(divisor));
+        self.steps = coarse.chain(bands.into_iter().map(Refinement::Band)).collect();
+        self.done = 0;
+        self.spent = Duration::ZERO;
+    }
+
+    // Takes the frame as far as the budget allows, always at least one step
+    fn refine(&mut self, settings: &RenderSettings, render: impl Fn(&RenderSettings) -> Vec<u32>) {
+        if self.done == self.steps.len() {
+            return;
+        }
+        let started = Instant::now();
+        while self.done < self.steps.len() && (self.done == 0 || started.elapsed() < REFINE_BUDGET) {
+            let Framebuffer { width, height } = self.size;
+            match &self.steps[self.done] {
+                Refinement::Coarse(divisor) => {
+                    let small = Framebuffer::new((width / divisor).max(1), (height / divisor).max(1));
+                    let coarse = render(&RenderSettings { framebuffer: small, ..settings.clone() });
+                    for y in 0..height {
+                        let row = y * small.height / height * small.width;
+                        for x in 0..width {
+                            self.buffer[y * width + x] = coarse[row + x * small.width / width];
+                        }
+                    }
+                }
+                Refinement::Band(rows) => {
+                    let band = render(&RenderSettings { rows: Some(rows.clone()), ..settings.clone() });
+                    self.buffer[rows.start * width..rows.end * width].copy_from_slice(&band);
+                }
+            }
+            self.done += 1;
+        }
+        self.spent += started.elapsed();
+        if self.done == self.steps.len() {
+            self.last_complete = Some(self.spent);
+        }
+    }
+}